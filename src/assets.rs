@@ -4,5 +4,80 @@
 //! `src/assets/` and included at compile time to keep the Rust source
 //! manageable while still producing a single self-contained binary.
 
+use std::sync::OnceLock;
+
 pub const CDV_CSS: &str = include_str!("assets/cdv.css");
 pub const CDV_JS: &str = include_str!("assets/cdv.js");
+/// Offline cache for served and exported sites, registered from `cdv.js`.
+pub const CDV_SW_JS: &str = include_str!("assets/cdv-sw.js");
+
+/// Directory that holds the linked assets, below the server root when
+/// serving and below the site root of exports.
+pub const ASSETS_DIR: &str = "cdv-assets";
+
+/// Route of the linked assets in serve mode.
+pub const ASSETS_PATH: &str = "/cdv-assets/";
+
+/// `cdv.<hash>.css`; the name changes with the content, so the file can be
+/// cached without revalidation.
+pub fn css_file_name() -> &'static str {
+    &file_names().0
+}
+
+/// `cdv.<hash>.js`, see [`css_file_name`].
+pub fn js_file_name() -> &'static str {
+    &file_names().1
+}
+
+/// Content and MIME type of the linked asset called `name`. Names of earlier
+/// builds are unknown, so a stale page cannot pick up newer assets.
+pub fn linked(name: &str) -> Option<(&'static str, &'static str)> {
+    if name == css_file_name() {
+        Some((CDV_CSS, "text/css; charset=utf-8"))
+    } else if name == js_file_name() {
+        Some((CDV_JS, "application/javascript; charset=utf-8"))
+    } else {
+        None
+    }
+}
+
+/// Hash of the CSS and JS together, stamped into injected pages.
+pub fn bundle_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| content_hash(&format!("{CDV_CSS}{CDV_JS}")))
+}
+
+fn file_names() -> &'static (String, String) {
+    static NAMES: OnceLock<(String, String)> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        (
+            format!("cdv.{}.css", content_hash(CDV_CSS)),
+            format!("cdv.{}.js", content_hash(CDV_JS)),
+        )
+    })
+}
+
+/// The first 10 hex digits of the 64-bit FNV-1a hash of `content`; stable
+/// across builds and platforms, unlike `DefaultHasher`.
+pub fn content_hash(content: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in content.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")[..10].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_names_carry_a_content_hash() {
+        assert!(css_file_name().starts_with("cdv.") && css_file_name().ends_with(".css"));
+        assert_ne!(content_hash(CDV_CSS), content_hash(CDV_JS));
+        assert_eq!(linked(js_file_name()).map(|(js, _)| js), Some(CDV_JS));
+        assert!(linked("cdv.0000000000.js").is_none());
+        assert!(linked("../cdv.css").is_none());
+    }
+}
@@ -75,13 +75,188 @@
       }
     })();
 
+    // Persistent UI state. Served pages keep it on the server, which inlines
+    // it as `__CDV_STATE__` so reads stay synchronous; writes are batched into
+    // `PATCH /cdv-api/state/<namespace>`. Values already in localStorage are
+    // moved over on first read. Pages opened from disk use localStorage.
+    var CDV_STATE = (function(){
+      var endpoint = '';
+      var data = null;
+      try {
+        var proto = String(location.protocol || '');
+        if ((proto === 'http:' || proto === 'https:') &&
+            typeof CDV_BOOTSTRAP.state_endpoint === 'string' && CDV_BOOTSTRAP.state_endpoint &&
+            window.__CDV_STATE__ && typeof window.__CDV_STATE__ === 'object') {
+          endpoint = CDV_BOOTSTRAP.state_endpoint;
+          data = window.__CDV_STATE__;
+        }
+      } catch (_) {}
+      var pending = {};
+      var timer = 0;
+
+      function flush() {
+        clearTimeout(timer);
+        timer = 0;
+        Object.keys(pending).forEach(function(ns){
+          var changes = pending[ns];
+          delete pending[ns];
+          try {
+            fetch(endpoint + encodeURIComponent(ns), {
+              method: 'PATCH',
+              headers: { 'Content-Type': 'application/json' },
+              body: JSON.stringify(changes),
+              keepalive: true
+            }).catch(function(err){ console.warn('[CDV] Failed to save state:', err); });
+          } catch (_) {}
+        });
+      }
+
+      function queue(ns, key, value) {
+        (pending[ns] = pending[ns] || {})[key] = value;
+        if (!timer) timer = setTimeout(flush, 500);
+      }
+
+      function bucket(ns) {
+        if (!data[ns] || typeof data[ns] !== 'object') data[ns] = {};
+        return data[ns];
+      }
+
+      if (data) {
+        window.addEventListener('pagehide', flush);
+      }
+
+      return {
+        remote: !!data,
+        get: function(ns, key) {
+          if (!data) {
+            try { return localStorage.getItem(key); } catch (_) { return null; }
+          }
+          var values = bucket(ns);
+          if (Object.prototype.hasOwnProperty.call(values, key)) return values[key];
+          var legacy = null;
+          try {
+            legacy = localStorage.getItem(key);
+            if (legacy !== null) localStorage.removeItem(key);
+          } catch (_) {}
+          if (legacy !== null) {
+            values[key] = legacy;
+            queue(ns, key, legacy);
+          }
+          return legacy;
+        },
+        set: function(ns, key, value) {
+          var remove = value === null || value === undefined;
+          if (!data) {
+            try {
+              if (remove) localStorage.removeItem(key); else localStorage.setItem(key, String(value));
+            } catch (_) {}
+            return;
+          }
+          var values = bucket(ns);
+          if (remove) delete values[key]; else values[key] = String(value);
+          queue(ns, key, remove ? null : String(value));
+        },
+        keys: function(ns) {
+          return data ? Object.keys(bucket(ns)) : [];
+        }
+      };
+    })();
+
+    // `window.CDV`, the hooks for user plugins (`plugins/*.js`, which run
+    // right after this script):
+    //   CDV.on('page-ready', fn)    once the UI is set up; handlers added
+    //                               later are called right away.
+    //   CDV.on('item-changed', fn)  when the URL hash moves to another item
+    //                               or heading.
+    //   CDV.on('chat-context', fn)  whenever the chat context is assembled,
+    //                               for the preview and for every question;
+    //                               handlers may rewrite its `system`,
+    //                               `environment`, `summary` and `retrieved`
+    //                               text and must not block.
+    // Page events pass {crate, path, item, title}, plus `previous` for
+    // item-changed. CDV.off(event, fn) removes a handler.
+    var CDV_HOOKS = (function(){
+      var handlers = {'page-ready': [], 'item-changed': [], 'chat-context': []};
+      var ready = null;
+
+      function call(event, fn, detail) {
+        try {
+          fn(detail);
+        } catch (err) {
+          console.warn('[CDV] ' + event + ' handler failed:', err);
+        }
+      }
+
+      function emit(event, detail) {
+        (handlers[event] || []).slice().forEach(function(fn){ call(event, fn, detail); });
+      }
+
+      function hashItem() {
+        var id = location.hash ? location.hash.slice(1) : '';
+        try { return decodeURIComponent(id); } catch (_) { return id; }
+      }
+
+      function pageInfo(item) {
+        var meta = document.querySelector('meta[name="rustdoc-vars"]');
+        return {
+          crate: meta && meta.dataset && meta.dataset.currentCrate || '',
+          path: location.pathname,
+          item: item,
+          title: document.title || ''
+        };
+      }
+
+      try {
+        window.CDV = {
+          version: CDV_BOOTSTRAP.version,
+          on: function(event, fn) {
+            if (!handlers[event]) throw new Error('[CDV] Unknown event: ' + event);
+            if (typeof fn !== 'function') return;
+            handlers[event].push(fn);
+            if (event === 'page-ready' && ready) call(event, fn, ready);
+          },
+          off: function(event, fn) {
+            var list = handlers[event] || [];
+            var index = list.indexOf(fn);
+            if (index >= 0) list.splice(index, 1);
+          }
+        };
+      } catch (_) {}
+
+      var currentItem = hashItem();
+      function start() {
+        ready = pageInfo(currentItem);
+        emit('page-ready', ready);
+        window.addEventListener('hashchange', function(){
+          var item = hashItem();
+          if (item === currentItem) return;
+          var detail = pageInfo(item);
+          detail.previous = currentItem;
+          currentItem = item;
+          emit('item-changed', detail);
+        });
+      }
+      // Wait for the plugins to subscribe.
+      if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', start);
+      } else {
+        setTimeout(start, 0);
+      }
+
+      return { emit: emit };
+    })();
+
     (function registerServiceWorker(){
       try {
         if (!('serviceWorker' in navigator)) return;
         var proto = String(location.protocol || '');
         if (proto !== 'http:' && proto !== 'https:') return;
-        var swUrl = location.origin + '/cdv-sw.js';
-        navigator.serviceWorker.register(swUrl, {scope: location.origin + '/'}).catch(function(err){
+        // Served and exported pages link this script from `cdv-assets/` below
+        // the site root, which for exports may itself be below the origin's.
+        var script = document.currentScript && document.currentScript.src;
+        var swUrl = script ? new URL('../cdv-sw.js', script).href : location.origin + '/cdv-sw.js';
+        var scope = script ? new URL('./', swUrl).href : location.origin + '/';
+        navigator.serviceWorker.register(swUrl, {scope: scope}).catch(function(err){
           console.warn('[CDV] Failed to register service worker:', err);
         });
       } catch (err) {
@@ -89,6 +264,62 @@
       }
     })();
 
+    // Live reload for `serve --watch`: the server pushes `reload` after a
+    // successful `cargo doc`; scroll position is restored by setupScrollMemory.
+    // `config-updated` only offers a reload, since the page may hold a draft.
+    (function setupLiveReload(){
+      function showConfigNotice() {
+        if (document.getElementById('cdv-config-notice')) return;
+        var notice = document.createElement('div');
+        notice.id = 'cdv-config-notice';
+        notice.setAttribute('role', 'status');
+        notice.innerHTML = '<span>配置已更新 Config updated</span>' +
+          '<button type="button" data-action="reload">重新加载 Reload</button>' +
+          '<button type="button" data-action="dismiss" aria-label="Dismiss">×</button>';
+        notice.addEventListener('click', function(ev){
+          var action = ev.target && ev.target.getAttribute('data-action');
+          if (action === 'reload') {
+            try {
+              sessionStorage.setItem('cdv.scroll::' + location.pathname, String(window.scrollY || window.pageYOffset || 0));
+            } catch (_) {}
+            location.reload();
+          } else if (action === 'dismiss') {
+            notice.remove();
+          }
+        });
+        document.body.appendChild(notice);
+      }
+
+      try {
+        var endpoint = CDV_BOOTSTRAP.events_endpoint;
+        if (typeof endpoint !== 'string' || !endpoint) return;
+        if (!window.EventSource) return;
+        var proto = String(location.protocol || '');
+        if (proto !== 'http:' && proto !== 'https:') return;
+        var source = new EventSource(endpoint);
+        source.addEventListener('rebuilding', function(){
+          console.info('[CDV] Sources changed; rebuilding docs…');
+        });
+        source.addEventListener('rebuild-failed', function(){
+          console.warn('[CDV] `cargo doc` failed; showing previous docs.');
+        });
+        source.addEventListener('config-updated', function(){
+          console.info('[CDV] Config changed on the server.');
+          showConfigNotice();
+        });
+        source.addEventListener('reload', function(){
+          try {
+            sessionStorage.setItem('cdv.scroll::' + location.pathname, String(window.scrollY || window.pageYOffset || 0));
+          } catch (_) {}
+          source.close();
+          location.reload();
+        });
+        window.addEventListener('pagehide', function(){ source.close(); });
+      } catch (err) {
+        console.warn('[CDV] Live reload unavailable:', err);
+      }
+    })();
+
     var CDV_REFRESH_QUICK = function(){};
     var CDV_OUTLINE_STATE = {
       items: [],
@@ -242,6 +473,7 @@
           '<div id="cdv-home-dropdown-content">' +
             '<div class="home-item" data-action="crate" title="返回当前 crate 首页">当前包首页</div>' +
             '<div class="home-item" data-action="overview" title="查看所有包的卡片式概览">所有包概览</div>' +
+            '<div class="home-item" data-action="deps" title="查看解析后的依赖图">依赖图</div>' +
           '</div>' +
         '</div>' +
         '<div id="cdv-search-host"></div>' +
@@ -287,6 +519,8 @@
           } else if (action === 'overview') {
             // Crate overview page with cards
             target = buildCrateOverviewUrl();
+          } else if (action === 'deps') {
+            target = buildRootPageUrl('cdv-deps.html');
           }
           
           if (target) {
@@ -379,7 +613,7 @@
 
       function loadWidthPreference() {
         try {
-          var saved = localStorage.getItem(widthKey);
+          var saved = CDV_STATE.get('ui', widthKey);
           if (saved && getWidthOption(saved)) {
             return saved;
           }
@@ -441,7 +675,7 @@
         updateWidthButtons(currentWidthId);
         if (persist) {
           try {
-            localStorage.setItem(widthKey, currentWidthId);
+            CDV_STATE.set('ui', widthKey, currentWidthId);
           } catch (_) {}
         }
       }
@@ -518,13 +752,13 @@
       }
 
       var initial = false;
-      try { initial = localStorage.getItem(key) === '1'; } catch (_) {}
+      initial = CDV_STATE.get('ui', key) === '1';
       apply(initial);
 
       btn.addEventListener('click', function(){
         var next = !focusActive;
         apply(next);
-        try { localStorage.setItem(key, next ? '1' : '0'); } catch (_) {}
+        CDV_STATE.set('ui', key, next ? '1' : '0');
       });
 
       // Allow width preference to be changed even if focus was active via storage
@@ -570,10 +804,10 @@
         }
         var key = cdvFilterKey();
         function load() {
-          try { var raw = localStorage.getItem(key); if (!raw) return null; return JSON.parse(raw); } catch(_) { return null; }
+          try { var raw = CDV_STATE.get('ui', key); if (!raw) return null; return JSON.parse(raw); } catch(_) { return null; }
         }
         function save(state) {
-          try { localStorage.setItem(key, JSON.stringify(state)); } catch(_) {}
+          CDV_STATE.set('ui', key, JSON.stringify(state));
         }
         function readState() {
           var s = {};
@@ -617,12 +851,14 @@
           '</div>' +
           '<div class="cdv-chat-actions">' +
             '<span class="cdv-chat-tokens" id="cdv-chat-token-indicator"></span>' +
+            '<button id="cdv-chat-history-toggle" title="已保存的对话" hidden>History</button>' +
             '<button id="cdv-chat-context-toggle" title="查看当前请求上下文">Context</button>' +
             '<button id="cdv-chat-cancel" title="停止当前请求" disabled>Stop</button>' +
             '<button id="cdv-chat-close" title="关闭面板">×</button>' +
           '</div>' +
         '</div>' +
         '<div id="cdv-chat-context" class="collapsed"></div>' +
+        '<div id="cdv-chat-transcripts" class="collapsed"></div>' +
         '<div id="cdv-chat-messages" aria-live="polite"></div>' +
         '<div id="cdv-chat-selection-chip" class="cdv-selection-chip"></div>' +
         '<div id="cdv-chat-input-row">' +
@@ -1338,7 +1574,7 @@
     function loadOutlineExpanded() {
       var map = Object.create(null);
       try {
-        var raw = localStorage.getItem(outlineExpandStorageKey());
+        var raw = CDV_STATE.get('outline', outlineExpandStorageKey());
         if (!raw) return map;
         var list = JSON.parse(raw);
         if (Array.isArray(list)) {
@@ -1361,7 +1597,7 @@
             list.push(key);
           }
         }
-        localStorage.setItem(outlineExpandStorageKey(), JSON.stringify(list));
+        CDV_STATE.set('outline', outlineExpandStorageKey(), JSON.stringify(list));
       } catch(_) {}
     }
 
@@ -1483,11 +1719,15 @@
     }
 
     function buildCrateOverviewUrl() {
+      // Navigate to our generated crate overview page
+      return buildRootPageUrl('cdv-crate-overview.html');
+    }
+
+    function buildRootPageUrl(page) {
       try {
         var meta = document.querySelector('meta[name="rustdoc-vars"]');
         var root = (meta && meta.dataset && meta.dataset.rootPath) || './';
-        // Navigate to our generated crate overview page
-        var url = new URL(root + 'cdv-crate-overview.html', location.href);
+        var url = new URL(root + page, location.href);
         return url.href;
       } catch(_) {
         // If it fails for any reason, fall back to the crate home.
@@ -1592,18 +1832,254 @@
       } catch(_) {}
     })();
 
-    // Restore scroll position when returning, unless on a hash
+    // Team notes from the project's `.cargo-doc-viewer/notes/`, inlined as
+    // `__CDV_NOTES__` and shown under the heading or item they belong to.
+    // Notes whose anchor is gone are collected at the top of the page. Served
+    // pages can add, edit and delete notes through `/cdv-api/notes/<id>`.
+    (function setupNotes(){
+      var data = window.__CDV_NOTES__;
+      if (!data || !Array.isArray(data.notes)) return;
+      var endpoint = typeof CDV_BOOTSTRAP.notes_endpoint === 'string' ? CDV_BOOTSTRAP.notes_endpoint : '';
+      var page = String(data.page || '');
+      var notes = data.notes.slice();
+      var main = document.querySelector('#main-content') || document.querySelector('main') || document.body;
+
+      // Item sections (`section#method.new`) take the button in their code
+      // header; headings take it themselves.
+      function headerOf(target) {
+        if (/^H[1-6]$/.test(target.tagName)) return target;
+        return target.querySelector('.code-header') || target;
+      }
+
+      // Notes go below the header, or below the `<summary>` of a collapsible
+      // item so clicking them does not fold it.
+      function placeAfter(target) {
+        var parent = target.parentElement;
+        if (parent && parent.tagName === 'SUMMARY') return parent;
+        return headerOf(target);
+      }
+
+      function findTarget(anchor) {
+        if (!anchor) return null;
+        var target = document.getElementById(anchor);
+        return target && main.contains(target) ? target : null;
+      }
+
+      function pageHeader() {
+        return document.querySelector('.main-heading') || main.firstElementChild;
+      }
+
+      function render() {
+        main.querySelectorAll('.cdv-note, .cdv-notes-orphaned').forEach(function(node){ node.remove(); });
+        var orphaned = [];
+        var byHost = [];
+        notes.forEach(function(note){
+          var host = note.anchor ? findTarget(note.anchor) : null;
+          if (note.anchor && !host) {
+            orphaned.push(note);
+            return;
+          }
+          var after = host ? placeAfter(host) : pageHeader();
+          if (!after) return;
+          var entry = byHost.filter(function(item){ return item.after === after; })[0];
+          if (!entry) {
+            entry = { after: after, notes: [] };
+            byHost.push(entry);
+          }
+          entry.notes.push(note);
+        });
+        byHost.forEach(function(entry){
+          var last = entry.after;
+          entry.notes.forEach(function(note){
+            var node = noteNode(note);
+            last.insertAdjacentElement('afterend', node);
+            last = node;
+          });
+        });
+        if (orphaned.length) {
+          var box = document.createElement('div');
+          box.className = 'cdv-notes-orphaned';
+          box.innerHTML = '<div class="cdv-notes-orphaned-title">以下团队注释的锚点已不存在 Orphaned notes</div>';
+          orphaned.forEach(function(note){
+            var node = noteNode(note);
+            var where = document.createElement('div');
+            where.className = 'cdv-note-anchor';
+            where.textContent = '#' + note.anchor;
+            node.insertBefore(where, node.firstChild);
+            box.appendChild(node);
+          });
+          var top = pageHeader();
+          if (top) top.insertAdjacentElement('afterend', box);
+        }
+      }
+
+      function noteNode(note) {
+        var node = document.createElement('aside');
+        node.className = 'cdv-note';
+        node.setAttribute('data-note-id', note.id);
+        var text = document.createElement('div');
+        text.className = 'cdv-note-text';
+        text.textContent = note.text || '';
+        node.appendChild(text);
+        var meta = document.createElement('div');
+        meta.className = 'cdv-note-meta';
+        meta.textContent = [note.author, note.updated].filter(Boolean).join(' · ');
+        if (endpoint) {
+          meta.appendChild(actionButton('编辑', function(){ openEditor(note, node); }));
+          meta.appendChild(actionButton('删除', function(){
+            if (!confirm('删除这条团队注释？')) return;
+            request('DELETE', note.id).then(function(){
+              notes = notes.filter(function(other){ return other.id !== note.id; });
+              render();
+            }).catch(reportError);
+          }));
+        }
+        node.appendChild(meta);
+        return node;
+      }
+
+      function actionButton(label, onClick) {
+        var btn = document.createElement('button');
+        btn.type = 'button';
+        btn.textContent = label;
+        btn.addEventListener('click', function(ev){ ev.preventDefault(); ev.stopPropagation(); onClick(); });
+        return btn;
+      }
+
+      function request(method, id, body) {
+        return fetch(endpoint + '/' + encodeURIComponent(id), {
+          method: method,
+          headers: body ? { 'Content-Type': 'application/json' } : {},
+          body: body ? JSON.stringify(body) : undefined
+        }).then(function(resp){
+          return resp.json().catch(function(){ return {}; }).then(function(json){
+            if (!resp.ok) throw new Error(json && json.error && json.error.message || ('HTTP ' + resp.status));
+            return json;
+          });
+        });
+      }
+
+      function reportError(err) {
+        alert('保存团队注释失败：' + (err && err.message ? err.message : String(err)));
+      }
+
+      // Replaces `replaced` (a note) or follows `after` (a header) with a
+      // small form; saving writes the note back to its YAML file.
+      function openEditor(note, replaced, after) {
+        main.querySelectorAll('.cdv-note-editor').forEach(function(node){ node.remove(); });
+        var form = document.createElement('form');
+        form.className = 'cdv-note-editor';
+        var text = document.createElement('textarea');
+        text.rows = 3;
+        text.placeholder = '例如：不要在热路径中使用，见 incident 42';
+        text.value = note.text || '';
+        var author = document.createElement('input');
+        author.type = 'text';
+        author.placeholder = '作者 Author';
+        author.value = note.author || CDV_STATE.get('notes', 'cdv.notes.author') || '';
+        var row = document.createElement('div');
+        row.className = 'cdv-note-editor-row';
+        var save = document.createElement('button');
+        save.type = 'submit';
+        save.textContent = '保存';
+        row.appendChild(author);
+        row.appendChild(save);
+        row.appendChild(actionButton('取消', function(){
+          form.remove();
+          if (replaced) replaced.hidden = false;
+        }));
+        form.appendChild(text);
+        form.appendChild(row);
+        form.addEventListener('submit', function(ev){
+          ev.preventDefault();
+          var body = {
+            page: page,
+            anchor: note.anchor || '',
+            text: text.value,
+            author: author.value.trim(),
+            updated: new Date().toISOString().slice(0, 10)
+          };
+          var id = note.id || (Date.now().toString(36) + '-' + Math.random().toString(36).slice(2, 6));
+          CDV_STATE.set('notes', 'cdv.notes.author', body.author || null);
+          request('PUT', id, body).then(function(saved){
+            notes = notes.filter(function(other){ return other.id !== saved.id; });
+            notes.push(saved);
+            render();
+          }).catch(reportError);
+        });
+        if (replaced) {
+          replaced.hidden = true;
+          replaced.insertAdjacentElement('afterend', form);
+        } else if (after) {
+          after.insertAdjacentElement('afterend', form);
+        }
+        text.focus();
+      }
+
+      function addButtons() {
+        var targets = [];
+        var heading = document.querySelector('.main-heading h1');
+        if (heading) targets.push({ anchor: '', header: heading, after: pageHeader() });
+        main.querySelectorAll('h2[id], h3[id], h4[id], section[id]').forEach(function(target){
+          targets.push({ anchor: target.id, header: headerOf(target), after: placeAfter(target) });
+        });
+        targets.forEach(function(target){
+          if (!target.header || target.header.querySelector(':scope > .cdv-note-add')) return;
+          var btn = actionButton('添加注释', function(){
+            openEditor({ anchor: target.anchor }, null, target.after);
+          });
+          btn.className = 'cdv-note-add';
+          btn.title = '添加团队注释（保存到 .cargo-doc-viewer/notes/）';
+          target.header.appendChild(btn);
+        });
+      }
+
+      try {
+        render();
+        if (endpoint && page) addButtons();
+      } catch (err) {
+        console.warn('[CDV] Failed to show team notes:', err);
+      }
+    })();
+
+    // Restore scroll position when returning, unless on a hash. The tab's
+    // own position wins; served pages also remember the last position of the
+    // most recently read pages across browsers.
     (function setupScrollMemory(){
+      var MAX_REMEMBERED = 200;
       try {
         var key = 'cdv.scroll::' + location.pathname;
         if (!location.hash) {
           var y = parseInt(sessionStorage.getItem(key) || '0', 10);
+          if (!(y > 0) && CDV_STATE.remote) {
+            try { y = JSON.parse(CDV_STATE.get('scroll', key) || '{}').y || 0; } catch (_) { y = 0; }
+          }
           if (y > 0) setTimeout(function(){ window.scrollTo(0, y); }, 0);
         }
         var scheduled = false;
+        var saveTimer = 0;
+        function remember() {
+          saveTimer = 0;
+          var entry = { y: window.scrollY || window.pageYOffset || 0, t: Date.now() };
+          CDV_STATE.set('scroll', key, JSON.stringify(entry));
+          var keys = CDV_STATE.keys('scroll');
+          if (keys.length <= MAX_REMEMBERED) return;
+          var stamped = keys.map(function(k){
+            var t = 0;
+            try { t = JSON.parse(CDV_STATE.get('scroll', k)).t || 0; } catch (_) {}
+            return { key: k, t: t };
+          }).sort(function(a, b){ return a.t - b.t; });
+          stamped.slice(0, keys.length - MAX_REMEMBERED).forEach(function(old){
+            CDV_STATE.set('scroll', old.key, null);
+          });
+        }
         window.addEventListener('scroll', function(){
           if (scheduled) return; scheduled=true;
           setTimeout(function(){ scheduled=false; try { sessionStorage.setItem(key, String(window.scrollY||window.pageYOffset||0)); } catch(_){} }, 200);
+          if (CDV_STATE.remote) {
+            clearTimeout(saveTimer);
+            saveTimer = setTimeout(remember, 1500);
+          }
         });
       } catch(_) {}
     })();
@@ -1756,14 +2232,14 @@
 
     function loadHistoryList() {
       try {
-        var raw = localStorage.getItem(cdvHistoryKey());
+        var raw = CDV_STATE.get('history', cdvHistoryKey());
         if (!raw) return [];
         var arr = JSON.parse(raw); if (Array.isArray(arr)) return arr; return [];
       } catch(_) { return []; }
     }
 
     function saveHistoryList(list) {
-      try { localStorage.setItem(cdvHistoryKey(), JSON.stringify(list)); } catch(_) {}
+      CDV_STATE.set('history', cdvHistoryKey(), JSON.stringify(list));
     }
 
     function saveHistory(q) {
@@ -2118,6 +2594,7 @@
       var STORAGE_KEYS = {
         apiKey: 'cdv.ai.api_key',
         model: 'cdv.ai.model',
+        profile: 'cdv.ai.profile',
         systemPrompt: 'cdv.ai.system_prompt',
         panelWidth: 'cdv.ai.panel_width'
       };
@@ -2125,6 +2602,7 @@
       var DEFAULT_CHAT_WIDTH = 420;
       var DEFAULT_CONFIG = {
         api: {
+          provider: 'openai',
           base_url: 'https://api.openai.com/v1',
           model: 'gpt-4.1-mini',
           timeout_ms: 15000,
@@ -2141,7 +2619,9 @@
           debounce_ms: 300,
           sanitize_patterns: [
             { regex: '(?i)apikey=[A-Za-z0-9_-]+', replacement: '[redacted]' }
-          ]
+          ],
+          retrieval_top_k: 5,
+          retrieval_tokens_budget: 1500
         },
         ui: {
           language: 'auto',
@@ -2159,15 +2639,20 @@
         tokenIndicator: null,
         cancelBtn: null,
         contextToggle: null,
+        historyToggle: null,
+        transcriptsHost: null,
         closeBtn: null,
         resizer: null,
         selectionChip: null,
         envPreview: null,
         selectionPreview: null,
         selectionMeta: null,
+        retrievedPreview: null,
+        retrievedMeta: null,
         budget: null,
         systemInput: null,
         resetSystem: null,
+        profileSelect: null,
         modelInput: null,
         apiKeyInput: null,
         copyContextBtn: null,
@@ -2211,6 +2696,8 @@
           var keyPreview = state.apiKey ? maskSecret(state.apiKey) : '(none)';
           console.groupCollapsed('[CDV][config] AI chat configuration');
           console.log('configPath:', state.configPath || '(default)');
+          console.log('chat endpoint:', state.chatEndpoint ? (state.chatEndpoint + ' (server proxy)') : 'direct');
+          console.log('profile:', state.profile, '(' + (state.config.api.provider || 'openai') + ')');
           console.log('resolved base_url:', state.config.api.base_url);
           console.log('resolved model:', state.config.api.model);
           console.log('configured headers:', headers);
@@ -2230,6 +2717,17 @@
           defaults: defaults,
           version: typeof CDV_BOOTSTRAP.version === 'string' ? CDV_BOOTSTRAP.version : '0.0.0',
           configPath: typeof CDV_BOOTSTRAP.config_path === 'string' ? CDV_BOOTSTRAP.config_path : '',
+          chatEndpoint: resolveChatEndpoint(CDV_BOOTSTRAP.chat_endpoint),
+          retrieveEndpoint: resolveChatEndpoint(CDV_BOOTSTRAP.retrieve_endpoint),
+          settingsPage: resolveChatEndpoint(CDV_BOOTSTRAP.settings_page),
+          transcriptsEndpoint: resolveChatEndpoint(CDV_BOOTSTRAP.transcripts_endpoint),
+          // The conversation being saved; replaced when one is reopened.
+          transcript: null,
+          transcriptsOpen: false,
+          // Credential fields `enhance` left out of the page; the key has to
+          // be entered here instead.
+          redacted: Array.isArray(CDV_BOOTSTRAP.redacted) ? CDV_BOOTSTRAP.redacted : [],
+          retrieved: [],
           pending: false,
           abort: null,
           history: [],
@@ -2242,6 +2740,7 @@
             environment: 0,
             page: 0,
             selection: 0,
+            retrieved: 0,
             history: 0,
             user: 0,
             budget: TOTAL_TOKEN_BUDGET,
@@ -2251,10 +2750,17 @@
           sanitizers: compileSanitizers(cfg.context.sanitize_patterns || []),
           systemPrompt: '',
           envTemplate: cfg.prompts.environment_template || DEFAULT_CONFIG.prompts.environment_template,
+          profiles: collectProfiles(cfg),
+          profile: 'default',
           modelOverride: '',
           apiKey: '',
           lastContextLayers: null
         };
+        var storedProfile = loadFromStorage(STORAGE_KEYS.profile) || '';
+        if (storedProfile && st.profiles[storedProfile]) {
+          st.profile = storedProfile;
+          st.config.api = mergeObjects(st.profiles[storedProfile], null);
+        }
         st.systemPrompt = loadSystemPromptOverride(cfg.prompts.system || DEFAULT_CONFIG.prompts.system);
         st.apiKey = loadFromStorage(STORAGE_KEYS.apiKey) || '';
         st.modelOverride = loadFromStorage(STORAGE_KEYS.model) || '';
@@ -2264,6 +2770,32 @@
         return st;
       }
 
+      // `api` is the "default" profile; named profiles come from `profiles:`
+      // in the config and only work through the server proxy unless they
+      // speak the OpenAI format.
+      function collectProfiles(cfg) {
+        var profiles = { 'default': mergeObjects(cfg.api, null) };
+        var named = cfg.profiles && typeof cfg.profiles === 'object' ? cfg.profiles : {};
+        Object.keys(named).sort().forEach(function(name){
+          if (name === 'default' || !named[name] || typeof named[name] !== 'object') return;
+          profiles[name] = mergeObjects(DEFAULT_CONFIG.api, named[name]);
+        });
+        return profiles;
+      }
+
+      function switchProfile(name) {
+        if (!state.profiles[name] || name === state.profile) return;
+        state.profile = name;
+        state.config.api = mergeObjects(state.profiles[name], null);
+        state.modelOverride = '';
+        saveToStorage(STORAGE_KEYS.profile, name === 'default' ? '' : name);
+        saveToStorage(STORAGE_KEYS.model, '');
+        if (dom.modelInput) dom.modelInput.value = state.config.api.model || '';
+        updateModelLabel();
+        updateContextPreview();
+        notifyContext('Switched to profile "' + name + '".');
+      }
+
       function init() {
         if (CDV_FLAGS.noChat) return;
         dom.panel = document.getElementById('cdv-chat-panel');
@@ -2275,6 +2807,8 @@
         dom.tokenIndicator = document.getElementById('cdv-chat-token-indicator');
         dom.cancelBtn = document.getElementById('cdv-chat-cancel');
         dom.contextToggle = document.getElementById('cdv-chat-context-toggle');
+        dom.historyToggle = document.getElementById('cdv-chat-history-toggle');
+        dom.transcriptsHost = document.getElementById('cdv-chat-transcripts');
         dom.closeBtn = document.getElementById('cdv-chat-close');
         dom.selectionChip = document.getElementById('cdv-chat-selection-chip');
         if (!dom.sendBtn || !dom.input || !dom.messages || !dom.panel) return;
@@ -2308,6 +2842,13 @@
           state.contextOpen = !state.contextOpen;
           updateContextVisibility();
         });
+        if (dom.historyToggle && dom.transcriptsHost && state.transcriptsEndpoint) {
+          dom.historyToggle.hidden = false;
+          dom.historyToggle.addEventListener('click', function(){
+            setTranscriptsOpen(!state.transcriptsOpen);
+          });
+          dom.transcriptsHost.addEventListener('click', handleTranscriptClick);
+        }
         if (dom.closeBtn) dom.closeBtn.addEventListener('click', function(){
           dom.panel.classList.remove('open');
           document.body.classList.remove('cdv-chat-open');
@@ -2389,6 +2930,13 @@
         if (!dom.contextHost) return;
         var allowEdit = !!(state.config.ui && state.config.ui.allow_prompt_edit);
         var configSource = state.configPath ? ('Config: ' + state.configPath) : 'Config: embedded defaults';
+        var keyPlaceholder = '';
+        if (state.chatEndpoint) {
+          keyPlaceholder = 'Using server credentials';
+        } else if (state.redacted.length) {
+          keyPlaceholder = 'Configure in browser';
+          configSource += ' · credentials not embedded (' + state.redacted.join(', ') + ')';
+        }
         dom.contextHost.innerHTML =
           '<section class="cdv-context-section" data-section="system">' +
             '<header><span>System Prompt</span>' +
@@ -2404,15 +2952,23 @@
             '<header><span>Selection</span><span id="cdv-chat-selection-meta"></span></header>' +
             '<pre id="cdv-chat-selection"></pre>' +
           '</section>' +
+          '<section class="cdv-context-section" data-section="retrieved">' +
+            '<header><span>Retrieved</span><span id="cdv-chat-retrieved-meta"></span></header>' +
+            '<div id="cdv-chat-retrieved"></div>' +
+          '</section>' +
           '<section class="cdv-context-section" data-section="budget">' +
             '<header>Token Budget</header>' +
             '<div id="cdv-chat-budget"></div>' +
           '</section>' +
           '<section class="cdv-context-section" data-section="api">' +
             '<header>API Settings</header>' +
+            buildProfileField() +
             '<label class="cdv-field"><span>Model</span><input id="cdv-chat-model-input" type="text" /></label>' +
-            '<label class="cdv-field"><span>API Key</span><input id="cdv-chat-api-key" type="password" autocomplete="off" /></label>' +
-            '<div class="cdv-chat-config-meta">' + configSource + '</div>' +
+            '<label class="cdv-field"><span>API Key</span><input id="cdv-chat-api-key" type="password" autocomplete="off"' +
+              (keyPlaceholder ? ' placeholder="' + keyPlaceholder + '"' : '') + ' /></label>' +
+            '<div class="cdv-chat-config-meta">' + escapeHtml(configSource) +
+              (state.settingsPage ? ' · <a href="' + escapeHtml(state.settingsPage) + '" target="_blank" rel="noopener">Edit settings</a>' : '') +
+            '</div>' +
             '<div class="cdv-chat-actions-row">' +
               '<button type="button" id="cdv-chat-copy-context">Copy context</button>' +
               '<span id="cdv-chat-context-status" class="cdv-chat-status"></span>' +
@@ -2422,9 +2978,12 @@
         dom.envPreview = document.getElementById('cdv-chat-env');
         dom.selectionPreview = document.getElementById('cdv-chat-selection');
         dom.selectionMeta = document.getElementById('cdv-chat-selection-meta');
+        dom.retrievedPreview = document.getElementById('cdv-chat-retrieved');
+        dom.retrievedMeta = document.getElementById('cdv-chat-retrieved-meta');
         dom.budget = document.getElementById('cdv-chat-budget');
         dom.systemInput = document.getElementById('cdv-chat-system-input');
         dom.resetSystem = document.getElementById('cdv-chat-reset-system');
+        dom.profileSelect = document.getElementById('cdv-chat-profile');
         dom.modelInput = document.getElementById('cdv-chat-model-input');
         dom.apiKeyInput = document.getElementById('cdv-chat-api-key');
         dom.copyContextBtn = document.getElementById('cdv-chat-copy-context');
@@ -2451,6 +3010,12 @@
             notifyContext('System prompt reset.');
           });
         }
+        if (dom.profileSelect) {
+          dom.profileSelect.value = state.profile;
+          dom.profileSelect.addEventListener('change', function(){
+            switchProfile(dom.profileSelect.value);
+          });
+        }
         if (dom.modelInput) {
           dom.modelInput.value = state.config.api.model || DEFAULT_CONFIG.api.model;
           dom.modelInput.addEventListener('change', function(){
@@ -2475,9 +3040,24 @@
         }
       }
 
+      function buildProfileField() {
+        var names = Object.keys(state.profiles);
+        if (names.length < 2) return '';
+        var options = names.map(function(name){
+          var api = state.profiles[name];
+          var label = name + ' (' + (api.provider || 'openai') + ' · ' + (api.model || '') + ')';
+          return '<option value="' + escapeAttr(name) + '">' + escapeHtml(label) + '</option>';
+        }).join('');
+        return '<label class="cdv-field"><span>Profile</span><select id="cdv-chat-profile">' + options + '</select></label>';
+      }
+
       function updateModelLabel() {
         if (!dom.modelLabel) return;
-        dom.modelLabel.textContent = state.config.api.model ? ('Model: ' + state.config.api.model) : '';
+        var label = state.config.api.model ? ('Model: ' + state.config.api.model) : '';
+        if (label && state.profile !== 'default') {
+          label += ' · ' + state.profile;
+        }
+        dom.modelLabel.textContent = label;
       }
 
       function updateTokenIndicator() {
@@ -2493,6 +3073,7 @@
           ', env ' + t.environment +
           ', page ' + t.page +
           ', selection ' + t.selection +
+          ', retrieved ' + (t.retrieved || 0) +
           ', history ' + t.history +
           ', user ' + t.user;
       }
@@ -2549,33 +3130,60 @@
         autoResizeTextarea(dom.input);
         appendMessage('user', sanitizedQuestion);
         pushHistory('user', sanitizedQuestion);
-        var request = buildRequestPayload(sanitizedQuestion);
-        state.lastContextLayers = request.layers;
-        updateTokenIndicator();
         var placeholder = appendMessage('assistant', 'Thinking…', { pending: true });
         setPending(true);
         var controller = new AbortController();
         state.abort = controller;
-        var url = buildEndpoint(state.config.api.base_url);
-        var headers = buildHeaders();
+        var url = state.chatEndpoint || buildEndpoint(state.config.api.base_url);
+        var headers = state.chatEndpoint ? buildProxyHeaders() : buildHeaders();
         if (!hasHeader(headers, 'content-type')) {
           headers['Content-Type'] = 'application/json';
         }
-        debugLogRequest(url, headers);
-        fetch(url, {
-          method: 'POST',
-          headers: headers,
-          body: JSON.stringify(request.payload),
-          signal: controller.signal
+        var streamed = '';
+        var renderScheduled = false;
+        var sentLayers = null;
+        function renderStreamed() {
+          renderScheduled = false;
+          setMessageContent(placeholder, sanitizeText(streamed));
+          dom.messages.scrollTop = dom.messages.scrollHeight;
+        }
+        retrieveContext(sanitizedQuestion, controller.signal).then(function(chunks){
+          var provider = state.config.api.provider || 'openai';
+          if (!state.chatEndpoint && provider !== 'openai') {
+            throw new Error('The ' + provider + ' provider is only available on pages opened through `serve`.');
+          }
+          state.retrieved = chunks;
+          updateRetrievedDisplay();
+          var request = buildRequestPayload(sanitizedQuestion);
+          state.lastContextLayers = request.layers;
+          sentLayers = request.layers;
+          updateTokenIndicator();
+          debugLogRequest(url, headers);
+          return fetch(url, {
+            method: 'POST',
+            headers: headers,
+            body: JSON.stringify(request.payload),
+            signal: controller.signal
+          });
         }).then(function(resp){
           if (!resp.ok) {
             return resp.text().then(function(text){
               throw buildHttpError(resp.status, text);
             });
           }
-          return resp.json();
-        }).then(function(data){
-          var answer = extractAssistantContent(data);
+          var type = resp.headers.get('content-type') || '';
+          if (/text\/event-stream/i.test(type) && resp.body && resp.body.getReader) {
+            return readEventStream(resp.body, function(delta){
+              streamed += delta;
+              placeholder.classList.remove('pending');
+              if (!renderScheduled) {
+                renderScheduled = true;
+                requestAnimationFrame(renderStreamed);
+              }
+            }).then(function(){ return streamed.trim(); });
+          }
+          return resp.json().then(extractAssistantContent);
+        }).then(function(answer){
           if (!answer) {
             answer = 'No response received.';
           }
@@ -2584,9 +3192,17 @@
           setMessageContent(placeholder, answer);
           placeholder.classList.remove('error');
           pushHistory('assistant', answer);
+          recordTurn(sanitizedQuestion, answer, sentLayers);
         }).catch(function(err){
           if (err && err.name === 'AbortError') {
             placeholder.classList.remove('pending');
+            if (streamed.trim()) {
+              var partial = sanitizeText(streamed.trim());
+              setMessageContent(placeholder, partial + '\n\n_(cancelled)_');
+              pushHistory('assistant', partial);
+              recordTurn(sanitizedQuestion, partial, sentLayers);
+              return;
+            }
             placeholder.classList.add('error');
             setMessageContent(placeholder, 'Request cancelled.');
             return;
@@ -2604,6 +3220,220 @@
         });
       }
 
+      // Served pages save the conversation after every answer, with the
+      // context layers that were sent, so it can be reopened from "History"
+      // or exported as Markdown.
+      function recordTurn(question, answer, layers) {
+        if (!state.transcriptsEndpoint) return;
+        var now = new Date().toISOString();
+        if (!state.transcript) {
+          var meta = gatherMetadata();
+          state.transcript = {
+            id: Date.now().toString(36) + '-' + Math.random().toString(36).slice(2, 8),
+            crate: meta.crate,
+            item: currentItemPath(meta.crate),
+            page: location.pathname,
+            title: question.length > 80 ? question.slice(0, 80) + '…' : question,
+            created: now,
+            turns: []
+          };
+        }
+        var transcript = state.transcript;
+        layers = layers || {};
+        transcript.updated = now;
+        transcript.model = state.config.api.model || '';
+        transcript.turns.push({
+          question: question,
+          answer: answer,
+          context: {
+            system: layers.system || '',
+            environment: layers.environment || '',
+            summary: layers.summary || '',
+            selection: layers.selection || '',
+            retrieved: layers.retrieved || ''
+          }
+        });
+        fetch(state.transcriptsEndpoint + '/' + encodeURIComponent(transcript.id), {
+          method: 'PUT',
+          headers: { 'Content-Type': 'application/json' },
+          body: JSON.stringify(transcript)
+        }).then(function(resp){
+          if (!resp.ok) throw new Error('HTTP ' + resp.status);
+          if (state.transcriptsOpen) loadTranscripts();
+        }).catch(function(err){
+          console.warn('[CDV] Failed to save the conversation:', err);
+        });
+      }
+
+      // `/<crate>/client/struct.Client.html` -> `<crate>::client::Client`.
+      function currentItemPath(crate) {
+        var parts = location.pathname.split('/').filter(Boolean);
+        var start = crate ? parts.lastIndexOf(crate) : -1;
+        if (start < 0) return crate || '';
+        parts = parts.slice(start);
+        var file = parts.pop() || '';
+        if (file && file !== 'index.html') {
+          var name = file.replace(/\.html$/, '');
+          parts.push(name.slice(name.indexOf('.') + 1));
+        }
+        return parts.join('::');
+      }
+
+      function setTranscriptsOpen(open) {
+        state.transcriptsOpen = open;
+        dom.transcriptsHost.classList.toggle('collapsed', !open);
+        dom.historyToggle.classList.toggle('active', open);
+        if (open) loadTranscripts();
+      }
+
+      // Lists the conversations saved for the current crate, newest first.
+      function loadTranscripts() {
+        var crate = gatherMetadata().crate;
+        var url = state.transcriptsEndpoint + (crate ? '?crate=' + encodeURIComponent(crate) : '');
+        fetch(url, { cache: 'no-store' }).then(function(resp){
+          if (!resp.ok) throw new Error('HTTP ' + resp.status);
+          return resp.json();
+        }).then(function(data){
+          renderTranscripts(Array.isArray(data && data.transcripts) ? data.transcripts : []);
+        }).catch(function(err){
+          dom.transcriptsHost.innerHTML = '<div class="cdv-transcripts-empty">无法读取已保存的对话：' +
+            escapeHtml(err && err.message ? err.message : String(err)) + '</div>';
+        });
+      }
+
+      function renderTranscripts(list) {
+        var current = state.transcript ? state.transcript.id : '';
+        var html = '<div class="cdv-transcripts-head">' +
+          '<span>Saved conversations</span>' +
+          '<button type="button" data-transcript-new>New chat</button>' +
+        '</div>';
+        if (!list.length) {
+          html += '<div class="cdv-transcripts-empty">还没有保存的对话，提问后会自动保存</div>';
+        }
+        list.forEach(function(entry){
+          var when = '';
+          try { when = new Date(entry.updated).toLocaleString(); } catch (_) {}
+          var classes = 'cdv-transcript-item' +
+            (entry.id === current ? ' active' : '') +
+            (entry.page === location.pathname ? ' here' : '');
+          html += '<div class="' + classes + '">' +
+            '<button type="button" class="cdv-transcript-open" data-transcript-open="' + escapeHtml(entry.id) + '">' +
+              escapeHtml(entry.title || '(untitled)') +
+            '</button>' +
+            '<div class="cdv-transcript-meta">' +
+              '<a href="' + escapeHtml(entry.page) + '">' + escapeHtml(entry.item || entry.page) + '</a>' +
+              ' · ' + entry.turns + (entry.turns === 1 ? ' question' : ' questions') +
+              (when ? ' · ' + escapeHtml(when) : '') +
+              ' · <a href="' + escapeHtml(state.transcriptsEndpoint + '/' + encodeURIComponent(entry.id) + '.md') + '" download>Markdown</a>' +
+            '</div>' +
+          '</div>';
+        });
+        dom.transcriptsHost.innerHTML = html;
+      }
+
+      function handleTranscriptClick(ev) {
+        var target = ev.target && ev.target.closest ? ev.target.closest('[data-transcript-open], [data-transcript-new]') : null;
+        if (!target || state.pending) return;
+        ev.preventDefault();
+        if (target.hasAttribute('data-transcript-new')) {
+          showConversation(null);
+          setTranscriptsOpen(false);
+          return;
+        }
+        var id = target.getAttribute('data-transcript-open');
+        fetch(state.transcriptsEndpoint + '/' + encodeURIComponent(id), { cache: 'no-store' }).then(function(resp){
+          if (!resp.ok) throw new Error('HTTP ' + resp.status);
+          return resp.json();
+        }).then(function(transcript){
+          showConversation(transcript);
+          setTranscriptsOpen(false);
+        }).catch(function(err){
+          console.warn('[CDV] Failed to open the conversation:', err);
+        });
+      }
+
+      // Replaces the panel's messages and history with `transcript`, or
+      // starts an empty conversation. Later answers are added to it.
+      function showConversation(transcript) {
+        state.transcript = transcript;
+        state.history = [];
+        dom.messages.innerHTML = '';
+        var turns = transcript && Array.isArray(transcript.turns) ? transcript.turns : [];
+        turns.forEach(function(turn){
+          appendMessage('user', turn.question || '');
+          appendMessage('assistant', turn.answer || '');
+          pushHistory('user', turn.question || '');
+          pushHistory('assistant', turn.answer || '');
+        });
+        updateContextPreview();
+      }
+
+      // Asks the server for documentation chunks from other pages that match
+      // the question. Resolves to [] whenever retrieval is unavailable.
+      function retrieveContext(question, signal) {
+        var k = state.config.context.retrieval_top_k;
+        if (!state.retrieveEndpoint || !k || !question) return Promise.resolve([]);
+        var url = state.retrieveEndpoint + '?k=' + encodeURIComponent(k) + '&q=' + encodeURIComponent(question);
+        var meta = gatherMetadata();
+        if (meta.crate) url += '&current=' + encodeURIComponent(meta.crate);
+        return fetch(url, { signal: signal }).then(function(resp){
+          return resp.ok ? resp.json() : null;
+        }).then(function(data){
+          var chunks = data && Array.isArray(data.chunks) ? data.chunks : [];
+          // The current page is already covered by the page summary.
+          return chunks.filter(function(chunk){
+            try {
+              return new URL(chunk.url, location.href).pathname !== location.pathname;
+            } catch (_) {
+              return true;
+            }
+          });
+        }).catch(function(err){
+          if (err && err.name === 'AbortError') throw err;
+          console.warn('[CDV] Retrieval failed:', err);
+          return [];
+        });
+      }
+
+      function formatRetrieved(chunks, budget) {
+        if (!chunks || !chunks.length) return '';
+        var parts = [];
+        var used = 0;
+        for (var i = 0; i < chunks.length; i++) {
+          var chunk = chunks[i];
+          var entry = '[' + (i + 1) + '] ' + chunk.path + ' (' + chunk.url + ')\n' + sanitizeText(chunk.text || '');
+          var cost = estimateTokens(entry);
+          if (parts.length && used + cost > budget) break;
+          parts.push(limitTextByTokens(entry, budget));
+          used += cost;
+        }
+        return parts.join('\n\n');
+      }
+
+      function updateRetrievedDisplay() {
+        if (!dom.retrievedPreview) return;
+        var chunks = state.retrieved || [];
+        if (dom.retrievedMeta) {
+          dom.retrievedMeta.textContent = chunks.length ? chunks.length + ' chunks' : 'None';
+        }
+        if (!chunks.length) {
+          dom.retrievedPreview.innerHTML = '<div class="cdv-retrieved-empty">' +
+            (state.retrieveEndpoint
+              ? '提问后会在这里列出从其他页面检索到的相关文档'
+              : '仅在 serve 模式下可用') +
+            '</div>';
+          return;
+        }
+        dom.retrievedPreview.innerHTML = chunks.map(function(chunk){
+          var text = String(chunk.text || '');
+          if (text.length > 240) text = text.slice(0, 240) + '…';
+          return '<div class="cdv-retrieved-item">' +
+            '<a href="' + escapeHtml(chunk.url) + '">' + escapeHtml(chunk.path) + '</a>' +
+            '<div class="cdv-retrieved-text">' + escapeHtml(text) + '</div>' +
+          '</div>';
+        }).join('');
+      }
+
       function cancelRequest() {
         if (state.abort) {
           state.abort.abort();
@@ -2626,6 +3456,7 @@
         if (layers.system) messages.push({ role: 'system', content: layers.system });
         if (layers.environment) messages.push({ role: 'system', content: 'Environment context:\n' + layers.environment });
         if (layers.summary) messages.push({ role: 'system', content: 'Page summary:\n' + layers.summary });
+        if (layers.retrieved) messages.push({ role: 'system', content: 'Related documentation from other pages:\n' + layers.retrieved });
         if (layers.history && layers.history.length) {
           layers.history.forEach(function(entry){
             messages.push({ role: entry.role, content: entry.content });
@@ -2635,6 +3466,7 @@
         var payload = {
           model: state.config.api.model || DEFAULT_CONFIG.api.model,
           messages: messages,
+          stream: true,
           metadata: {
             cdv_version: state.version,
             doc_path: layers.metadata && layers.metadata.location || '',
@@ -2642,6 +3474,9 @@
             module: layers.metadata && layers.metadata.module_path || ''
           }
         };
+        if (state.chatEndpoint && state.profile !== 'default') {
+          payload.profile = state.profile;
+        }
         return { payload: payload, layers: layers };
       }
 
@@ -2654,6 +3489,7 @@
         var summarySanitized = limitTextByTokens(sanitizeText(summary), state.config.context.page_tokens_budget);
         var selection = state.selectionSnippet;
         var selectionText = selection && selection.text ? limitTextByTokens(selection.text, state.config.context.page_tokens_budget) : '';
+        var retrievedText = formatRetrieved(state.retrieved, state.config.context.retrieval_tokens_budget || DEFAULT_CONFIG.context.retrieval_tokens_budget);
         var historyMessages = sliceHistory();
         var historyTokens = 0;
         for (var i = 0; i < historyMessages.length; i++) {
@@ -2665,6 +3501,20 @@
           systemPrompt = systemPrompt + '\nRespond in ' + fallback + '.';
         }
         systemPrompt = systemPrompt.trim();
+        var pluginContext = {
+          question: sanitizedQuestion,
+          system: systemPrompt,
+          environment: environmentText,
+          summary: summarySanitized,
+          retrieved: retrievedText,
+          crate: meta.crate,
+          location: meta.location
+        };
+        CDV_HOOKS.emit('chat-context', pluginContext);
+        systemPrompt = String(pluginContext.system || '').trim();
+        environmentText = String(pluginContext.environment || '');
+        summarySanitized = String(pluginContext.summary || '');
+        retrievedText = String(pluginContext.retrieved || '');
         var combinedQuestion = sanitizedQuestion;
         if (selectionText) {
           combinedQuestion = '用户选择了文档中这段话：\n' + selectionText + '\n\n结合这个信息回答下面的问题：\n' + sanitizedQuestion;
@@ -2674,18 +3524,21 @@
           environment: environmentText ? estimateTokens(environmentText) : 0,
           page: summarySanitized ? estimateTokens(summarySanitized) : 0,
           selection: selectionText ? estimateTokens(selectionText) : 0,
+          retrieved: retrievedText ? estimateTokens(retrievedText) : 0,
           history: historyTokens,
           user: combinedQuestion ? estimateTokens(combinedQuestion) : 0,
           budget: TOTAL_TOKEN_BUDGET,
           pageBudget: state.config.context.page_tokens_budget || DEFAULT_CONFIG.context.page_tokens_budget
         };
-        tokens.total = tokens.system + tokens.environment + tokens.page + tokens.history + tokens.user;
+        tokens.total = tokens.system + tokens.environment + tokens.page + tokens.retrieved + tokens.history + tokens.user;
         return {
           system: systemPrompt,
           environment: environmentText,
           summary: summarySanitized,
           selection: selectionText,
           selectionMeta: selection,
+          retrieved: retrievedText,
+          retrievedChunks: state.retrieved || [],
           history: historyMessages,
           question: sanitizedQuestion,
           combined_question: combinedQuestion,
@@ -2694,6 +3547,8 @@
             crate_version: meta.crateVersion,
             module_path: meta.modulePath,
             edition: meta.edition,
+            rust_version: meta.rustVersion,
+            repository: meta.repository,
             features: features,
             location: meta.location,
             language: meta.language,
@@ -2718,6 +3573,7 @@
           dom.budget.innerHTML = renderBudgetHtml(layers.tokens);
         }
         updateSelectionDisplays();
+        updateRetrievedDisplay();
         updateTokenIndicator();
       }
 
@@ -2729,6 +3585,7 @@
           '<div class="cdv-budget-item">Environment: ' + tokens.environment + '</div>',
           '<div class="cdv-budget-item">Page summary: ' + tokens.page + ' / ' + tokens.pageBudget + '</div>',
           '<div class="cdv-budget-item">Selection: ' + tokens.selection + '</div>',
+          '<div class="cdv-budget-item">Retrieved: ' + (tokens.retrieved || 0) + '</div>',
           '<div class="cdv-budget-item">History: ' + tokens.history + '</div>',
           '<div class="cdv-budget-item">User: ' + tokens.user + '</div>'
         ];
@@ -2747,6 +3604,8 @@
           modulePath: meta.module_path,
           title: meta.title,
           edition: meta.edition,
+          rustVersion: meta.rust_version,
+          repository: meta.repository,
           language: meta.language,
           location: meta.location,
           itemType: meta.item_type,
@@ -2799,6 +3658,7 @@
         lines.push('\nEnvironment:\n' + (layers.environment || '(none)'));
         lines.push('\nPage Summary:\n' + (layers.summary || '(none)'));
         lines.push('\nSelection:\n' + (layers.selection || '(none)'));
+        lines.push('\nRetrieved:\n' + (layers.retrieved || '(none)'));
         if (layers.history && layers.history.length) {
           lines.push('\nHistory:');
           for (var i = 0; i < layers.history.length; i++) {
@@ -2855,9 +3715,11 @@
         }
         return {
           crate: ds.currentCrate || ds.crate || ds.rootCrate || '',
-          crateVersion: ds.currentVersion || ds.crateVersion || '',
+          crateVersion: ds.currentVersion || ds.crateVersion || readCrateMeta('version'),
           modulePath: ds.currentModule || ds.modulePath || '',
-          edition: ds.edition || '',
+          edition: ds.edition || readCrateMeta('edition'),
+          repository: readCrateMeta('repository'),
+          rustVersion: readCrateMeta('rust-version'),
           language: language || 'en',
           location: (location.pathname || '') + (location.search || '') + (location.hash || ''),
           title: title,
@@ -2868,6 +3730,16 @@
         };
       }
 
+      // Values emitted by the injector from `cargo metadata`.
+      function readCrateMeta(name) {
+        try {
+          var meta = document.querySelector('meta[name="cdv-crate-' + name + '"]');
+          return meta && meta.content ? meta.content : '';
+        } catch (_) {
+          return '';
+        }
+      }
+
       function collectEnabledFeatures() {
         var features = [];
         try {
@@ -3229,6 +4101,8 @@
         var itemType = meta.itemType ? String(meta.itemType) : '';
         var title = meta.title ? String(meta.title) : '';
         var edition = meta.edition ? String(meta.edition) : '';
+        var rustVersion = meta.rustVersion ? String(meta.rustVersion) : '';
+        var repository = meta.repository ? String(meta.repository) : '';
         var location = meta.location ? String(meta.location) : '(unknown)';
         var language = meta.language ? String(meta.language) : 'en';
         var focusMode = !!meta.focusMode;
@@ -3238,6 +4112,8 @@
         if (itemType) lines.push('- Item: ' + itemType);
         if (title) lines.push('- Title: ' + title);
         if (edition) lines.push('- Edition: ' + edition);
+        if (rustVersion) lines.push('- Minimum Rust version: ' + rustVersion);
+        if (repository) lines.push('- Repository: ' + repository);
         var featureText = features && features.length ? features.join(', ') : 'none';
         lines.push('- Enabled features: ' + featureText);
         lines.push('- Doc path: ' + location);
@@ -3265,6 +4141,8 @@
             },
             environment: {
               edition: edition,
+              rust_version: rustVersion,
+              repository: repository,
               features: featureText,
               language: language
             },
@@ -3335,12 +4213,12 @@
         var defaultPrompt = state.config.prompts.system || DEFAULT_CONFIG.prompts.system;
         var trimmed = (value || '').trim();
         if (!trimmed) {
-          try { localStorage.removeItem(STORAGE_KEYS.systemPrompt); } catch (_) {}
+          saveToStorage(STORAGE_KEYS.systemPrompt, null);
           state.systemPrompt = defaultPrompt;
         } else {
           state.systemPrompt = trimmed;
           if (trimmed === defaultPrompt) {
-            try { localStorage.removeItem(STORAGE_KEYS.systemPrompt); } catch (_) {}
+            saveToStorage(STORAGE_KEYS.systemPrompt, null);
           } else {
             saveToStorage(STORAGE_KEYS.systemPrompt, trimmed);
           }
@@ -3482,6 +4360,23 @@
         return trimmed + '/chat/completions';
       }
 
+      // Pages served by `cargo doc-enhance serve` relay chat through the local
+      // server, which holds the configured credentials.
+      function resolveChatEndpoint(endpoint) {
+        if (typeof endpoint !== 'string' || !endpoint) return '';
+        var proto = String(location.protocol || '');
+        if (proto !== 'http:' && proto !== 'https:') return '';
+        return endpoint;
+      }
+
+      function buildProxyHeaders() {
+        var headers = {};
+        if (state.apiKey) {
+          headers['Authorization'] = normalizeBearer(state.apiKey);
+        }
+        return headers;
+      }
+
       function buildHeaders() {
         var headers = {};
         var src = state.config.api.headers || {};
@@ -3560,6 +4455,76 @@
         return '';
       }
 
+      // Reads an OpenAI-style SSE body (`data: {...}` events ending with
+      // `data: [DONE]`) and reports each content delta.
+      function readEventStream(body, onDelta) {
+        var reader = body.getReader();
+        var decoder = new TextDecoder();
+        var buffer = '';
+        var done = false;
+
+        function handleEvent(raw) {
+          var data = raw.split(/\r?\n/).filter(function(line){
+            return line.indexOf('data:') === 0;
+          }).map(function(line){
+            return line.slice(5).replace(/^ /, '');
+          }).join('\n');
+          if (!data) return;
+          if (data.trim() === '[DONE]') {
+            done = true;
+            return;
+          }
+          var parsed;
+          try {
+            parsed = JSON.parse(data);
+          } catch (_) {
+            return;
+          }
+          var delta = extractStreamDelta(parsed);
+          if (delta) onDelta(delta);
+        }
+
+        function pump() {
+          return reader.read().then(function(chunk){
+            if (chunk.done) {
+              buffer += decoder.decode();
+              if (buffer.trim()) handleEvent(buffer);
+              return;
+            }
+            buffer += decoder.decode(chunk.value, { stream: true });
+            var parts = buffer.split(/\r?\n\r?\n/);
+            buffer = parts.pop();
+            for (var i = 0; i < parts.length && !done; i++) {
+              handleEvent(parts[i]);
+            }
+            if (done) {
+              reader.cancel().catch(function(){});
+              return;
+            }
+            return pump();
+          });
+        }
+
+        return pump();
+      }
+
+      function extractStreamDelta(data) {
+        if (!data) return '';
+        if (data.error && data.error.message) {
+          throw new Error(data.error.message);
+        }
+        if (Array.isArray(data.choices) && data.choices.length > 0) {
+          var choice = data.choices[0];
+          if (choice.delta && typeof choice.delta.content === 'string') {
+            return choice.delta.content;
+          }
+          if (typeof choice.text === 'string') {
+            return choice.text;
+          }
+        }
+        return '';
+      }
+
       function autoResizeTextarea(el) {
         if (!el) return;
         el.style.height = 'auto';
@@ -3575,7 +4540,9 @@
         };
       }
 
+      // The API key stays in this browser; other preferences follow CDV_STATE.
       function loadFromStorage(key) {
+        if (key !== STORAGE_KEYS.apiKey) return CDV_STATE.get('chat', key);
         try {
           return localStorage.getItem(key);
         } catch (_) {
@@ -3584,8 +4551,13 @@
       }
 
       function saveToStorage(key, value) {
+        if (value === '') value = null;
+        if (key !== STORAGE_KEYS.apiKey) {
+          CDV_STATE.set('chat', key, value);
+          return;
+        }
         try {
-          if (value === null || value === undefined || value === '') {
+          if (value === null || value === undefined) {
             localStorage.removeItem(key);
           } else {
             localStorage.setItem(key, value);
@@ -7,20 +7,48 @@ use std::path::PathBuf;
 pub enum Parsed {
     Help,
     Command(CliOptions),
+    Config(ConfigCommand),
+}
+
+/// `config` subcommands; they manage configuration and never touch docs.
+#[derive(Debug)]
+pub enum ConfigCommand {
+    /// Write the commented template to the global file, or with `project` to
+    /// `.cargo-doc-viewer.yaml`; existing files are kept unless `force`.
+    Init { project: bool, force: bool },
+    /// Check every layer and exit non-zero on problems.
+    Validate,
+    /// Print the merged config; `resolved` applies placeholders and defaults.
+    Show { resolved: bool },
+    /// Print the global config path, or with `project` the project file's.
+    Path { project: bool },
+    /// Print the JSON Schema of the config file.
+    Schema,
 }
 
 #[derive(Debug)]
 pub struct CliOptions {
     pub doc_dir: PathBuf,
     pub doc_dir_was_provided: bool,
+    /// Arguments after `--`, forwarded to `cargo doc`.
+    pub cargo_args: Vec<String>,
     pub command: Command,
 }
 
 #[derive(Debug)]
 pub enum Command {
-    Serve { addr: SocketAddr },
+    Serve {
+        addr: SocketAddr,
+        watch: bool,
+        rebuild: bool,
+    },
     Enhance,
     Revert,
+    /// Copy the docs with the enhancements to `out` for static hosting.
+    Export {
+        out: PathBuf,
+        base_url: Option<String>,
+    },
 }
 
 #[derive(Debug)]
@@ -46,9 +74,18 @@ where
     let mut mode: Option<Mode> = None;
     let mut addr: Option<SocketAddr> = None;
     let mut port: Option<u16> = None;
+    let mut watch = false;
+    let mut rebuild = false;
+    let mut out: Option<PathBuf> = None;
+    let mut base_url: Option<String> = None;
+    let mut cargo_args: Vec<String> = Vec::new();
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
+            "--" => {
+                cargo_args.extend(args.by_ref());
+                break;
+            }
             "-h" | "--help" => return Ok(Parsed::Help),
             "-d" | "--doc-dir" => {
                 let value = args
@@ -75,6 +112,24 @@ where
                     .map_err(|_| CliError::new("invalid socket address for --addr"))?;
                 addr = Some(parsed);
             }
+            "-w" | "--watch" => {
+                watch = true;
+            }
+            "--rebuild" => {
+                rebuild = true;
+            }
+            "-o" | "--out" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::new("--out requires a value"))?;
+                out = Some(PathBuf::from(value));
+            }
+            "--base-url" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::new("--base-url requires a value"))?;
+                base_url = Some(value);
+            }
             "--revert" => {
                 mode = Some(Mode::Revert);
             }
@@ -85,9 +140,11 @@ where
                 return Err(CliError::new(format!("unrecognized option: {other}")));
             }
             other => match other {
+                "config" if mode.is_none() && doc_dir.is_none() => return parse_config(args),
                 "serve" | "server" => mode = Some(Mode::Serve),
                 "enhance" | "install" => mode = Some(Mode::Enhance),
                 "revert" => mode = Some(Mode::Revert),
+                "export" => mode = Some(Mode::Export),
                 _ => {
                     if doc_dir.is_none() {
                         doc_dir = Some(PathBuf::from(other));
@@ -112,6 +169,11 @@ where
     };
 
     let mode = mode.unwrap_or(Mode::Serve);
+    if !matches!(mode, Mode::Export) && (out.is_some() || base_url.is_some()) {
+        return Err(CliError::new(
+            "--out/--base-url are only valid with the export command",
+        ));
+    }
 
     match mode {
         Mode::Serve => {
@@ -119,38 +181,84 @@ where
             Ok(Parsed::Command(CliOptions {
                 doc_dir,
                 doc_dir_was_provided,
-                command: Command::Serve { addr },
+                cargo_args,
+                command: Command::Serve {
+                    addr,
+                    watch,
+                    rebuild,
+                },
             }))
         }
         Mode::Enhance => {
-            if addr.is_some() || port.is_some() {
-                return Err(CliError::new(
-                    "--addr/--port are only valid with the serve command",
-                ));
-            }
+            ensure_serve_only_flags_absent(addr, port, watch, rebuild, &cargo_args)?;
             Ok(Parsed::Command(CliOptions {
                 doc_dir,
                 doc_dir_was_provided,
+                cargo_args,
                 command: Command::Enhance,
             }))
         }
         Mode::Revert => {
-            if addr.is_some() || port.is_some() {
-                return Err(CliError::new(
-                    "--addr/--port are only valid with the serve command",
-                ));
-            }
+            ensure_serve_only_flags_absent(addr, port, watch, rebuild, &cargo_args)?;
             Ok(Parsed::Command(CliOptions {
                 doc_dir,
                 doc_dir_was_provided,
+                cargo_args,
                 command: Command::Revert,
             }))
         }
+        Mode::Export => {
+            ensure_serve_only_flags_absent(addr, port, watch, rebuild, &cargo_args)?;
+            let out = out.ok_or_else(|| CliError::new("export requires --out <dir>"))?;
+            Ok(Parsed::Command(CliOptions {
+                doc_dir,
+                doc_dir_was_provided,
+                cargo_args,
+                command: Command::Export { out, base_url },
+            }))
+        }
+    }
+}
+
+fn parse_config(mut args: impl Iterator<Item = String>) -> Result<Parsed, CliError> {
+    let Some(subcommand) = args.next() else {
+        return Err(CliError::new(
+            "config requires a subcommand (init, validate, show, path, schema)",
+        ));
+    };
+    let mut project = false;
+    let mut force = false;
+    let mut resolved = false;
+    for arg in args {
+        match (subcommand.as_str(), arg.as_str()) {
+            (_, "-h" | "--help") => return Ok(Parsed::Help),
+            ("init" | "path", "--project") => project = true,
+            ("init", "--force") => force = true,
+            ("show", "--resolved") => resolved = true,
+            ("init" | "validate" | "show" | "path" | "schema", other) => {
+                return Err(CliError::new(format!(
+                    "unexpected argument for config {subcommand}: {other}"
+                )));
+            }
+            _ => break,
+        }
     }
+    let command = match subcommand.as_str() {
+        "init" => ConfigCommand::Init { project, force },
+        "validate" => ConfigCommand::Validate,
+        "show" => ConfigCommand::Show { resolved },
+        "path" => ConfigCommand::Path { project },
+        "schema" => ConfigCommand::Schema,
+        "-h" | "--help" => return Ok(Parsed::Help),
+        other => {
+            return Err(CliError::new(format!("unknown config subcommand: {other}")));
+        }
+    };
+    Ok(Parsed::Config(command))
 }
 
 pub fn usage() -> &'static str {
-    "cargo-doc-enhance (or via \ncargo doc-enhance)\n\nUSAGE:\n  cargo-doc-enhance [serve] [-d|--doc-dir <path>] [--addr <ip:port>] [--port <port>]\n  cargo-doc-enhance enhance [-d|--doc-dir <path>]\n  cargo-doc-enhance revert [-d|--doc-dir <path>]\n\nDESCRIPTION:\n  Serve rustdoc HTML with runtime enhancements (default) or statically inject/remove them in place.\n\nEXAMPLES:\n  cargo doc && cargo doc-enhance\n  cargo doc-enhance serve --port 4200\n  cargo doc-enhance enhance --doc-dir target/doc\n  cargo doc-enhance revert --doc-dir target/doc\n"
+    "cargo-doc-enhance (or via \ncargo doc-enhance)\n\nUSAGE:\n  cargo-doc-enhance [serve] [-d|--doc-dir <path>] [--addr <ip:port>] [--port <port>] [-w|--watch] [--rebuild] [-- <cargo doc args>...]\n  cargo-doc-enhance enhance [-d|--doc-dir <path>]\n  cargo-doc-enhance revert [-d|--doc-dir <path>]\n  cargo-doc-enhance export -o|--out <dir> [-d|--doc-dir <path>] [--base-url <url>]\n  cargo-doc-enhance config init [--project] [--force]\n  cargo-doc-enhance config validate\n  cargo-doc-enhance config show [--resolved]\n  cargo-doc-enhance config path [--project]\n  cargo-doc-enhance config schema\n\nDESCRIPTION:\n  Serve rustdoc HTML with runtime enhancements (default) or statically inject/remove them in place, or export an enhanced copy for static hosting.\n\nEXAMPLES:\n  cargo doc && cargo doc-enhance\n  cargo doc-enhance serve --port 4200\n  cargo doc-enhance serve --watch\n  cargo doc-enhance serve --rebuild -- --document-private-items --features tls\n  cargo doc-enhance enhance --doc-dir target/doc\n  cargo doc-enhance revert --doc-dir target/doc\n  cargo doc-enhance export --out site --base-url https://example.github.io/mycrate/\n  cargo doc-enhance config init --project\n  cargo doc-enhance config validate\n  cargo doc-enhance config show --resolved\n  cargo doc-enhance config schema > ~/.cargo-doc-viewer/config.schema.json\n"
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -158,6 +266,32 @@ enum Mode {
     Serve,
     Enhance,
     Revert,
+    Export,
+}
+
+fn ensure_serve_only_flags_absent(
+    addr: Option<SocketAddr>,
+    port: Option<u16>,
+    watch: bool,
+    rebuild: bool,
+    cargo_args: &[String],
+) -> Result<(), CliError> {
+    if addr.is_some() || port.is_some() {
+        return Err(CliError::new(
+            "--addr/--port are only valid with the serve command",
+        ));
+    }
+    if watch || rebuild {
+        return Err(CliError::new(
+            "--watch/--rebuild are only valid with the serve command",
+        ));
+    }
+    if !cargo_args.is_empty() {
+        return Err(CliError::new(
+            "arguments after `--` are only valid with the serve command",
+        ));
+    }
+    Ok(())
 }
 
 fn finalize_addr(addr: Option<SocketAddr>, port: Option<u16>) -> Result<SocketAddr, CliError> {
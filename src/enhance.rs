@@ -3,24 +3,43 @@ use std::fs;
 use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 
-use crate::injector;
+use crate::config;
+use crate::injector::{self, Assets, Injection};
+use crate::notes::NoteIndex;
+use crate::plugins::Plugins;
 
 #[derive(Debug, Default)]
 pub struct Summary {
-    pub modified: usize,
+    /// Pages enhanced for the first time.
+    pub fresh: usize,
+    /// Enhanced pages whose earlier injection was replaced, e.g. one written
+    /// by an older version.
+    pub upgraded: usize,
+    /// Pages reverted.
+    pub reverted: usize,
+    /// Pages left as they were: already current, or nothing to revert.
+    pub untouched: usize,
+    /// Files `injector::should_skip_file` excludes.
     pub skipped: usize,
 }
 
-pub fn enhance_dir(doc_dir: &Path) -> io::Result<Summary> {
-    process_dir(doc_dir, Mode::Enhance)
+enum Outcome {
+    Fresh,
+    Upgraded,
+    Reverted,
+    Untouched,
+}
+
+pub fn enhance_dir(doc_dir: &Path, notes: &NoteIndex, plugins: &Plugins) -> io::Result<Summary> {
+    process_dir(doc_dir, Mode::Enhance(notes, plugins))
 }
 
 pub fn revert_dir(doc_dir: &Path) -> io::Result<Summary> {
     process_dir(doc_dir, Mode::Revert)
 }
 
-enum Mode {
-    Enhance,
+enum Mode<'a> {
+    Enhance(&'a NoteIndex, &'a Plugins),
     Revert,
 }
 
@@ -44,9 +63,11 @@ fn process_dir(doc_dir: &Path, mode: Mode) -> io::Result<Summary> {
                     continue;
                 }
 
-                match process_html_file(&path, &mode) {
-                    Ok(true) => summary.modified += 1,
-                    Ok(false) => summary.skipped += 1,
+                match process_html_file(doc_dir, &path, &mode) {
+                    Ok(Outcome::Fresh) => summary.fresh += 1,
+                    Ok(Outcome::Upgraded) => summary.upgraded += 1,
+                    Ok(Outcome::Reverted) => summary.reverted += 1,
+                    Ok(Outcome::Untouched) => summary.untouched += 1,
                     Err(e) => eprintln!("Failed to process {}: {e}", path.display()),
                 }
             }
@@ -56,19 +77,87 @@ fn process_dir(doc_dir: &Path, mode: Mode) -> io::Result<Summary> {
     Ok(summary)
 }
 
-fn process_html_file(path: &Path, mode: &Mode) -> io::Result<bool> {
+fn process_html_file(doc_dir: &Path, path: &Path, mode: &Mode) -> io::Result<Outcome> {
     let mut content = String::new();
     fs::File::open(path)?.read_to_string(&mut content)?;
 
-    let updated = match mode {
-        Mode::Enhance => injector::inject(&content),
-        Mode::Revert => injector::revert(&content),
+    let (outcome, html) = match mode {
+        Mode::Enhance(notes, plugins) => {
+            let page = page_path(doc_dir, path);
+            let bootstrap = format!(
+                "{}\n{}",
+                config::bootstrap_assignment(),
+                notes.script_assignment(&page)
+            );
+            match injector::inject_with(&content, &bootstrap, &Assets::Inline(plugins)) {
+                Injection::Fresh(html) => (Outcome::Fresh, Some(html)),
+                Injection::Upgraded(html) => (Outcome::Upgraded, Some(html)),
+                Injection::Untouched => (Outcome::Untouched, None),
+            }
+        }
+        Mode::Revert => match injector::revert(&content) {
+            Some(html) => (Outcome::Reverted, Some(html)),
+            None => (Outcome::Untouched, None),
+        },
     };
 
-    if let Some(modified) = updated {
-        fs::File::create(path)?.write_all(modified.as_bytes())?;
-        Ok(true)
-    } else {
-        Ok(false)
+    if let Some(html) = html {
+        fs::File::create(path)?.write_all(html.as_bytes())?;
+    }
+    Ok(outcome)
+}
+
+/// `path` relative to `doc_dir` with `/` separators, as notes refer to it.
+pub fn page_path(doc_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(doc_dir).unwrap_or(path);
+    relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_fresh_upgraded_and_untouched_pages() {
+        let root = std::env::temp_dir().join(format!("cdv-enhance-{}", std::process::id()));
+        let docs = root.join("doc");
+        fs::create_dir_all(docs.join("demo")).unwrap();
+        let page = "<html><head></head><body><h1>Demo</h1></body></html>";
+        fs::write(docs.join("demo/index.html"), page).unwrap();
+        fs::write(docs.join("demo/struct.Old.html"), page).unwrap();
+        fs::write(docs.join("search.html"), page).unwrap();
+        let notes = NoteIndex::default();
+        let plugins = Plugins::default();
+
+        let first = enhance_dir(&docs, &notes, &plugins).unwrap();
+        assert_eq!((first.fresh, first.upgraded, first.skipped), (2, 0, 1));
+
+        // Swap in an injection from an older build.
+        let old = docs.join("demo/struct.Old.html");
+        let current = fs::read_to_string(&old).unwrap();
+        let stamp = current.find("<!-- CDV: injected").unwrap();
+        let end = stamp + current[stamp..].find("-->").unwrap() + 3;
+        fs::write(
+            &old,
+            format!(
+                "{}<!-- CDV: injected -->{}",
+                &current[..stamp],
+                &current[end..]
+            ),
+        )
+        .unwrap();
+
+        let second = enhance_dir(&docs, &notes, &plugins).unwrap();
+        assert_eq!((second.fresh, second.upgraded, second.untouched), (0, 1, 1));
+        assert_eq!(fs::read_to_string(&old).unwrap(), current);
+
+        let reverted = revert_dir(&docs).unwrap();
+        assert_eq!(reverted.reverted, 2);
+        assert_eq!(fs::read_to_string(&old).unwrap(), page);
+        fs::remove_dir_all(root).unwrap();
     }
 }
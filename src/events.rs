@@ -0,0 +1,73 @@
+//! Push channel from the server to open pages.
+//!
+//! Background tasks publish [`ServerEvent`]s on an [`EventBus`]; the server
+//! relays them to every page connected to the events route as Server-Sent
+//! Events.
+
+use tokio::sync::broadcast;
+
+/// Route served by `server.rs` that streams [`ServerEvent`]s.
+pub const EVENTS_PATH: &str = "/cdv-api/events";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerEvent {
+    /// Sources changed and `cargo doc` is running again.
+    Rebuilding,
+    /// Fresh docs are on disk; pages should reload.
+    Reload,
+    /// The last `cargo doc` run failed; pages keep showing the old docs.
+    RebuildFailed,
+    /// The config was reloaded; pages loaded from now on carry the new one.
+    ConfigUpdated,
+}
+
+impl ServerEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            ServerEvent::Rebuilding => "rebuilding",
+            ServerEvent::Reload => "reload",
+            ServerEvent::RebuildFailed => "rebuild-failed",
+            ServerEvent::ConfigUpdated => "config-updated",
+        }
+    }
+
+    /// Formats the event as an SSE frame.
+    pub fn to_sse(self) -> String {
+        format!("event: {}\ndata: {{}}\n\n", self.name())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(16);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: ServerEvent) {
+        // No subscribers simply means no pages are open.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_sse_frames() {
+        assert_eq!(ServerEvent::Reload.to_sse(), "event: reload\ndata: {}\n\n");
+        assert_eq!(
+            ServerEvent::ConfigUpdated.to_sse(),
+            "event: config-updated\ndata: {}\n\n"
+        );
+    }
+}
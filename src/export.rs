@@ -0,0 +1,265 @@
+//! `export`: a copy of the docs that any static file server can host.
+//!
+//! `enhance` rewrites `target/doc` in place and inlines the UI assets into
+//! every page. `export` copies the docs to a separate directory instead and
+//! links the CSS and JS, written once under content-hashed names. It also
+//! writes what `serve` renders on demand: the crate overview and dependency
+//! pages, the service worker, a search index for the overview's item search
+//! and a `sitemap.xml`. Plugins are linked and written like the built-in
+//! assets.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::assets::{self, ASSETS_DIR};
+use crate::config;
+use crate::enhance;
+use crate::injector::{self, Assets};
+use crate::metadata::{self, Metadata};
+use crate::notes::NoteIndex;
+use crate::overview;
+use crate::plugins::Plugins;
+use crate::search;
+
+/// Read by the overview page's item search on exported sites.
+pub const SEARCH_INDEX_FILE: &str = "cdv-search-index.json";
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub pages: usize,
+    pub files: usize,
+    /// Whether `sitemap.xml` holds full URLs; it needs an absolute
+    /// `--base-url` to be accepted by search engines.
+    pub absolute_sitemap: bool,
+}
+
+/// Exports `doc_dir` to `out`, which must be empty, missing, or an earlier
+/// export. `base_url` is where the site will be hosted, e.g. `/docs/` or
+/// `https://example.com/docs/`; without it pages link to the assets with
+/// relative paths.
+pub fn export(
+    doc_dir: &Path,
+    out: &Path,
+    base_url: Option<&str>,
+    notes: &NoteIndex,
+    plugins: &Plugins,
+) -> io::Result<Summary> {
+    prepare_out(doc_dir, out)?;
+    let base_url = base_url.map(|url| format!("{}/", url.trim_end_matches('/')));
+    let bootstrap = config::bootstrap_assignment();
+
+    let mut summary = Summary {
+        absolute_sitemap: base_url
+            .as_deref()
+            .is_some_and(|url| url.starts_with("https://") || url.starts_with("http://")),
+        ..Summary::default()
+    };
+    let mut sitemap = Vec::new();
+    let mut stack: Vec<PathBuf> = vec![doc_dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            let page = enhance::page_path(doc_dir, &path);
+            let target = out.join(&page);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if path.extension() != Some(OsStr::new("html")) || injector::should_skip_file(&path) {
+                fs::copy(&path, &target)?;
+                summary.files += 1;
+                continue;
+            }
+
+            // Pages enhanced in place get their inline assets replaced.
+            let content = fs::read_to_string(&path)?;
+            let root = match &base_url {
+                Some(url) => url.clone(),
+                None => "../".repeat(page.matches('/').count()),
+            };
+            let page_bootstrap = format!("{bootstrap}\n{}", notes.script_assignment(&page));
+            let linked = Assets::Linked {
+                root: &root,
+                plugins,
+            };
+            let html = injector::inject_with(&content, &page_bootstrap, &linked)
+                .into_html()
+                .unwrap_or(content);
+            fs::write(&target, html)?;
+            summary.pages += 1;
+            if !page.starts_with("src/") {
+                sitemap.push(page);
+            }
+        }
+    }
+
+    let assets_dir = out.join(ASSETS_DIR);
+    fs::create_dir_all(&assets_dir)?;
+    fs::write(assets_dir.join(assets::css_file_name()), assets::CDV_CSS)?;
+    fs::write(assets_dir.join(assets::js_file_name()), assets::CDV_JS)?;
+    if let Some(name) = plugins.css_file_name() {
+        fs::write(assets_dir.join(name), &plugins.css)?;
+    }
+    if let Some(name) = plugins.js_file_name() {
+        fs::write(assets_dir.join(name), &plugins.js)?;
+    }
+    fs::write(out.join("cdv-sw.js"), assets::CDV_SW_JS)?;
+
+    let crates = overview::scan_crates(doc_dir)?;
+    let overview_html = with_search_index(&overview::generate_overview_html(&crates));
+    fs::write(out.join("cdv-crate-overview.html"), &overview_html)?;
+    sitemap.push("cdv-crate-overview.html".to_string());
+    // rustdoc only writes a root index with `--enable-index-page`.
+    if !doc_dir.join("index.html").exists() {
+        fs::write(out.join("index.html"), &overview_html)?;
+        sitemap.push("index.html".to_string());
+    }
+    let deps_html = overview::generate_deps_html(metadata::get().map(Metadata::graph), &crates);
+    fs::write(out.join("cdv-deps.html"), deps_html)?;
+
+    let mut items = search::scan_items(doc_dir)?;
+    for item in &mut items {
+        item.url = item.url.trim_start_matches('/').to_string();
+    }
+    let index =
+        serde_json::to_vec(&serde_json::json!({ "items": items })).map_err(io::Error::other)?;
+    fs::write(out.join(SEARCH_INDEX_FILE), index)?;
+
+    sitemap.sort();
+    fs::write(
+        out.join("sitemap.xml"),
+        render_sitemap(base_url.as_deref().unwrap_or_default(), &sitemap),
+    )?;
+    Ok(summary)
+}
+
+/// Creates `out`, clearing it first when it holds an earlier export. Any
+/// other non-empty directory is left alone.
+fn prepare_out(doc_dir: &Path, out: &Path) -> io::Result<()> {
+    if out.exists() {
+        let docs = fs::canonicalize(doc_dir)?;
+        let target = fs::canonicalize(out)?;
+        if target.starts_with(&docs) || docs.starts_with(&target) {
+            return Err(io::Error::other(format!(
+                "{} overlaps the doc directory {}",
+                out.display(),
+                doc_dir.display()
+            )));
+        }
+        let empty = fs::read_dir(out)?.next().is_none();
+        if !empty && !out.join(ASSETS_DIR).is_dir() {
+            return Err(io::Error::other(format!(
+                "{} is not empty and does not hold an earlier export",
+                out.display()
+            )));
+        }
+        fs::remove_dir_all(out)?;
+    }
+    fs::create_dir_all(out)
+}
+
+/// Points the overview's item search at the static index.
+fn with_search_index(html: &str) -> String {
+    let meta = format!("<meta name=\"cdv-search-index\" content=\"{SEARCH_INDEX_FILE}\">\n");
+    match html.find("</head>") {
+        Some(idx) => format!("{}{meta}{}", &html[..idx], &html[idx..]),
+        None => html.to_string(),
+    }
+}
+
+fn render_sitemap(base_url: &str, pages: &[String]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for page in pages {
+        xml.push_str(&format!(
+            "  <url><loc>{}</loc></url>\n",
+            overview::escape_attr(&format!("{base_url}{page}"))
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_pages_with_linked_assets() {
+        let root = std::env::temp_dir().join(format!("cdv-export-{}", std::process::id()));
+        let docs = root.join("doc");
+        let out = root.join("site");
+        fs::create_dir_all(docs.join("demo/sub")).unwrap();
+        fs::create_dir_all(docs.join("static.files")).unwrap();
+        let page = r#"<html><head><meta name="rustdoc-vars" data-current-crate="demo"></head><body><h1>Demo</h1></body></html>"#;
+        fs::write(docs.join("demo/index.html"), page).unwrap();
+        fs::write(docs.join("demo/sub/struct.Item.html"), page).unwrap();
+        fs::write(docs.join("static.files/rustdoc.css"), "body {}").unwrap();
+        let notes = NoteIndex::default();
+        let plugins = Plugins {
+            js: "window.tracker = 1;".to_string(),
+            ..Plugins::default()
+        };
+
+        let summary = export(
+            &docs,
+            &out,
+            Some("https://example.com/docs"),
+            &notes,
+            &plugins,
+        )
+        .unwrap();
+        assert_eq!(summary.pages, 2);
+        assert_eq!(summary.files, 1);
+        assert!(summary.absolute_sitemap);
+
+        let item = fs::read_to_string(out.join("demo/sub/struct.Item.html")).unwrap();
+        let css = format!(
+            "https://example.com/docs/{ASSETS_DIR}/{}",
+            assets::css_file_name()
+        );
+        assert!(item.contains(&format!(
+            r#"<link id="cdv-style" rel="stylesheet" href="{css}">"#
+        )));
+        assert!(!item.contains("<style id=\"cdv-style\">"));
+        assert!(out.join(ASSETS_DIR).join(assets::js_file_name()).is_file());
+        let plugin_js = plugins.js_file_name().unwrap();
+        assert!(out.join(ASSETS_DIR).join(&plugin_js).is_file());
+        assert!(item.contains(&format!("docs/{ASSETS_DIR}/{plugin_js}")));
+        assert!(out.join("cdv-sw.js").is_file());
+        assert!(out.join("index.html").is_file());
+        assert!(
+            fs::read_to_string(out.join("cdv-crate-overview.html"))
+                .unwrap()
+                .contains(r#"<meta name="cdv-search-index""#)
+        );
+        let sitemap = fs::read_to_string(out.join("sitemap.xml")).unwrap();
+        assert!(sitemap.contains("<loc>https://example.com/docs/demo/sub/struct.Item.html</loc>"));
+
+        // A second run replaces the earlier export; relative links without a
+        // base URL climb back to the site root.
+        let summary = export(&docs, &out, None, &notes, &plugins).unwrap();
+        assert!(!summary.absolute_sitemap);
+        let item = fs::read_to_string(out.join("demo/sub/struct.Item.html")).unwrap();
+        assert!(item.contains(&format!(
+            r#"src="../../{ASSETS_DIR}/{}""#,
+            assets::js_file_name()
+        )));
+
+        // Unrelated directories and the docs themselves are never cleared.
+        let other = root.join("other");
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("keep.txt"), "keep").unwrap();
+        assert!(export(&docs, &other, None, &notes, &plugins).is_err());
+        assert!(other.join("keep.txt").is_file());
+        assert!(export(&docs, &docs.join("demo"), None, &notes, &plugins).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
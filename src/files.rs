@@ -0,0 +1,82 @@
+//! File helpers shared by the stores under `~/.cargo-doc-viewer/` and the
+//! config writer.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Tells apart the temporary files of concurrent writes in this process.
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+/// Whether `name` can be used as a file name in the state, transcript and
+/// notes directories: short, and only ASCII letters, digits, `-` and `_`, so
+/// it cannot reach outside them.
+pub fn is_plain_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Writes `contents` beside `path` and renames it into place, so a crash never
+/// leaves a half-written file behind. Creates the parent directory.
+///
+/// The temporary name is unique to the process and the call, so two writes of
+/// the same file cannot interleave in one temporary file; the last rename wins.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(err) = fs::write(&partial, contents) {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+    fs::rename(&partial, path).inspect_err(|_| {
+        let _ = fs::remove_file(&partial);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_names_and_atomic_writes() {
+        assert!(is_plain_name("chat-panel_2"));
+        for name in ["", "../config", "a/b", "a.json", &"x".repeat(65)] {
+            assert!(!is_plain_name(name), "{name:?}");
+        }
+
+        let dir = std::env::temp_dir().join(format!("cdv-atomic-{}", process::id()));
+        let path = dir.join("nested/value.json");
+        write_atomic(&path, b"{}").unwrap();
+        write_atomic(&path, b"[]").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+
+        let writers: Vec<_> = (0..8)
+            .map(|index| {
+                let path = path.clone();
+                std::thread::spawn(move || write_atomic(&path, format!("[{index}]").as_bytes()))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(
+            contents.starts_with('[') && contents.ends_with(']'),
+            "{contents}"
+        );
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,101 +1,319 @@
+//! Adds the CDV assets to rustdoc pages and takes them out again.
+//!
+//! Pages go through `lol_html`, so tags are found by parsing rather than by
+//! searching the text: a `</body>` inside a script or an attribute, or an
+//! escaped marker in a code example, is left alone. Every injected element
+//! carries a `cdv-*` id (crate meta tags a `cdv-crate-*` name) and is removed
+//! by it, which also makes injecting an already injected page replace the
+//! earlier assets instead of adding a second copy.
+//!
+//! The `<!-- CDV: injected v<version> <hash> -->` marker records which build
+//! wrote a page; older versions wrote a bare `<!-- CDV: injected -->`.
+//!
+//! User plugins (see `plugins.rs`) follow the built-in style and script as
+//! `cdv-plugins-style` and `cdv-plugins-script`.
+
+use std::cell::Cell;
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::OnceLock;
 
-use crate::assets::{CDV_CSS, CDV_JS};
-use crate::config;
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{
+    HandlerResult, HtmlRewriter, OutputSink, Settings, doc_comments, doc_text, element,
+};
 
-pub fn inject(content: &str) -> Option<String> {
-    if content.contains("<!-- CDV: injected -->") {
-        return None;
-    }
+use crate::assets::{self, ASSETS_DIR, CDV_CSS, CDV_JS};
+use crate::metadata::{self, CrateMeta};
+use crate::overview::escape_attr;
+use crate::plugins::Plugins;
 
-    let mut modified = content.to_string();
-    let mut did_modify = false;
+const CRATE_META_PREFIX: &str = "<meta name=\"cdv-crate-";
 
-    if let Some(idx) = modified.rfind("</head>") {
-        let head_inject = format!(
-            "<!-- CDV: injected -->\n<style id=\"cdv-style\">\n{}\n</style>\n",
-            CDV_CSS
-        );
-        modified.insert_str(idx, &head_inject);
-        did_modify = true;
+const MARKER_PREFIX: &str = "CDV: injected";
+
+/// Elements written by [`inject_with`], old versions included.
+const INJECTED: [&str; 6] = [
+    "#cdv-style",
+    "#cdv-script",
+    "#cdv-bootstrap",
+    "#cdv-plugins-style",
+    "#cdv-plugins-script",
+    "meta[name^=\"cdv-crate-\"]",
+];
+
+/// How [`inject_with`] includes the UI assets.
+pub enum Assets<'a> {
+    /// Inline `<style>` and `<script>`, so pages opened from disk work.
+    Inline(&'a Plugins),
+    /// `<link>` and `<script src>` pointing at files written once under
+    /// `<root>cdv-assets/`, where `root` is the site root ending in `/`.
+    Linked { root: &'a str, plugins: &'a Plugins },
+}
+
+impl Assets<'_> {
+    fn plugins(&self) -> &Plugins {
+        match self {
+            Assets::Inline(plugins) | Assets::Linked { plugins, .. } => plugins,
+        }
     }
+}
 
-    if let Some(idx) = modified.rfind("</body>") {
-        let body_inject = format!(
-            "<script id=\"cdv-bootstrap\">\n{}\n</script>\n<script id=\"cdv-script\">\n{}\n</script>\n",
-            config::bootstrap_assignment(),
-            CDV_JS
-        );
-        let mut buffer = String::with_capacity(modified.len() + body_inject.len());
-        buffer.push_str(&modified[..idx]);
-        buffer.push_str(&body_inject);
-        buffer.push_str(&modified[idx..]);
-        modified = buffer;
-        did_modify = true;
+/// What [`inject_with`] did to a page.
+#[derive(Debug)]
+pub enum Injection {
+    /// The page had no assets before.
+    Fresh(String),
+    /// Assets from another build, or with another bootstrap, were replaced.
+    Upgraded(String),
+    /// The page already carries the same assets and bootstrap, or has no
+    /// `<head>`/`<body>` to add them to.
+    Untouched,
+}
+
+impl Injection {
+    /// The rewritten page, unless it is unchanged.
+    pub fn into_html(self) -> Option<String> {
+        match self {
+            Injection::Fresh(html) | Injection::Upgraded(html) => Some(html),
+            Injection::Untouched => None,
+        }
     }
+}
 
-    if did_modify { Some(modified) } else { None }
+/// Injects the CDV assets into `content`, using `bootstrap` as the body of the
+/// `cdv-bootstrap` script (see `config::bootstrap_assignment`).
+pub fn inject_with(content: &str, bootstrap: &str, assets: &Assets) -> Injection {
+    match rewrite(content, Some((bootstrap, assets))) {
+        Some((html, false)) => Injection::Fresh(html),
+        Some((html, true)) => Injection::Upgraded(html),
+        None => Injection::Untouched,
+    }
 }
 
+/// Removes injected assets, whichever version wrote them; `None` when the
+/// page has none.
 pub fn revert(content: &str) -> Option<String> {
-    if !content.contains("cdv-style") && !content.contains("cdv-script") {
+    rewrite(content, None).map(|(html, _)| html)
+}
+
+/// `CDV: injected v<version> <hash>`, the hash covering the CSS and JS.
+fn marker() -> &'static str {
+    static MARKER: OnceLock<String> = OnceLock::new();
+    MARKER.get_or_init(|| {
+        format!(
+            "{MARKER_PREFIX} v{} {}",
+            env!("CARGO_PKG_VERSION"),
+            assets::bundle_hash()
+        )
+    })
+}
+
+/// A streaming [`inject_with`]: feed the page to the returned rewriter in
+/// chunks of any size and it hands the result to `sink` as it goes.
+pub fn rewriter<'h, O: OutputSink>(
+    bootstrap: &'h str,
+    assets: &'h Assets<'h>,
+    sink: O,
+) -> HtmlRewriter<'h, O> {
+    build(Some((bootstrap, assets)), &Rc::default(), sink)
+}
+
+/// The rewritten page, if it changed, and whether it carried an earlier
+/// injection.
+fn rewrite(content: &str, insert: Option<(&str, &Assets)>) -> Option<(String, bool)> {
+    let mut output = Vec::with_capacity(content.len() + CDV_CSS.len() + CDV_JS.len());
+    let found = Rc::new(Cell::new(false));
+    let mut rewriter = build(insert, &found, |chunk: &[u8]| {
+        output.extend_from_slice(chunk)
+    });
+    let result = rewriter
+        .write(content.as_bytes())
+        .and_then(|()| rewriter.end());
+    if let Err(err) = result {
+        eprintln!("Warning: Failed to rewrite page: {err}");
         return None;
     }
+    let output = String::from_utf8(output).ok()?;
+    (output != content).then(|| (output, found.get()))
+}
 
-    let mut modified = content.to_string();
-    if let Some(start) = modified.find("<style id=\"cdv-style\">") {
-        if let Some(end_rel) = modified[start..].find("</style>") {
-            let end = start + end_rel + "</style>".len();
-            let end_with_newline = if modified.as_bytes().get(end).copied() == Some(b'\n') {
-                end + 1
-            } else {
-                end
-            };
-            modified.replace_range(start..end_with_newline, "");
-        }
+/// `found` is set when the page carries any part of an earlier injection.
+fn build<'h, O: OutputSink>(
+    insert: Option<(&'h str, &'h Assets<'h>)>,
+    found: &Rc<Cell<bool>>,
+    sink: O,
+) -> HtmlRewriter<'h, O> {
+    // Injected nodes are each followed by a newline; set when one is removed
+    // so the newline goes with it.
+    let removed = Rc::new(Cell::new(false));
+    let mut settings = Settings::new();
+    for selector in INJECTED {
+        let removed = removed.clone();
+        let found = found.clone();
+        settings = settings.append_element_content_handler(element!(selector, move |el| {
+            found.set(true);
+            remove(el, &removed)
+        }));
     }
+    let marker_removed = removed.clone();
+    let marker_found = found.clone();
+    settings = settings
+        .append_document_content_handler(doc_comments!(move |comment| {
+            if comment.text().trim().starts_with(MARKER_PREFIX) {
+                comment.remove();
+                marker_removed.set(true);
+                marker_found.set(true);
+            }
+            Ok(())
+        }))
+        .append_document_content_handler(doc_text!(move |text| {
+            if removed.get() && !text.as_str().is_empty() {
+                removed.set(false);
+                if let Some(rest) = text.as_str().strip_prefix('\n') {
+                    let rest = rest.to_string();
+                    text.replace(&rest, ContentType::Html);
+                }
+            }
+            Ok(())
+        }));
 
-    if let Some(start) = modified.find("<script id=\"cdv-script\">") {
-        if let Some(end_rel) = modified[start..].find("</script>") {
-            let end = start + end_rel + "</script>".len();
-            let end_with_newline = if modified.as_bytes().get(end).copied() == Some(b'\n') {
-                end + 1
-            } else {
-                end
-            };
-            modified.replace_range(start..end_with_newline, "");
-        }
+    if let Some((bootstrap, assets)) = insert {
+        // Raw HTML in a docblock can repeat `<head>` or `<body>`; only the
+        // page's own elements get the assets.
+        let head_seen = Cell::new(false);
+        let body_seen = Cell::new(false);
+        settings = settings
+            .append_element_content_handler(element!("head", move |el| {
+                if !head_seen.replace(true) {
+                    el.append(&head_html(assets), ContentType::Html);
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("meta[name=\"rustdoc-vars\"]", |el| {
+                let meta = el
+                    .get_attribute("data-current-crate")
+                    .filter(|name| !name.is_empty())
+                    .and_then(|name| metadata::get()?.crate_meta(&name));
+                if let Some(meta) = meta {
+                    el.after(&crate_meta_tags(meta), ContentType::Html);
+                }
+                Ok(())
+            }))
+            .append_element_content_handler(element!("body", move |el| {
+                if !body_seen.replace(true) {
+                    el.append(&body_html(bootstrap, assets), ContentType::Html);
+                }
+                Ok(())
+            }));
     }
+    HtmlRewriter::new(settings, sink)
+}
 
-    if let Some(start) = modified.find("<script id=\"cdv-bootstrap\">") {
-        if let Some(end_rel) = modified[start..].find("</script>") {
-            let end = start + end_rel + "</script>".len();
-            let end_with_newline = if modified.as_bytes().get(end).copied() == Some(b'\n') {
-                end + 1
-            } else {
-                end
-            };
-            modified.replace_range(start..end_with_newline, "");
-        }
+/// Removes `el`, flagging `removed` once its end tag (if any) has passed.
+fn remove(el: &mut Element, removed: &Rc<Cell<bool>>) -> HandlerResult {
+    el.remove();
+    if el.can_have_content() {
+        let removed = removed.clone();
+        el.on_end_tag(Box::new(move |_| {
+            removed.set(true);
+            Ok(())
+        }))?;
+    } else {
+        removed.set(true);
     }
+    Ok(())
+}
 
-    let modified = modified
-        .replace("<!-- CDV: injected -->\n", "")
-        .replace("<!-- CDV: injected -->", "");
+/// Goes right before `</head>`.
+fn head_html(assets: &Assets) -> String {
+    let plugins = assets.plugins();
+    let mut html = format!("<!-- {} -->\n", marker());
+    html.push_str(&style(
+        "cdv-style",
+        CDV_CSS,
+        assets::css_file_name(),
+        assets,
+    ));
+    if let Some(name) = plugins.css_file_name() {
+        html.push_str(&style("cdv-plugins-style", &plugins.css, &name, assets));
+    }
+    html
+}
 
-    if modified != content {
-        Some(modified)
-    } else {
-        None
+/// Goes right before `</body>`.
+fn body_html(bootstrap: &str, assets: &Assets) -> String {
+    let plugins = assets.plugins();
+    let mut html = format!("<script id=\"cdv-bootstrap\">\n{bootstrap}\n</script>\n");
+    html.push_str(&script(
+        "cdv-script",
+        CDV_JS,
+        assets::js_file_name(),
+        assets,
+    ));
+    if let Some(name) = plugins.js_file_name() {
+        html.push_str(&script("cdv-plugins-script", &plugins.js, &name, assets));
+    }
+    html
+}
+
+/// `css` inline, or a link to the file `name` below the assets directory.
+fn style(id: &str, css: &str, name: &str, assets: &Assets) -> String {
+    match assets {
+        Assets::Inline(_) => format!("<style id=\"{id}\">\n{css}\n</style>\n"),
+        Assets::Linked { root, .. } => format!(
+            "<link id=\"{id}\" rel=\"stylesheet\" href=\"{}\">\n",
+            escape_attr(&format!("{root}{ASSETS_DIR}/{name}"))
+        ),
+    }
+}
+
+/// [`style`] for scripts.
+fn script(id: &str, js: &str, name: &str, assets: &Assets) -> String {
+    match assets {
+        Assets::Inline(_) => format!("<script id=\"{id}\">\n{js}\n</script>\n"),
+        Assets::Linked { root, .. } => format!(
+            "<script id=\"{id}\" src=\"{}\"></script>\n",
+            escape_attr(&format!("{root}{ASSETS_DIR}/{name}"))
+        ),
     }
 }
 
+/// `<meta name="cdv-crate-*">` tags read by cdv.js for the chat environment.
+fn crate_meta_tags(meta: &CrateMeta) -> String {
+    let mut fields = vec![
+        ("version", meta.version.clone()),
+        ("edition", meta.edition.clone()),
+        ("features", meta.features.join(",")),
+    ];
+    if let Some(repository) = &meta.repository {
+        fields.push(("repository", repository.clone()));
+    }
+    if let Some(rust_version) = &meta.rust_version {
+        fields.push(("rust-version", rust_version.clone()));
+    }
+
+    fields
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "{CRATE_META_PREFIX}{name}\" content=\"{}\">\n",
+                escape_attr(&value)
+            )
+        })
+        .collect()
+}
+
 pub fn should_skip_file(path: &Path) -> bool {
     if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
         let name = name.to_lowercase();
         return matches!(
             name.as_str(),
-            "search.html" | "settings.html" | "source-src.html" | "cdv-crate-overview.html"
+            "search.html"
+                | "settings.html"
+                | "source-src.html"
+                | "cdv-crate-overview.html"
+                | "cdv-deps.html"
         );
     }
     false
@@ -105,26 +323,186 @@ pub fn should_skip_file(path: &Path) -> bool {
 mod tests {
     use super::*;
 
+    const BOOTSTRAP: &str = "window.__CDV_BOOTSTRAP__ = {};";
+
+    /// Inline assets without plugins, as `enhance` writes them.
+    fn inject(content: &str, bootstrap: &str) -> Injection {
+        inject_with(content, bootstrap, &Assets::Inline(&Plugins::default()))
+    }
+
+    /// A rustdoc page whose docs quote the tags the old text search looked
+    /// for: in a script, in attributes, in comments and in code examples.
+    const ADVERSARIAL_PAGE: &str = r#"<!DOCTYPE html><html lang="en"><head><meta charset="utf-8"><meta name="rustdoc-vars" data-root-path="../" data-current-crate="html_tricks"><title>Template in html_tricks - Rust</title>
+<script>window.TEMPLATE = "<head></head><body></body>";</script>
+</head><body class="rustdoc struct"><main><section id="main-content" class="content"><div class="main-heading"><h1>Struct <span class="struct">Template</span></h1></div>
+<details class="toggle top-doc" open><summary class="hideme"><span>Expand description</span></summary><div class="docblock"><p>Wraps a page, e.g. <code>&lt;/head&gt;&lt;/body&gt;</code>.</p>
+<div class="example-wrap"><pre class="rust rust-example-rendered"><code><span class="kw">let </span>page = <span class="string">"&lt;!-- CDV: injected --&gt;&lt;style id=\"cdv-style\"&gt;&lt;/style&gt;"</span>;</code></pre></div>
+<p title="ends with </body>">Raw HTML in a docblock:</p><body data-note="nested"><style>.tricks { color: red; }</style><!-- </body></html> --></div></details>
+<script>document.write("<!-- CDV: injected -->"); var close = "</style></script" + ">";</script>
+</section></main></body></html><!-- </body> -->
+"#;
+
     #[test]
     fn inject_adds_assets_once() {
         let original = "<html><head><title>demo</title></head><body><h1>Hi</h1></body></html>";
-        let first = inject(original).expect("first injection should modify");
-        assert!(first.contains("<!-- CDV: injected -->"));
+        let first = inject(original, BOOTSTRAP)
+            .into_html()
+            .expect("first injection should modify");
+        assert!(first.contains(&format!("<!-- {} -->", marker())));
         assert!(first.contains("id=\"cdv-style\""));
         assert!(first.contains("id=\"cdv-bootstrap\""));
         assert!(first.contains("id=\"cdv-script\""));
 
         assert!(
-            inject(&first).is_none(),
+            matches!(inject(&first, BOOTSTRAP), Injection::Untouched),
             "second injection should be skipped"
         );
     }
 
+    #[test]
+    fn renders_and_reverts_crate_meta_tags() {
+        let meta = CrateMeta {
+            version: "1.2.3".to_string(),
+            edition: "2021".to_string(),
+            features: vec!["default".to_string(), "tls".to_string()],
+            repository: Some("https://example.com/?a=1&b=2".to_string()),
+            rust_version: None,
+            ..CrateMeta::default()
+        };
+        let tags = crate_meta_tags(&meta);
+        assert!(tags.contains(r#"<meta name="cdv-crate-features" content="default,tls">"#));
+        assert!(tags.contains("a=1&amp;b=2"));
+        assert!(!tags.contains("rust-version"));
+
+        let original = "<html><head></head><body></body></html>";
+        let injected = inject(original, BOOTSTRAP)
+            .into_html()
+            .expect("should inject");
+        let stamp = format!("<!-- {} -->\n", marker());
+        let with_meta = injected.replacen(&stamp, &format!("{stamp}{tags}"), 1);
+        assert_ne!(with_meta, injected);
+        assert_eq!(revert(&with_meta).expect("should revert"), original);
+    }
+
     #[test]
     fn revert_restores_original_content() {
         let original = "<html><head></head><body></body></html>";
-        let injected = inject(original).expect("should inject");
+        let injected = inject(original, BOOTSTRAP)
+            .into_html()
+            .expect("should inject");
         let reverted = revert(&injected).expect("should revert");
         assert_eq!(reverted, original);
+
+        let plugins = Plugins {
+            css: ".tracker {}".to_string(),
+            js: "window.tracker = 1;".to_string(),
+            ..Plugins::default()
+        };
+        let linked = Assets::Linked {
+            root: "../",
+            plugins: &plugins,
+        };
+        let injected = inject_with(original, BOOTSTRAP, &linked)
+            .into_html()
+            .expect("should inject");
+        assert!(injected.contains(&format!(
+            r#"<script id="cdv-script" src="../cdv-assets/{}"></script>"#,
+            assets::js_file_name()
+        )));
+        // Plugins come after the built-in assets.
+        let plugin_js = plugins.js_file_name().unwrap();
+        assert!(injected.contains(&format!(
+            "{}\"></script>\n<script id=\"cdv-plugins-script\" src=\"../cdv-assets/{plugin_js}\">",
+            assets::js_file_name()
+        )));
+        assert_eq!(revert(&injected).expect("should revert"), original);
+
+        let injected = inject_with(original, BOOTSTRAP, &Assets::Inline(&plugins))
+            .into_html()
+            .expect("should inject");
+        assert!(
+            injected.contains("</style>\n<style id=\"cdv-plugins-style\">\n.tracker {}\n</style>")
+        );
+        assert_eq!(revert(&injected).expect("should revert"), original);
+    }
+
+    #[test]
+    fn injects_by_parsing_adversarial_pages() {
+        let injected = inject(ADVERSARIAL_PAGE, BOOTSTRAP)
+            .into_html()
+            .expect("should inject");
+        assert_eq!(injected.matches("<style id=\"cdv-style\">").count(), 1);
+        assert_eq!(injected.matches("<script id=\"cdv-script\">").count(), 1);
+        // The assets sit at the real end of `<head>` and `<body>`.
+        let head_end = injected.find("\n</head><body class=").unwrap();
+        assert!(injected[..head_end].ends_with("\n</style>"));
+        let bootstrap = injected.find("<script id=\"cdv-bootstrap\">").unwrap();
+        assert!(injected[..bootstrap].ends_with("</section></main>"));
+        assert!(injected.contains("</script>\n</body></html><!-- </body> -->\n"));
+        assert!(injected.contains(r#"window.TEMPLATE = "<head></head><body></body>";"#));
+        assert!(injected.ends_with("</body></html><!-- </body> -->\n"));
+
+        assert!(matches!(inject(&injected, BOOTSTRAP), Injection::Untouched));
+        let reinjected = inject(&injected, "window.__CDV_BOOTSTRAP__ = {\"changed\": true};")
+            .into_html()
+            .expect("a new bootstrap replaces the old one");
+        assert_eq!(reinjected.matches("id=\"cdv-bootstrap\"").count(), 1);
+        assert_eq!(
+            revert(&reinjected).expect("should revert"),
+            ADVERSARIAL_PAGE
+        );
+        assert!(revert(ADVERSARIAL_PAGE).is_none());
+    }
+
+    #[test]
+    fn streaming_matches_whole_page_rewrites() {
+        let plugins = Plugins::default();
+        let linked = Assets::Linked {
+            root: "/",
+            plugins: &plugins,
+        };
+        let whole = inject_with(ADVERSARIAL_PAGE, BOOTSTRAP, &linked)
+            .into_html()
+            .unwrap();
+        for size in [1, 7, 64] {
+            let mut streamed = Vec::new();
+            let mut rewriter = rewriter(BOOTSTRAP, &linked, |chunk: &[u8]| {
+                streamed.extend_from_slice(chunk)
+            });
+            for chunk in ADVERSARIAL_PAGE.as_bytes().chunks(size) {
+                rewriter.write(chunk).unwrap();
+            }
+            rewriter.end().unwrap();
+            assert_eq!(
+                String::from_utf8(streamed).unwrap(),
+                whole,
+                "chunks of {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn upgrades_and_reverts_older_formats() {
+        let original = "<html><head><title>demo</title></head><body><h1>Hi</h1></body></html>";
+        // As written before markers were stamped: a bare marker, the crate
+        // meta tags after it, and inline assets.
+        let unstamped = "<html><head><title>demo</title><!-- CDV: injected -->\n<meta name=\"cdv-crate-version\" content=\"0.1.0\">\n<style id=\"cdv-style\">\n.old {}\n</style>\n</head><body><h1>Hi</h1><script id=\"cdv-bootstrap\">\nwindow.__CDV_BOOTSTRAP__ = {};\n</script>\n<script id=\"cdv-script\">\nold();\n</script>\n</body></html>";
+        assert_eq!(revert(unstamped).expect("should revert"), original);
+
+        let upgraded = match inject(unstamped, BOOTSTRAP) {
+            Injection::Upgraded(html) => html,
+            other => panic!("expected an upgrade, got {other:?}"),
+        };
+        assert!(!upgraded.contains("old()"));
+        assert_eq!(upgraded.matches("<!-- CDV: injected").count(), 1);
+        assert!(matches!(inject(original, BOOTSTRAP), Injection::Fresh(_)));
+        assert_eq!(
+            inject(original, BOOTSTRAP).into_html().as_deref(),
+            Some(upgraded.as_str())
+        );
+
+        let stale = upgraded.replace(marker(), "CDV: injected v0.0.1 0123456789");
+        assert!(matches!(inject(&stale, BOOTSTRAP), Injection::Upgraded(_)));
+        assert_eq!(revert(&stale).expect("should revert"), original);
     }
 }
@@ -0,0 +1,436 @@
+//! Server-side chat forwarding.
+//!
+//! The chat panel posts its assembled context layers to `/cdv-api/chat` and
+//! the server relays them to the provider of the selected profile, attaching
+//! the headers from its config so credentials stay in this process instead of
+//! being embedded in every page. Replies are translated back into the OpenAI
+//! chat-completions format by [`providers`]; requests with `stream: true` are
+//! relayed as Server-Sent Events.
+
+use std::fmt;
+use std::time::Duration;
+
+use hyper::body::{self, HttpBody};
+use hyper::client::HttpConnector;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ApiConfig;
+use crate::providers::{self, StreamTranslator, WireFormat};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatRequest {
+    #[serde(default)]
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    /// Config profile to use; `api` when absent.
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<ChatMetadata>,
+}
+
+/// Where the question was asked, as reported by the panel.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ChatMetadata {
+    pub doc_path: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub module: String,
+}
+
+#[derive(Debug)]
+pub struct UpstreamResponse {
+    pub status: StatusCode,
+    pub content_type: Option<HeaderValue>,
+    /// Fully buffered for regular requests; for streaming requests this is
+    /// the live upstream body, so dropping it closes the provider connection.
+    pub body: Body,
+}
+
+#[derive(Debug)]
+pub enum ChatError {
+    InvalidRequest(String),
+    Timeout,
+    Upstream(String),
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatError::InvalidRequest(message) => write!(f, "invalid chat request: {message}"),
+            ChatError::Timeout => f.write_str("upstream chat request timed out"),
+            ChatError::Upstream(message) => write!(f, "upstream chat request failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+pub struct ChatClient {
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+}
+
+impl ChatClient {
+    pub fn new() -> Self {
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder().build(connector),
+        }
+    }
+
+    /// Forwards `request` to the configured provider.
+    ///
+    /// `fallback_auth` is the `Authorization` header sent by the browser (the
+    /// key a user saved in the panel); it is only used when the config does
+    /// not define one itself.
+    pub async fn forward(
+        &self,
+        api: &ApiConfig,
+        request: &ChatRequest,
+        fallback_auth: Option<&str>,
+    ) -> Result<UpstreamResponse, ChatError> {
+        if request.messages.is_empty() {
+            return Err(ChatError::InvalidRequest(
+                "no messages supplied".to_string(),
+            ));
+        }
+        let Some(provider) = WireFormat::of(api.provider) else {
+            return Err(ChatError::InvalidRequest(
+                "the mock provider is answered by the server itself".to_string(),
+            ));
+        };
+
+        let model = request
+            .model
+            .as_deref()
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .unwrap_or(api.model.as_str());
+        let payload = providers::encode_request(provider, model, &request.messages, request.stream)
+            .map_err(|err| ChatError::InvalidRequest(err.to_string()))?;
+
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .uri(providers::chat_url(provider, &api.base_url))
+            .header(header::CONTENT_TYPE, "application/json");
+
+        let (auth_name, _) = providers::auth_header(provider, "");
+        let mut has_auth = false;
+        for (name, value) in &api.headers {
+            if value.trim().is_empty() {
+                continue;
+            }
+            if name.eq_ignore_ascii_case(auth_name) {
+                has_auth = true;
+                builder = builder.header(name.as_str(), providers::auth_header(provider, value).1);
+            } else {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+        }
+        if !has_auth && let Some(auth) = fallback_auth.filter(|v| !v.trim().is_empty()) {
+            builder = builder.header(auth_name, providers::auth_header(provider, auth).1);
+        }
+        for (name, value) in providers::default_headers(provider) {
+            if !api.headers.keys().any(|key| key.eq_ignore_ascii_case(name)) {
+                builder = builder.header(*name, *value);
+            }
+        }
+
+        let upstream = builder
+            .body(Body::from(payload))
+            .map_err(|err| ChatError::InvalidRequest(err.to_string()))?;
+
+        // For streams the timeout only covers the wait for response headers;
+        // tokens may keep arriving for as long as the provider needs.
+        let timeout = Duration::from_millis(api.timeout_ms);
+        let stream = request.stream;
+        let exchange = async {
+            let response = self.client.request(upstream).await?;
+            let status = response.status();
+            let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+            if stream && status.is_success() {
+                if provider == WireFormat::OpenAi {
+                    return Ok(UpstreamResponse {
+                        status,
+                        content_type,
+                        body: response.into_body(),
+                    });
+                }
+                return Ok(UpstreamResponse {
+                    status,
+                    content_type: Some(HeaderValue::from_static("text/event-stream")),
+                    body: translate_stream(provider, response.into_body()),
+                });
+            }
+
+            let raw = body::to_bytes(response.into_body()).await?;
+            let body = if status.is_success() {
+                providers::translate_response(provider, &raw).map_err(ChatError::Upstream)?
+            } else {
+                providers::translate_error(&raw)
+            };
+            let content_type = if provider == WireFormat::OpenAi {
+                content_type
+            } else {
+                Some(HeaderValue::from_static("application/json"))
+            };
+            Ok(UpstreamResponse {
+                status,
+                content_type,
+                body: Body::from(body),
+            })
+        };
+
+        match tokio::time::timeout(timeout, exchange).await {
+            Ok(result) => result,
+            Err(_) => Err(ChatError::Timeout),
+        }
+    }
+}
+
+impl From<hyper::Error> for ChatError {
+    fn from(err: hyper::Error) -> Self {
+        ChatError::Upstream(err.to_string())
+    }
+}
+
+/// Relays a provider stream through a [`StreamTranslator`]. Once the browser
+/// goes away the send fails and the upstream body is dropped with the task.
+fn translate_stream(provider: WireFormat, mut upstream: Body) -> Body {
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut translator = StreamTranslator::new(provider);
+        while let Some(chunk) = upstream.data().await {
+            let Ok(chunk) = chunk else {
+                sender.abort();
+                return;
+            };
+            let frames = translator.push(&chunk);
+            if !frames.is_empty() && sender.send_data(frames.into()).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender.send_data(translator.finish().into()).await;
+    });
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server};
+
+    use super::*;
+    use crate::config::Provider;
+
+    /// What the mock provider received.
+    #[derive(Debug, Default, Clone)]
+    struct Captured {
+        path: String,
+        headers: Vec<(String, String)>,
+        body: serde_json::Value,
+    }
+
+    impl Captured {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Serves one canned reply on a local port and records the request.
+    async fn mock_provider(
+        status: u16,
+        content_type: &'static str,
+        reply: &'static str,
+    ) -> (SocketAddr, Arc<Mutex<Captured>>) {
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let sink = captured.clone();
+        let make = make_service_fn(move |_| {
+            let sink = sink.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let sink = sink.clone();
+                    async move {
+                        let path = req.uri().path().to_string();
+                        let headers = req
+                            .headers()
+                            .iter()
+                            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                            .collect();
+                        let raw = body::to_bytes(req.into_body()).await.unwrap_or_default();
+                        *sink.lock().unwrap() = Captured {
+                            path,
+                            headers,
+                            body: serde_json::from_slice(&raw).unwrap_or_default(),
+                        };
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .header(header::CONTENT_TYPE, content_type)
+                                .body(Body::from(reply))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, captured)
+    }
+
+    fn api(provider: Provider, base_url: String) -> ApiConfig {
+        ApiConfig {
+            provider,
+            base_url,
+            model: "test-model".to_string(),
+            ..ApiConfig::default()
+        }
+    }
+
+    fn request(stream: bool) -> ChatRequest {
+        ChatRequest {
+            model: None,
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: "be brief".to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: "hi".to_string(),
+                },
+            ],
+            stream,
+            profile: None,
+            metadata: None,
+        }
+    }
+
+    async fn reply_text(response: UpstreamResponse) -> String {
+        let bytes = body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    const HI_EVENTS: &str =
+        "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"index\":0}]}\n\ndata: [DONE]\n\n";
+
+    #[tokio::test]
+    async fn openai_requests_pass_through() {
+        let reply = r#"{"choices":[{"message":{"role":"assistant","content":"hello"}}]}"#;
+        let (addr, captured) = mock_provider(200, "application/json", reply).await;
+        let config = api(Provider::OpenAi, format!("http://{addr}/v1"));
+        let response = ChatClient::new()
+            .forward(&config, &request(false), Some("sk-browser"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(reply_text(response).await, reply);
+        let captured = captured.lock().unwrap().clone();
+        assert_eq!(captured.path, "/v1/chat/completions");
+        assert_eq!(captured.header("authorization"), Some("Bearer sk-browser"));
+        assert_eq!(captured.body["messages"][0]["role"], "system");
+        assert_eq!(captured.body["model"], "test-model");
+    }
+
+    #[tokio::test]
+    async fn anthropic_replies_are_translated() {
+        let reply = r#"{"content":[{"type":"text","text":"hello"}],"stop_reason":"end_turn"}"#;
+        let (addr, captured) = mock_provider(200, "application/json", reply).await;
+        let mut config = api(Provider::Anthropic, format!("http://{addr}/v1"));
+        config
+            .headers
+            .insert("x-api-key".to_string(), "sk-ant-config".to_string());
+        let response = ChatClient::new()
+            .forward(&config, &request(false), Some("sk-browser"))
+            .await
+            .unwrap();
+
+        let completion: serde_json::Value =
+            serde_json::from_str(&reply_text(response).await).unwrap();
+        assert_eq!(completion["choices"][0]["message"]["content"], "hello");
+        assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+
+        let captured = captured.lock().unwrap().clone();
+        assert_eq!(captured.path, "/v1/messages");
+        assert_eq!(captured.header("x-api-key"), Some("sk-ant-config"));
+        assert_eq!(captured.header("anthropic-version"), Some("2023-06-01"));
+        assert_eq!(captured.header("authorization"), None);
+        assert_eq!(captured.body["system"], "be brief");
+        assert_eq!(captured.body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn anthropic_streams_become_chat_completion_events() {
+        let reply = "event: message_start\ndata: {\"type\":\"message_start\"}\n\n\
+                     event: content_block_delta\n\
+                     data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n\
+                     event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n";
+        let (addr, _) = mock_provider(200, "text/event-stream", reply).await;
+        let config = api(Provider::Anthropic, format!("http://{addr}/v1"));
+        let response = ChatClient::new()
+            .forward(&config, &request(true), None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.content_type,
+            Some(HeaderValue::from_static("text/event-stream"))
+        );
+        assert_eq!(reply_text(response).await, HI_EVENTS);
+    }
+
+    #[tokio::test]
+    async fn ollama_streams_and_errors_are_translated() {
+        let reply = "{\"message\":{\"role\":\"assistant\",\"content\":\"Hi\"},\"done\":false}\n\
+                     {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n";
+        let (addr, captured) = mock_provider(200, "application/x-ndjson", reply).await;
+        let config = api(Provider::Ollama, format!("http://{addr}"));
+        let response = ChatClient::new()
+            .forward(&config, &request(true), None)
+            .await
+            .unwrap();
+        assert_eq!(reply_text(response).await, HI_EVENTS);
+        let captured = captured.lock().unwrap().clone();
+        assert_eq!(captured.path, "/api/chat");
+        assert_eq!(captured.body["stream"], true);
+
+        let (addr, _) =
+            mock_provider(404, "application/json", r#"{"error":"model not found"}"#).await;
+        let config = api(Provider::Ollama, format!("http://{addr}"));
+        let response = ChatClient::new()
+            .forward(&config, &request(false), None)
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            reply_text(response).await,
+            r#"{"error":{"message":"model not found"}}"#
+        );
+    }
+}
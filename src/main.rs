@@ -1,10 +1,27 @@
 mod assets;
 mod cli;
 mod config;
+mod docgen;
 mod enhance;
+mod events;
+mod export;
+mod files;
 mod injector;
+mod llm;
+mod metadata;
+mod mock;
+mod notes;
 mod overview;
+mod plugins;
+mod providers;
+mod retrieval;
+mod search;
+mod secrets;
 mod server;
+mod settings;
+mod state;
+mod transcripts;
+mod watch;
 
 use std::env;
 
@@ -19,12 +36,12 @@ async fn main() {
 
 async fn run() -> Result<(), i32> {
     let mut args: Vec<String> = env::args().collect();
-    if let Some(first) = args.get(1) {
-        if first == "doc-enhance" || first == "doc-viewer" {
-            args.remove(1);
-        }
+    if let Some(first) = args.get(1)
+        && (first == "doc-enhance" || first == "doc-viewer")
+    {
+        args.remove(1);
     }
-    let parsed = match cli::parse(args.into_iter()) {
+    let parsed = match cli::parse(args) {
         Ok(parsed) => parsed,
         Err(err) => {
             eprintln!("{err}");
@@ -39,30 +56,53 @@ async fn run() -> Result<(), i32> {
             println!("{}", cli::usage());
             Ok(())
         }
-        Parsed::Command(options) => {
-            ensure_doc_dir(&options)?;
+        Parsed::Config(command) => run_config(command),
+        Parsed::Command(mut options) => {
+            if !matches!(options.command, Command::Revert) {
+                config::report_layers();
+                plugins::report();
+            }
+            let cargo_args = doc_args(&options);
+            load_metadata(&options, &cargo_args);
+            default_doc_dir(&mut options, &cargo_args);
+            ensure_doc_dir(&options, &cargo_args)?;
 
             match options.command {
-                Command::Serve { addr } => match server::run(&options.doc_dir, addr).await {
-                    Ok(()) => Ok(()),
-                    Err(err) => {
-                        eprintln!("Error while serving docs: {err}");
-                        Err(1)
+                Command::Serve { addr, watch, .. } => {
+                    let events = events::EventBus::new();
+                    if let Err(err) = watch::spawn_config(events.clone()) {
+                        eprintln!("Warning: Config changes need a restart: {err}");
                     }
-                },
+                    if watch {
+                        start_watch(cargo_args, &events)?;
+                    }
+                    match server::run(&options.doc_dir, addr, events).await {
+                        Ok(()) => Ok(()),
+                        Err(err) => {
+                            eprintln!("Error while serving docs: {err}");
+                            Err(1)
+                        }
+                    }
+                }
                 Command::Enhance => {
+                    warn_static_credentials("enhanced");
                     if let Err(e) = overview::generate_overview_page(&options.doc_dir) {
                         eprintln!("Warning: Failed to generate crate overview: {e}");
                     }
 
-                    match enhance::enhance_dir(&options.doc_dir) {
+                    let notes = notes::NoteStore::discover();
+                    let plugins = plugins::Plugins::load();
+                    match enhance::enhance_dir(&options.doc_dir, &notes.index(), &plugins) {
                         Ok(summary) => {
                             println!(
-                                "Enhanced docs under {} (modified {} files, skipped {}).",
+                                "Enhanced docs under {} ({} fresh, {} upgraded, {} untouched, skipped {}).",
                                 options.doc_dir.display(),
-                                summary.modified,
+                                summary.fresh,
+                                summary.upgraded,
+                                summary.untouched,
                                 summary.skipped
                             );
+                            notes.report_orphaned(&options.doc_dir);
                             println!(
                                 "Open the docs as usual (e.g., target/doc/<crate>/index.html)."
                             );
@@ -74,6 +114,39 @@ async fn run() -> Result<(), i32> {
                         }
                     }
                 }
+                Command::Export { out, base_url } => {
+                    warn_static_credentials("exported");
+                    let notes = notes::NoteStore::discover();
+                    let plugins = plugins::Plugins::load();
+                    match export::export(
+                        &options.doc_dir,
+                        &out,
+                        base_url.as_deref(),
+                        &notes.index(),
+                        &plugins,
+                    ) {
+                        Ok(summary) => {
+                            println!(
+                                "Exported {} pages and {} other files from {} to {}.",
+                                summary.pages,
+                                summary.files,
+                                options.doc_dir.display(),
+                                out.display()
+                            );
+                            if !summary.absolute_sitemap {
+                                println!(
+                                    "Note: sitemap.xml holds relative URLs; pass an absolute --base-url (https://...) for search engines."
+                                );
+                            }
+                            notes.report_orphaned(&options.doc_dir);
+                            Ok(())
+                        }
+                        Err(err) => {
+                            eprintln!("Error exporting docs: {err}");
+                            Err(1)
+                        }
+                    }
+                }
                 Command::Revert => match enhance::revert_dir(&options.doc_dir) {
                     Ok(summary) => {
                         if let Err(e) = overview::remove_overview_page(&options.doc_dir) {
@@ -82,8 +155,8 @@ async fn run() -> Result<(), i32> {
                         println!(
                             "Reverted enhancements under {} (modified {} files, skipped {}).",
                             options.doc_dir.display(),
-                            summary.modified,
-                            summary.skipped
+                            summary.reverted,
+                            summary.untouched + summary.skipped
                         );
                         Ok(())
                     }
@@ -97,13 +170,124 @@ async fn run() -> Result<(), i32> {
     }
 }
 
-fn ensure_doc_dir(options: &cli::CliOptions) -> Result<(), i32> {
-    if options.doc_dir.exists() {
+fn run_config(command: cli::ConfigCommand) -> Result<(), i32> {
+    match command {
+        cli::ConfigCommand::Init { project, force } => match config::init_config(project, force) {
+            Ok(path) => {
+                println!("Wrote config template to {}", path.display());
+                Ok(())
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                Err(1)
+            }
+        },
+        cli::ConfigCommand::Validate => {
+            let problems = config::validate();
+            if problems.is_empty() {
+                println!("Config is valid.");
+                return Ok(());
+            }
+            for problem in &problems {
+                eprintln!("{problem}");
+            }
+            eprintln!(
+                "Found {} config problem{}.",
+                problems.len(),
+                if problems.len() == 1 { "" } else { "s" }
+            );
+            Err(1)
+        }
+        cli::ConfigCommand::Path { project } => {
+            let path = if project {
+                config::project_config_path()
+            } else {
+                Some(config::config_path())
+            };
+            match path {
+                Some(path) => {
+                    println!("{}", path.display());
+                    Ok(())
+                }
+                None => {
+                    eprintln!("Unable to determine current directory.");
+                    Err(1)
+                }
+            }
+        }
+        cli::ConfigCommand::Schema => {
+            match serde_json::to_string_pretty(&config::config_schema()) {
+                Ok(schema) => {
+                    println!("{schema}");
+                    Ok(())
+                }
+                Err(err) => {
+                    eprintln!("Failed to render the config schema: {err}");
+                    Err(1)
+                }
+            }
+        }
+        cli::ConfigCommand::Show { resolved } => {
+            for layer in config::config_layers() {
+                println!("# {}: {}", layer.label, layer.path.display());
+            }
+            print!("{}", config::render_config(resolved));
+            Ok(())
+        }
+    }
+}
+
+/// Credentials never reach pages written to disk; says so when the config
+/// has some, so a missing chat key is not a surprise.
+fn warn_static_credentials(pages: &str) {
+    let credentials = config::credential_fields();
+    if !credentials.is_empty() {
+        eprintln!(
+            "Warning: The config contains credentials ({}). They are left out of the {pages} pages; enter an API key in the chat panel, or use `serve` to keep them on the server.",
+            credentials.join(", ")
+        );
+    }
+}
+
+/// Resolves the `cargo doc` arguments for serve: CLI args after `--`, else
+/// `doc.args` from config.
+fn doc_args(options: &cli::CliOptions) -> Vec<String> {
+    if !matches!(options.command, Command::Serve { .. }) {
+        return Vec::new();
+    }
+
+    if options.cargo_args.is_empty() {
+        config::app_config().doc.args.clone()
+    } else {
+        options.cargo_args.clone()
+    }
+}
+
+/// Points serve's default doc dir at the output of `cargo doc` with
+/// `cargo_args`, in the target directory `cargo metadata` reports.
+fn default_doc_dir(options: &mut cli::CliOptions, cargo_args: &[String]) {
+    if !matches!(options.command, Command::Serve { .. }) || options.doc_dir_was_provided {
+        return;
+    }
+    if let Ok(cwd) = env::current_dir() {
+        let target_directory = metadata::get().and_then(|metadata| metadata.target_directory());
+        options.doc_dir = docgen::doc_output_dir(&cwd, cargo_args, target_directory);
+    }
+}
+
+fn ensure_doc_dir(options: &cli::CliOptions, cargo_args: &[String]) -> Result<(), i32> {
+    let rebuild = matches!(options.command, Command::Serve { rebuild: true, .. });
+    if rebuild && !is_rust_project_root() {
+        eprintln!("--rebuild must be run from a Cargo project root (no Cargo.toml found).");
+        return Err(1);
+    }
+
+    if options.doc_dir.exists() && !rebuild {
         return Ok(());
     }
 
-    if should_generate_docs(options) {
-        match generate_docs() {
+    if rebuild || should_generate_docs(options) {
+        match generate_docs(cargo_args) {
             Ok(()) => {
                 if options.doc_dir.exists() {
                     return Ok(());
@@ -120,6 +304,17 @@ fn ensure_doc_dir(options: &cli::CliOptions) -> Result<(), i32> {
     Err(1)
 }
 
+/// Reads `cargo metadata` for the project in the current directory so
+/// injected pages can describe their crate; skipped outside a Cargo project.
+fn load_metadata(options: &cli::CliOptions, cargo_args: &[String]) {
+    if matches!(options.command, Command::Revert) || !is_rust_project_root() {
+        return;
+    }
+    if let Ok(dir) = env::current_dir() {
+        metadata::init(&dir, cargo_args);
+    }
+}
+
 fn should_generate_docs(options: &cli::CliOptions) -> bool {
     !options.doc_dir_was_provided
         && matches!(options.command, Command::Serve { .. })
@@ -133,7 +328,7 @@ fn is_rust_project_root() -> bool {
     }
 }
 
-fn generate_docs() -> Result<(), i32> {
+fn generate_docs(cargo_args: &[String]) -> Result<(), i32> {
     let dir = match env::current_dir() {
         Ok(dir) => dir,
         Err(err) => {
@@ -142,16 +337,13 @@ fn generate_docs() -> Result<(), i32> {
         }
     };
 
-    println!(
-        "Doc directory not found; running `cargo doc` in {}...",
-        dir.display()
-    );
+    let command = std::iter::once("cargo doc")
+        .chain(cargo_args.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!("Running `{command}` in {}...", dir.display());
 
-    match std::process::Command::new("cargo")
-        .arg("doc")
-        .current_dir(&dir)
-        .status()
-    {
+    match docgen::cargo_doc(&dir, cargo_args) {
         Ok(status) if status.success() => {
             println!("`cargo doc` completed successfully.");
             Ok(())
@@ -166,3 +358,18 @@ fn generate_docs() -> Result<(), i32> {
         }
     }
 }
+
+fn start_watch(cargo_args: Vec<String>, events: &events::EventBus) -> Result<(), i32> {
+    if !is_rust_project_root() {
+        eprintln!("--watch must be run from a Cargo project root (no Cargo.toml found).");
+        return Err(1);
+    }
+    let dir = env::current_dir().map_err(|err| {
+        eprintln!("Unable to determine current directory: {err}");
+        1
+    })?;
+    watch::spawn(dir, cargo_args, events.clone()).map_err(|err| {
+        eprintln!("Failed to start file watcher: {err}");
+        1
+    })
+}
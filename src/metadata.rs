@@ -0,0 +1,458 @@
+//! Crate metadata from `cargo metadata`.
+//!
+//! rustdoc output carries little beyond the crate name, so the tool runs
+//! `cargo metadata` once at startup and keeps the results for the injector
+//! (per-crate `<meta name="cdv-crate-*">` tags) and other pages.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+
+static METADATA: OnceCell<Metadata> = OnceCell::new();
+
+/// Facts about one documented crate, keyed by its rustdoc directory name.
+#[derive(Debug, Clone, Default)]
+pub struct CrateMeta {
+    pub version: String,
+    pub edition: String,
+    pub features: Vec<String>,
+    pub repository: Option<String>,
+    pub rust_version: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub workspace_member: bool,
+}
+
+/// The resolved dependency graph, one node per package.
+#[derive(Debug, Clone, Default)]
+pub struct DepGraph {
+    pub nodes: Vec<DepNode>,
+    pub edges: Vec<DepEdge>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DepNode {
+    pub name: String,
+    pub version: String,
+    /// Directory rustdoc writes the package's library docs to.
+    pub doc_name: String,
+    pub workspace_member: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: DepKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+#[derive(Debug, Default)]
+pub struct Metadata {
+    crates: HashMap<String, CrateMeta>,
+    graph: DepGraph,
+    target_directory: Option<PathBuf>,
+    workspace_root: Option<PathBuf>,
+    member_dirs: Vec<PathBuf>,
+}
+
+impl Metadata {
+    /// Looks up a crate by the name rustdoc uses (`data-current-crate`).
+    pub fn crate_meta(&self, doc_name: &str) -> Option<&CrateMeta> {
+        self.crates.get(doc_name)
+    }
+
+    pub fn graph(&self) -> &DepGraph {
+        &self.graph
+    }
+
+    /// Cargo's target directory, after `CARGO_TARGET_DIR`, `build.target-dir`
+    /// and the workspace root are taken into account.
+    pub fn target_directory(&self) -> Option<&Path> {
+        self.target_directory.as_deref()
+    }
+
+    pub fn workspace_root(&self) -> Option<&Path> {
+        self.workspace_root.as_deref()
+    }
+
+    /// Package directories of the workspace members.
+    pub fn member_dirs(&self) -> &[PathBuf] {
+        &self.member_dirs
+    }
+
+    fn from_json(raw: &[u8]) -> serde_json::Result<Self> {
+        let output: CargoMetadata = serde_json::from_slice(raw)?;
+
+        let mut enabled: HashMap<&str, Vec<String>> = HashMap::new();
+        if let Some(resolve) = &output.resolve {
+            for node in &resolve.nodes {
+                let mut features = node.features.clone();
+                features.sort();
+                enabled.insert(node.id.as_str(), features);
+            }
+        }
+
+        let mut crates = HashMap::new();
+        // Workspace members win when a dependency produces the same doc dir.
+        let mut packages: Vec<&Package> = output.packages.iter().collect();
+        packages.sort_by_key(|pkg| !output.workspace_members.contains(&pkg.id));
+
+        for pkg in packages {
+            let meta = CrateMeta {
+                version: pkg.version.clone(),
+                edition: pkg.edition.clone(),
+                features: enabled.get(pkg.id.as_str()).cloned().unwrap_or_default(),
+                repository: pkg.repository.clone(),
+                rust_version: pkg.rust_version.clone(),
+                description: pkg.description.clone(),
+                license: pkg.license.clone(),
+                workspace_member: output.workspace_members.contains(&pkg.id),
+            };
+            for name in pkg.doc_names() {
+                crates.entry(name).or_insert_with(|| meta.clone());
+            }
+        }
+
+        let member_dirs = output
+            .packages
+            .iter()
+            .filter(|pkg| output.workspace_members.contains(&pkg.id))
+            .filter_map(|pkg| Some(pkg.manifest_path.as_deref()?.parent()?.to_path_buf()))
+            .collect();
+        let graph = DepGraph::from_metadata(&output);
+        Ok(Self {
+            crates,
+            graph,
+            target_directory: output.target_directory,
+            workspace_root: output.workspace_root,
+            member_dirs,
+        })
+    }
+}
+
+impl DepGraph {
+    fn from_metadata(output: &CargoMetadata) -> Self {
+        let Some(resolve) = &output.resolve else {
+            return Self::default();
+        };
+
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut nodes = Vec::new();
+        for pkg in &output.packages {
+            if !resolve.nodes.iter().any(|node| node.id == pkg.id) {
+                continue;
+            }
+            index.insert(pkg.id.as_str(), nodes.len());
+            nodes.push(DepNode {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                doc_name: pkg.lib_doc_name(),
+                workspace_member: output.workspace_members.contains(&pkg.id),
+            });
+        }
+
+        let mut edges = Vec::new();
+        for node in &resolve.nodes {
+            let Some(&from) = index.get(node.id.as_str()) else {
+                continue;
+            };
+            for dep in &node.deps {
+                let Some(&to) = index.get(dep.pkg.as_str()) else {
+                    continue;
+                };
+                edges.push(DepEdge {
+                    from,
+                    to,
+                    kind: dep.kind(),
+                });
+            }
+        }
+
+        Self { nodes, edges }
+    }
+}
+
+/// Runs `cargo metadata` in `dir` once; later calls are no-ops.
+///
+/// Feature selection flags in `cargo_args` (`--features`, `--all-features`,
+/// `--no-default-features`) are forwarded so the reported features match
+/// what `cargo doc` builds.
+pub fn init(dir: &Path, cargo_args: &[String]) {
+    if METADATA.get().is_some() {
+        return;
+    }
+    match load(dir, cargo_args) {
+        Ok(metadata) => {
+            let _ = METADATA.set(metadata);
+        }
+        Err(err) => eprintln!("cargo-doc-viewer: Unable to read cargo metadata: {err}"),
+    }
+}
+
+pub fn get() -> Option<&'static Metadata> {
+    METADATA.get()
+}
+
+fn load(dir: &Path, cargo_args: &[String]) -> io::Result<Metadata> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1"])
+        .args(feature_args(cargo_args))
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    Metadata::from_json(&output.stdout).map_err(io::Error::other)
+}
+
+fn feature_args(cargo_args: &[String]) -> Vec<String> {
+    let mut forwarded = Vec::new();
+    let mut iter = cargo_args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--all-features" | "--no-default-features" => forwarded.push(arg.clone()),
+            "--features" | "-F" => {
+                forwarded.push(arg.clone());
+                if let Some(value) = iter.next() {
+                    forwarded.push(value.clone());
+                }
+            }
+            // `--features=a`, `-Fa` and `-F=a`.
+            other if other.starts_with("--features=") || other.starts_with("-F") => {
+                forwarded.push(arg.clone())
+            }
+            _ => {}
+        }
+    }
+    forwarded
+}
+
+#[derive(Deserialize)]
+struct CargoMetadata {
+    packages: Vec<Package>,
+    #[serde(default)]
+    workspace_members: Vec<String>,
+    resolve: Option<Resolve>,
+    target_directory: Option<PathBuf>,
+    workspace_root: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    version: String,
+    #[serde(default)]
+    edition: String,
+    repository: Option<String>,
+    rust_version: Option<String>,
+    description: Option<String>,
+    license: Option<String>,
+    manifest_path: Option<PathBuf>,
+    #[serde(default)]
+    targets: Vec<Target>,
+}
+
+impl Package {
+    /// Directory names rustdoc uses for this package's documented targets.
+    fn doc_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .targets
+            .iter()
+            .filter(|target| target.is_documented())
+            .map(|target| target.name.replace('-', "_"))
+            .collect();
+        let fallback = self.name.replace('-', "_");
+        if !names.contains(&fallback) {
+            names.push(fallback);
+        }
+        names
+    }
+
+    /// Doc directory of the library target, falling back to the package name.
+    fn lib_doc_name(&self) -> String {
+        self.targets
+            .iter()
+            .find(|target| target.is_documented() && !target.kind.iter().any(|k| k == "bin"))
+            .map(|target| target.name.replace('-', "_"))
+            .unwrap_or_else(|| self.name.replace('-', "_"))
+    }
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    #[serde(default)]
+    kind: Vec<String>,
+}
+
+impl Target {
+    fn is_documented(&self) -> bool {
+        self.kind.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro" | "bin"
+            )
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct Resolve {
+    nodes: Vec<ResolveNode>,
+}
+
+#[derive(Deserialize)]
+struct ResolveNode {
+    id: String,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    deps: Vec<NodeDep>,
+}
+
+#[derive(Deserialize)]
+struct NodeDep {
+    pkg: String,
+    #[serde(default)]
+    dep_kinds: Vec<NodeDepKind>,
+}
+
+impl NodeDep {
+    /// The strongest kind among the dependency's declarations.
+    fn kind(&self) -> DepKind {
+        let kinds = || self.dep_kinds.iter().map(|k| k.kind.as_deref());
+        if self.dep_kinds.is_empty() || kinds().any(|k| k.is_none()) {
+            DepKind::Normal
+        } else if kinds().any(|k| k == Some("build")) {
+            DepKind::Build
+        } else {
+            DepKind::Dev
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NodeDepKind {
+    kind: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{
+        "packages": [
+            {
+                "id": "path+file:///work/my-app#0.3.0",
+                "name": "my-app",
+                "version": "0.3.0",
+                "edition": "2021",
+                "repository": "https://example.com/my-app",
+                "rust_version": "1.74",
+                "description": "An app",
+                "license": "MIT",
+                "manifest_path": "/work/my-app/Cargo.toml",
+                "targets": [{"name": "my-app", "kind": ["lib"]}]
+            },
+            {
+                "id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.210",
+                "name": "serde",
+                "version": "1.0.210",
+                "edition": "2018",
+                "repository": null,
+                "rust_version": null,
+                "targets": [{"name": "serde", "kind": ["lib"]}, {"name": "build-script-build", "kind": ["custom-build"]}]
+            }
+        ],
+        "workspace_members": ["path+file:///work/my-app#0.3.0"],
+        "target_directory": "/work/target",
+        "workspace_root": "/work",
+        "resolve": {
+            "nodes": [
+                {
+                    "id": "path+file:///work/my-app#0.3.0",
+                    "features": ["tls", "default"],
+                    "deps": [{
+                        "name": "serde",
+                        "pkg": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.210",
+                        "dep_kinds": [{"kind": "dev", "target": null}, {"kind": null, "target": null}]
+                    }]
+                },
+                {"id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.210", "features": ["std"]}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn parses_crates_by_doc_name() {
+        let metadata = Metadata::from_json(SAMPLE.as_bytes()).expect("valid metadata");
+
+        let app = metadata.crate_meta("my_app").expect("member present");
+        assert_eq!(app.version, "0.3.0");
+        assert_eq!(app.edition, "2021");
+        assert_eq!(app.features, vec!["default", "tls"]);
+        assert_eq!(app.rust_version.as_deref(), Some("1.74"));
+        assert_eq!(app.license.as_deref(), Some("MIT"));
+        assert!(app.workspace_member);
+
+        let serde = metadata.crate_meta("serde").expect("dependency present");
+        assert_eq!(serde.features, vec!["std"]);
+        assert!(!serde.workspace_member);
+        assert!(metadata.crate_meta("build_script_build").is_none());
+        assert_eq!(metadata.target_directory(), Some(Path::new("/work/target")));
+        assert_eq!(metadata.workspace_root(), Some(Path::new("/work")));
+        assert_eq!(metadata.member_dirs(), [PathBuf::from("/work/my-app")]);
+    }
+
+    #[test]
+    fn builds_dependency_graph_from_resolve() {
+        let metadata = Metadata::from_json(SAMPLE.as_bytes()).expect("valid metadata");
+        let graph = metadata.graph();
+
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.nodes[0].doc_name, "my_app");
+        assert!(graph.nodes[0].workspace_member);
+        assert_eq!(
+            graph.edges,
+            vec![DepEdge {
+                from: 0,
+                to: 1,
+                kind: DepKind::Normal
+            }]
+        );
+    }
+
+    #[test]
+    fn forwards_only_feature_flags() {
+        let args: Vec<String> = [
+            "--document-private-items",
+            "-F",
+            "tls",
+            "--all-features",
+            "-p",
+            "x",
+            "-Fserde",
+            "-F=json",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(
+            feature_args(&args),
+            vec!["-F", "tls", "--all-features", "-Fserde", "-F=json"]
+        );
+    }
+}
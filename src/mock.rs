@@ -0,0 +1,261 @@
+//! The offline `mock` chat provider.
+//!
+//! Answers are assembled from the context layers the panel already sends:
+//! the page summary and retrieved sentences that share the most words with
+//! the question are quoted back with citations. Nothing leaves the process,
+//! so the chat flow can be demonstrated and tested without network access.
+
+use crate::llm::ChatRequest;
+use crate::retrieval::STOPWORDS;
+use crate::search;
+
+/// System message prefixes written by the panel's `buildRequestPayload`.
+const PAGE_SUMMARY_PREFIX: &str = "Page summary:\n";
+const RETRIEVED_PREFIX: &str = "Related documentation from other pages:\n";
+
+/// Sentences quoted per answer.
+const MAX_SENTENCES: usize = 3;
+
+struct Source {
+    label: String,
+    location: String,
+}
+
+struct Sentence {
+    text: String,
+    source: usize,
+    terms: Vec<String>,
+}
+
+/// The deterministic reply to `request`.
+pub fn answer(request: &ChatRequest) -> String {
+    let question = request
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map_or("", |message| message.content.as_str());
+    let (sources, sentences) = collect_context(request);
+
+    let mut wanted: Vec<String> = terms(question).collect();
+    wanted.sort();
+    wanted.dedup();
+
+    let mut ranked: Vec<(usize, usize)> = sentences
+        .iter()
+        .enumerate()
+        .map(|(index, sentence)| {
+            let score = wanted
+                .iter()
+                .filter(|term| sentence.terms.contains(term))
+                .count();
+            (index, score)
+        })
+        .filter(|&(_, score)| score > 0)
+        .collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut picked: Vec<usize> = ranked
+        .into_iter()
+        .take(MAX_SENTENCES)
+        .map(|(index, _)| index)
+        .collect();
+    if picked.is_empty() {
+        // Nothing matched: open with the page itself.
+        picked = (0..sentences.len())
+            .filter(|&index| sentences[index].source == 0)
+            .take(2)
+            .collect();
+    }
+    picked.sort_unstable();
+
+    if picked.is_empty() {
+        return "_Offline answer from the `mock` provider._\n\n\
+                No documentation context was supplied with this question. \
+                Open a documentation page or select some text and ask again."
+            .to_string();
+    }
+
+    let mut cited: Vec<usize> = Vec::new();
+    let mut out = String::from(
+        "_Offline answer from the `mock` provider, quoting the supplied documentation._\n\n",
+    );
+    for index in picked {
+        let sentence = &sentences[index];
+        let number = match cited.iter().position(|&source| source == sentence.source) {
+            Some(position) => position + 1,
+            None => {
+                cited.push(sentence.source);
+                cited.len()
+            }
+        };
+        out.push_str(&format!("- {} [{number}]\n", sentence.text));
+    }
+    out.push_str("\nSources:\n");
+    for (position, &source) in cited.iter().enumerate() {
+        let source = &sources[source];
+        out.push_str(&format!("- [{}] {}", position + 1, source.label));
+        if !source.location.is_empty() {
+            out.push_str(&format!(" ({})", source.location));
+        }
+        out.push('\n');
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// Sources and their sentences. The current page is always source `0`.
+fn collect_context(request: &ChatRequest) -> (Vec<Source>, Vec<Sentence>) {
+    let metadata = request.metadata.as_ref();
+    let page_label = metadata
+        .map(|meta| {
+            if !meta.module.is_empty() {
+                meta.module.as_str()
+            } else {
+                meta.krate.as_str()
+            }
+        })
+        .filter(|label| !label.is_empty())
+        .map_or_else(|| "Current page".to_string(), |label| format!("`{label}`"));
+    let mut sources = vec![Source {
+        label: page_label,
+        location: metadata
+            .map(|meta| meta.doc_path.clone())
+            .unwrap_or_default(),
+    }];
+    let mut sentences = Vec::new();
+
+    let system = request
+        .messages
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| message.content.as_str());
+    for content in system {
+        if let Some(summary) = content.strip_prefix(PAGE_SUMMARY_PREFIX) {
+            push_sentences(&mut sentences, summary, 0);
+        } else if let Some(retrieved) = content.strip_prefix(RETRIEVED_PREFIX) {
+            for entry in retrieved.split("\n\n") {
+                let (header, text) = entry.split_once('\n').unwrap_or((entry, ""));
+                // `[n] path (url)`, as written by the panel's `formatRetrieved`.
+                let header = header
+                    .trim()
+                    .split_once("] ")
+                    .map_or(header.trim(), |(_, rest)| rest);
+                let (label, location) = match header.rsplit_once(" (") {
+                    Some((path, url)) => (path, url.trim_end_matches(')')),
+                    None => (header, ""),
+                };
+                sources.push(Source {
+                    label: format!("`{label}`"),
+                    location: location.to_string(),
+                });
+                push_sentences(&mut sentences, text, sources.len() - 1);
+            }
+        }
+    }
+    (sources, sentences)
+}
+
+fn push_sentences(sentences: &mut Vec<Sentence>, text: &str, source: usize) {
+    for sentence in split_sentences(text) {
+        sentences.push(Sentence {
+            terms: terms(&sentence).collect(),
+            text: sentence,
+            source,
+        });
+    }
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let boundary = c == '\n'
+            || (matches!(c, '.' | '!' | '?' | '。' | '！' | '？')
+                && chars.peek().is_none_or(|next| next.is_whitespace()));
+        if c != '\n' {
+            current.push(c);
+        }
+        if boundary {
+            let sentence = current.split_whitespace().collect::<Vec<_>>().join(" ");
+            if sentence.chars().count() > 3 {
+                sentences.push(sentence);
+            }
+            current.clear();
+        }
+    }
+    let rest = current.split_whitespace().collect::<Vec<_>>().join(" ");
+    if rest.chars().count() > 3 {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace()
+        .flat_map(search::tokenize)
+        .filter(|term| term.len() > 1 && !STOPWORDS.contains(&term.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatMessage, ChatMetadata};
+
+    fn request(system: &[&str], question: &str) -> ChatRequest {
+        let mut messages: Vec<ChatMessage> = system
+            .iter()
+            .map(|content| ChatMessage {
+                role: "system".to_string(),
+                content: content.to_string(),
+            })
+            .collect();
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: question.to_string(),
+        });
+        ChatRequest {
+            model: None,
+            messages,
+            stream: false,
+            profile: None,
+            metadata: Some(ChatMetadata {
+                doc_path: "/hyper/client/struct.Client.html".to_string(),
+                krate: "hyper".to_string(),
+                module: "hyper::client".to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn quotes_matching_sentences_with_citations() {
+        let request = request(
+            &[
+                "You are helpful.",
+                "Page summary:\nA Client to make outgoing HTTP requests. It holds a connection pool. Cloning is cheap.",
+                "Related documentation from other pages:\n[1] hyper::client::Builder (/hyper/client/struct.Builder.html)\nSet the pool idle timeout on the builder.",
+            ],
+            "How does the connection pool work?",
+        );
+        let reply = answer(&request);
+
+        assert!(reply.contains("- It holds a connection pool. [1]"));
+        assert!(reply.contains("- Set the pool idle timeout on the builder. [2]"));
+        assert!(!reply.contains("Cloning is cheap"));
+        assert!(reply.contains("[1] `hyper::client` (/hyper/client/struct.Client.html)"));
+        assert!(reply.contains("[2] `hyper::client::Builder` (/hyper/client/struct.Builder.html)"));
+        assert_eq!(reply, answer(&request));
+    }
+
+    #[test]
+    fn falls_back_to_the_page_or_a_hint() {
+        let reply = answer(&request(
+            &["Page summary:\nA Client to make requests. Cloning is cheap. Third."],
+            "zzz?",
+        ));
+        assert!(reply.contains("- A Client to make requests. [1]\n- Cloning is cheap. [1]\n"));
+
+        let empty = answer(&request(&[], "anything"));
+        assert!(empty.contains("No documentation context"));
+    }
+}
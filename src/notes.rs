@@ -0,0 +1,385 @@
+//! Team notes on documentation items.
+//!
+//! Notes are committed with the project in `.cargo-doc-viewer/notes/*.yaml`,
+//! each file a list of notes, and attach a short text to a page of the
+//! generated docs and optionally to a heading or item anchor on it. Pages get
+//! their notes inlined as `__CDV_NOTES__`, both when served and when enhanced
+//! on disk; served pages can also add, edit and delete them. A note whose page
+//! or anchor no longer exists after a rebuild is reported as orphaned.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::files;
+
+/// `GET` lists notes; `PUT`/`DELETE <path>/<id>` saves or removes one.
+pub const NOTES_PATH: &str = "/cdv-api/notes";
+
+const NOTES_DIR: &str = ".cargo-doc-viewer/notes";
+
+const FILE_HEADER: &str = "# Team notes shown on cargo-doc-viewer pages. `page` is relative to the\n\
+# doc root (target/doc) and `anchor` is the id of a heading or item on it.\n";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Note {
+    pub id: String,
+    /// Page relative to the doc root, e.g. `hyper/client/struct.Client.html`.
+    pub page: String,
+    /// `id` of the heading or item the note belongs to; empty for the page.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub anchor: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub updated: String,
+}
+
+#[derive(Debug)]
+pub enum NotesError {
+    InvalidId,
+    Invalid(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for NotesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotesError::InvalidId => f.write_str("invalid note id"),
+            NotesError::Invalid(message) => f.write_str(message),
+            NotesError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for NotesError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub struct NoteStore {
+    dir: PathBuf,
+    /// Serialises [`NoteStore::save`] and [`NoteStore::delete`], which
+    /// rewrite whole files.
+    writes: Mutex<()>,
+}
+
+/// Notes grouped by page, read once for a run over many pages.
+#[derive(Debug, Default)]
+pub struct NoteIndex {
+    by_page: HashMap<String, Vec<Note>>,
+}
+
+impl NoteIndex {
+    pub fn for_page(&self, page: &str) -> &[Note] {
+        self.by_page.get(page).map_or(&[], Vec::as_slice)
+    }
+
+    /// `window.__CDV_NOTES__ = {...};` for `page`. The page script looks up
+    /// the anchors itself, so this is known before the page is read.
+    pub fn script_assignment(&self, page: &str) -> String {
+        let payload = serde_json::json!({
+            "page": page,
+            "notes": self.for_page(page),
+        });
+        format!(
+            "window.__CDV_NOTES__ = {};",
+            config::escape_json_for_script(&payload.to_string())
+        )
+    }
+}
+
+impl NoteStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// The notes directory of the project around the current directory: the
+    /// nearest one that already exists, else the one next to `Cargo.lock`
+    /// (the workspace root), else one in the current directory.
+    pub fn discover() -> Self {
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let root = cwd
+            .ancestors()
+            .find(|dir| dir.join(NOTES_DIR).is_dir())
+            .or_else(|| cwd.ancestors().find(|dir| dir.join("Cargo.lock").is_file()))
+            .unwrap_or(&cwd);
+        Self::new(root.join(NOTES_DIR))
+    }
+
+    /// Every note, in file order. Files that fail to parse are reported and
+    /// skipped so one bad edit does not hide the rest.
+    pub fn all(&self) -> Vec<Note> {
+        let mut notes = Vec::new();
+        for path in self.files() {
+            match read_file(&path) {
+                Ok(mut found) => notes.append(&mut found),
+                Err(err) => eprintln!("Warning: Skipping notes in {}: {err}", path.display()),
+            }
+        }
+        notes
+    }
+
+    /// Reads every note once and groups them by page.
+    pub fn index(&self) -> NoteIndex {
+        let mut index = NoteIndex::default();
+        for note in self.all() {
+            index
+                .by_page
+                .entry(note.page.clone())
+                .or_default()
+                .push(note);
+        }
+        index
+    }
+
+    /// Adds or replaces the note `id`. Existing notes stay in their file; new
+    /// ones go to `<crate>.yaml`, named after the first segment of the page.
+    pub fn save(&self, id: &str, mut note: Note) -> Result<Note, NotesError> {
+        check_id(id)?;
+        note.id = id.to_string();
+        note.page = note.page.trim_start_matches('/').to_string();
+        note.text = note.text.trim().to_string();
+        if note.page.is_empty() || note.page.split('/').any(|segment| segment == "..") {
+            return Err(NotesError::Invalid("note needs a page".to_string()));
+        }
+        if note.text.is_empty() {
+            return Err(NotesError::Invalid(
+                "note text must not be empty".to_string(),
+            ));
+        }
+
+        let _guard = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        let (path, mut notes) = match self.find(id)? {
+            Some(found) => found,
+            None => {
+                let krate = note.page.split('/').next().unwrap_or_default();
+                let name = if check_id(krate).is_ok() {
+                    krate
+                } else {
+                    "notes"
+                };
+                let path = self.dir.join(format!("{name}.yaml"));
+                let notes = if path.is_file() {
+                    read_file(&path)?
+                } else {
+                    Vec::new()
+                };
+                (path, notes)
+            }
+        };
+        match notes.iter_mut().find(|existing| existing.id == id) {
+            Some(existing) => *existing = note.clone(),
+            None => notes.push(note.clone()),
+        }
+        write_file(&path, &notes)?;
+        Ok(note)
+    }
+
+    /// Removes the note `id`; `false` when there was none.
+    pub fn delete(&self, id: &str) -> Result<bool, NotesError> {
+        check_id(id)?;
+        let _guard = self.writes.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some((path, mut notes)) = self.find(id)? else {
+            return Ok(false);
+        };
+        notes.retain(|note| note.id != id);
+        write_file(&path, &notes)?;
+        Ok(true)
+    }
+
+    /// Notes whose page is missing from `doc_root` or no longer has their
+    /// anchor.
+    pub fn orphaned(&self, doc_root: &Path) -> Vec<Note> {
+        let mut pages: HashMap<String, Option<String>> = HashMap::new();
+        self.all()
+            .into_iter()
+            .filter(|note| {
+                let html = pages
+                    .entry(note.page.clone())
+                    .or_insert_with(|| fs::read_to_string(doc_root.join(&note.page)).ok());
+                match html {
+                    Some(html) => !has_anchor(html, &note.anchor),
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Prints the notes [`Self::orphaned`] finds, so they can be moved or
+    /// removed after the docs change.
+    pub fn report_orphaned(&self, doc_root: &Path) {
+        let orphaned = self.orphaned(doc_root);
+        if orphaned.is_empty() {
+            return;
+        }
+        println!(
+            "{} team note(s) in {} no longer match the docs:",
+            orphaned.len(),
+            self.dir.display()
+        );
+        for note in orphaned {
+            let target = if note.anchor.is_empty() {
+                note.page
+            } else {
+                format!("{}#{}", note.page, note.anchor)
+            };
+            println!("  {} ({target})", note.id);
+        }
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                matches!(
+                    path.extension().and_then(|ext| ext.to_str()),
+                    Some("yaml" | "yml")
+                )
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// The file holding `id` and all of its notes. Unlike [`Self::all`], a
+    /// file that fails to parse is an error, since it may hold the note.
+    fn find(&self, id: &str) -> Result<Option<(PathBuf, Vec<Note>)>, NotesError> {
+        for path in self.files() {
+            let notes = read_file(&path)?;
+            if notes.iter().any(|note| note.id == id) {
+                return Ok(Some((path, notes)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Page notes always match; anchored ones need an element with that id.
+fn has_anchor(html: &str, anchor: &str) -> bool {
+    anchor.is_empty() || html.contains(&format!("id=\"{anchor}\""))
+}
+
+fn read_file(path: &Path) -> Result<Vec<Note>, NotesError> {
+    let text = fs::read_to_string(path)?;
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_yaml::from_str::<Option<Vec<Note>>>(&text)
+        .map(Option::unwrap_or_default)
+        .map_err(|err| NotesError::Invalid(format!("{}: {err}", path.display())))
+}
+
+/// Rewrites `path` with `notes`, removing the file once it is empty.
+fn write_file(path: &Path, notes: &[Note]) -> Result<(), NotesError> {
+    if notes.is_empty() {
+        fs::remove_file(path)?;
+        return Ok(());
+    }
+    let yaml = serde_yaml::to_string(notes).map_err(io::Error::other)?;
+    files::write_atomic(path, format!("{FILE_HEADER}{yaml}").as_bytes())?;
+    Ok(())
+}
+
+/// Ids are written by hand too, so any short plain name is accepted.
+fn check_id(id: &str) -> Result<(), NotesError> {
+    if files::is_plain_name(id) {
+        Ok(())
+    } else {
+        Err(NotesError::InvalidId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(page: &str, anchor: &str, text: &str) -> Note {
+        Note {
+            page: page.to_string(),
+            anchor: anchor.to_string(),
+            text: text.to_string(),
+            ..Note::default()
+        }
+    }
+
+    #[test]
+    fn saves_notes_per_crate_and_finds_orphans() {
+        let root = std::env::temp_dir().join(format!("cdv-notes-{}", std::process::id()));
+        let docs = root.join("doc");
+        fs::create_dir_all(docs.join("hyper/client")).unwrap();
+        fs::write(
+            docs.join("hyper/client/struct.Client.html"),
+            r#"<section id="method.request" class="method"><h4 class="code-header">request</h4></section>"#,
+        )
+        .unwrap();
+        let store = NoteStore::new(root.join(NOTES_DIR));
+        let page = "hyper/client/struct.Client.html";
+
+        store
+            .save(
+                "hot-path",
+                note(
+                    page,
+                    "method.request",
+                    "Not in the hot path, see incident 42.",
+                ),
+            )
+            .unwrap();
+        store
+            .save("gone", note(page, "method.send", "Old advice."))
+            .unwrap();
+        store
+            .save("moved", note("hyper/old.html", "", "Page was removed."))
+            .unwrap();
+        assert!(matches!(
+            store.save("empty", note(page, "", "  ")),
+            Err(NotesError::Invalid(_))
+        ));
+
+        let written = fs::read_to_string(store.dir.join("hyper.yaml")).unwrap();
+        assert!(written.starts_with(FILE_HEADER));
+        assert!(written.contains("anchor: method.request"));
+
+        let updated = note(page, "method.request", "Fine since 1.0.");
+        store.save("hot-path", updated).unwrap();
+        let index = store.index();
+        let on_page = index.for_page(page);
+        assert_eq!(on_page.len(), 2);
+        assert_eq!(on_page[0].text, "Fine since 1.0.");
+        assert_eq!(index.for_page("hyper/old.html")[0].id, "moved");
+        assert!(index.for_page("hyper/index.html").is_empty());
+
+        let script = index.script_assignment(page);
+        assert!(script.contains(r#""id":"gone""#));
+
+        let orphaned: Vec<String> = store
+            .orphaned(&docs)
+            .into_iter()
+            .map(|note| note.id)
+            .collect();
+        assert_eq!(orphaned, ["gone", "moved"]);
+
+        assert!(store.delete("gone").unwrap());
+        assert!(store.delete("moved").unwrap());
+        assert!(!store.delete("moved").unwrap());
+        assert_eq!(store.all().len(), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,13 +1,22 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write as _;
 use std::fs;
 use std::io::{self, Read, Write};
 use std::path::Path;
 
-#[derive(Debug, Clone)]
+use crate::metadata::{self, DepGraph, DepKind, Metadata};
+
+#[derive(Debug, Clone, Default)]
 pub struct CrateInfo {
     pub name: String,
     pub description: String,
     pub version: Option<String>,
     pub path: String,
+    pub license: Option<String>,
+    pub repository: Option<String>,
+    pub workspace_member: bool,
+    /// Docs exist but the crate is no longer in the dependency graph.
+    pub stale: bool,
 }
 
 pub fn generate_overview_page(doc_dir: &Path) -> io::Result<()> {
@@ -16,6 +25,9 @@ pub fn generate_overview_page(doc_dir: &Path) -> io::Result<()> {
     let overview_path = doc_dir.join("cdv-crate-overview.html");
     let mut file = fs::File::create(overview_path)?;
     file.write_all(html.as_bytes())?;
+
+    let deps_html = generate_deps_html(metadata::get().map(Metadata::graph), &crates);
+    fs::write(doc_dir.join("cdv-deps.html"), deps_html)?;
     Ok(())
 }
 
@@ -24,10 +36,15 @@ pub fn remove_overview_page(doc_dir: &Path) -> io::Result<()> {
     if overview_path.exists() {
         fs::remove_file(overview_path)?;
     }
+    let deps_path = doc_dir.join("cdv-deps.html");
+    if deps_path.exists() {
+        fs::remove_file(deps_path)?;
+    }
     Ok(())
 }
 
 pub fn scan_crates(doc_dir: &Path) -> io::Result<Vec<CrateInfo>> {
+    let metadata = metadata::get();
     let mut crates = Vec::new();
 
     for entry in fs::read_dir(doc_dir)? {
@@ -36,18 +53,18 @@ pub fn scan_crates(doc_dir: &Path) -> io::Result<Vec<CrateInfo>> {
 
         if path.is_dir() {
             let index_path = path.join("index.html");
-            if index_path.exists() {
-                if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
-                    if matches!(
-                        dir_name,
-                        "static.files" | "src" | "implementors" | "help.html"
-                    ) {
-                        continue;
-                    }
-
-                    let crate_info = extract_crate_info(dir_name, &index_path)?;
-                    crates.push(crate_info);
+            if index_path.exists()
+                && let Some(dir_name) = path.file_name().and_then(|s| s.to_str())
+            {
+                if matches!(
+                    dir_name,
+                    "static.files" | "src" | "implementors" | "help.html"
+                ) {
+                    continue;
                 }
+
+                let crate_info = extract_crate_info(dir_name, &index_path, metadata)?;
+                crates.push(crate_info);
             }
         }
     }
@@ -56,20 +73,42 @@ pub fn scan_crates(doc_dir: &Path) -> io::Result<Vec<CrateInfo>> {
     Ok(crates)
 }
 
-fn extract_crate_info(dir_name: &str, index_path: &Path) -> io::Result<CrateInfo> {
+/// Builds the card data for one doc directory. With cargo metadata available
+/// the package fields are authoritative and the HTML is only a fallback.
+fn extract_crate_info(
+    dir_name: &str,
+    index_path: &Path,
+    metadata: Option<&Metadata>,
+) -> io::Result<CrateInfo> {
     let mut content = String::new();
     fs::File::open(index_path)?.read_to_string(&mut content)?;
 
-    let description =
-        extract_description(&content).unwrap_or_else(|| "Rust crate documentation".to_string());
-    let version = extract_version(&content);
-
-    Ok(CrateInfo {
+    let mut info = CrateInfo {
         name: dir_name.to_string(),
-        description,
-        version,
+        description: extract_description(&content)
+            .unwrap_or_else(|| "Rust crate documentation".to_string()),
+        version: extract_version(&content),
         path: format!("{}/index.html", dir_name),
-    })
+        ..CrateInfo::default()
+    };
+
+    match metadata.map(|m| m.crate_meta(dir_name)) {
+        Some(Some(meta)) => {
+            info.version = Some(meta.version.clone());
+            if let Some(description) = meta.description.as_deref().map(str::trim)
+                && !description.is_empty()
+            {
+                info.description = description.to_string();
+            }
+            info.license = meta.license.clone();
+            info.repository = meta.repository.clone();
+            info.workspace_member = meta.workspace_member;
+        }
+        Some(None) => info.stale = true,
+        None => {}
+    }
+
+    Ok(info)
 }
 
 fn extract_description(html: &str) -> Option<String> {
@@ -104,7 +143,7 @@ fn extract_version(html: &str) -> Option<String> {
                 if potential_version
                     .chars()
                     .next()
-                    .map_or(false, |c| c.is_ascii_digit())
+                    .is_some_and(|c| c.is_ascii_digit())
                 {
                     return Some(potential_version.to_string());
                 }
@@ -115,7 +154,7 @@ fn extract_version(html: &str) -> Option<String> {
     None
 }
 
-fn strip_html_tags(html: &str) -> String {
+pub fn strip_html_tags(html: &str) -> String {
     let mut result = String::new();
     let mut in_tag = false;
 
@@ -133,16 +172,59 @@ fn strip_html_tags(html: &str) -> String {
         .replace("&gt;", ">")
         .replace("&amp;", "&")
         .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
         .trim()
         .to_string()
 }
 
 pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
-    let cards_html = crates
+    let members: Vec<&CrateInfo> = crates.iter().filter(|c| c.workspace_member).collect();
+    let stale: Vec<&CrateInfo> = crates.iter().filter(|c| c.stale).collect();
+    let dependencies: Vec<&CrateInfo> = crates
         .iter()
-        .map(generate_crate_card_html)
+        .filter(|c| !c.workspace_member && !c.stale)
+        .collect();
+
+    // Without cargo metadata every crate is a plain dependency; keep the
+    // single ungrouped grid in that case.
+    let grid_html = if members.is_empty() && stale.is_empty() {
+        format!(
+            r#"<div class="crates-grid">{}</div>"#,
+            render_cards(&dependencies)
+        )
+    } else {
+        [
+            ("workspace", "🏠 工作区成员 Workspace members", "", &members),
+            ("dependencies", "📚 依赖 Dependencies", "", &dependencies),
+            (
+                "stale",
+                "🕸️ 过期文档 Stale docs",
+                "这些包的文档仍在 doc 目录中，但已不在当前依赖图里；可运行 <code>cargo clean --doc</code> 后重新生成。",
+                &stale,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, _, _, cards)| !cards.is_empty())
+        .map(|(id, title, hint, cards)| {
+            let hint = if hint.is_empty() {
+                String::new()
+            } else {
+                format!(r#"<p class="section-hint">{hint}</p>"#)
+            };
+            format!(
+                r#"<section class="crate-section" data-section="{id}">
+        <h2 class="section-title">{title} <span class="section-count">{count}</span></h2>
+        {hint}
+        <div class="crates-grid">{cards}</div>
+    </section>"#,
+                count = cards.len(),
+                cards = render_cards(cards)
+            )
+        })
         .collect::<Vec<_>>()
-        .join("\n");
+        .join("\n")
+    };
 
     let datalist_html = crates
         .iter()
@@ -160,13 +242,14 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             type="search"
             id="crate-search"
             class="search-input"
-            placeholder="搜索包名称或描述…"
+            placeholder="搜索包名称、描述或任意条目…"
             autocomplete="off"
             list="crate-list"
         />
         <datalist id="crate-list">
             {datalist}
         </datalist>
+        <div id="item-results" class="item-results" hidden></div>
     </div>"#,
             datalist = datalist_html
         )
@@ -188,14 +271,105 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
         r#"<script>
 (function () {
     const searchInput = document.getElementById("crate-search");
-    const grid = document.querySelector(".crates-grid");
     const noResults = document.getElementById("no-results");
+    const cards = Array.from(document.querySelectorAll(".crate-card"));
+    const sections = Array.from(document.querySelectorAll(".crate-section"));
 
-    if (!searchInput || !grid) {
+    if (!searchInput || cards.length === 0) {
         return;
     }
 
-    const cards = Array.from(grid.querySelectorAll(".crate-card"));
+    // Served pages can also search items across every crate; the static
+    // `enhance` copy has no server to ask. An `export` names a prebuilt index
+    // in a meta tag instead, which is loaded once and searched here.
+    const itemResults = document.getElementById("item-results");
+    const canSearchItems = itemResults && /^https?:$/.test(location.protocol);
+    const indexMeta = document.querySelector('meta[name="cdv-search-index"]');
+    let staticItems = null;
+    let itemTimer = null;
+    let itemSeq = 0;
+
+    const searchStatic = (query) => {
+        if (!staticItems) {
+            staticItems = fetch(indexMeta.content)
+                .then((response) => (response.ok ? response.json() : {}))
+                .then((data) => data.items || []);
+        }
+        const terms = query.toLowerCase().split(/\s+/).filter(Boolean);
+        const first = terms[0];
+        return staticItems.then((items) => {
+            const rank = (item) => {
+                const name = item.name.toLowerCase();
+                return name === first ? 0 : name.startsWith(first) ? 1 : 2;
+            };
+            const matches = items
+                .filter((item) => {
+                    const haystack = (item.name + " " + item.path).toLowerCase();
+                    return terms.every((term) => haystack.includes(term));
+                })
+                .sort((a, b) => rank(a) - rank(b) || a.path.length - b.path.length);
+            return {results: matches.slice(0, 20), total: matches.length};
+        });
+    };
+
+    const renderItems = (items, total) => {
+        itemResults.replaceChildren();
+        items.forEach((item) => {
+            const link = document.createElement("a");
+            link.className = "item-result";
+            link.href = item.url;
+            const kind = document.createElement("span");
+            kind.className = "item-kind";
+            kind.textContent = item.kind;
+            const path = document.createElement("span");
+            path.className = "item-path";
+            path.textContent = item.path;
+            link.append(kind, path);
+            if (item.summary) {
+                const summary = document.createElement("span");
+                summary.className = "item-summary";
+                summary.textContent = item.summary;
+                link.append(summary);
+            }
+            itemResults.append(link);
+        });
+        if (total > items.length) {
+            const more = document.createElement("div");
+            more.className = "item-more";
+            more.textContent = "还有 " + (total - items.length) + " 条结果，请输入更精确的关键字";
+            itemResults.append(more);
+        }
+        itemResults.hidden = items.length === 0;
+        if (items.length > 0 && noResults) {
+            noResults.hidden = true;
+        }
+    };
+
+    const searchItems = (rawValue) => {
+        if (!canSearchItems) {
+            return;
+        }
+        clearTimeout(itemTimer);
+        const query = rawValue.trim();
+        const seq = ++itemSeq;
+        if (query.length < 2) {
+            renderItems([], 0);
+            return;
+        }
+        itemTimer = setTimeout(() => {
+            const request = indexMeta
+                ? searchStatic(query)
+                : fetch("/cdv-api/search?limit=20&q=" + encodeURIComponent(query))
+                      .then((response) => (response.ok ? response.json() : null));
+            request
+                .then((data) => {
+                    if (data && seq === itemSeq) {
+                        renderItems(data.results || [], data.total || 0);
+                    }
+                })
+                .catch(() => {});
+        }, 150);
+    };
 
     const applyFilter = (rawValue) => {
         const terms = rawValue
@@ -224,6 +398,12 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             }
         });
 
+        sections.forEach((section) => {
+            const visible = Array.from(section.querySelectorAll(".crate-card"))
+                .some((card) => card.style.display !== "none");
+            section.hidden = !visible;
+        });
+
         if (noResults) {
             noResults.hidden = !(visibleCount === 0 && terms.length > 0);
         }
@@ -231,12 +411,20 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
 
     searchInput.addEventListener("input", (event) => {
         applyFilter(event.target.value || "");
+        searchItems(event.target.value || "");
     });
 
     searchInput.addEventListener("keydown", (event) => {
         if (event.key === "Escape") {
             searchInput.value = "";
             applyFilter("");
+            searchItems("");
+        } else if (event.key === "Enter" && canSearchItems) {
+            const first = itemResults.querySelector(".item-result");
+            if (first) {
+                event.preventDefault();
+                window.location.href = first.href;
+            }
         }
     });
 
@@ -296,6 +484,11 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             opacity: 0.8;
         }}
         
+        .header-link {{
+            color: var(--cdv-accent);
+            text-decoration: none;
+        }}
+        
         .search-container {{
             margin: 2rem auto 0;
             max-width: 480px;
@@ -323,6 +516,54 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             box-shadow: 0 0 0 3px rgba(106,166,255,0.2);
         }}
         
+        .item-results {{
+            margin-top: 0.75rem;
+            max-height: 60vh;
+            overflow-y: auto;
+            text-align: left;
+            background: rgba(0,0,0,0.35);
+            border: 1px solid var(--cdv-border);
+            border-radius: 12px;
+        }}
+        
+        .item-result {{
+            display: grid;
+            grid-template-columns: auto 1fr;
+            gap: 0.2rem 0.6rem;
+            padding: 0.6rem 1rem;
+            color: inherit;
+            text-decoration: none;
+            border-bottom: 1px solid var(--cdv-border);
+        }}
+        
+        .item-result:hover {{
+            background: var(--cdv-hover);
+        }}
+        
+        .item-kind {{
+            font-size: 0.75rem;
+            color: rgba(255,255,255,0.6);
+            align-self: center;
+        }}
+        
+        .item-path {{
+            color: var(--cdv-accent);
+            font-family: ui-monospace, SFMono-Regular, Menlo, monospace;
+            overflow-wrap: anywhere;
+        }}
+        
+        .item-summary {{
+            grid-column: 2;
+            font-size: 0.85rem;
+            color: rgba(255,255,255,0.7);
+        }}
+        
+        .item-more {{
+            padding: 0.5rem 1rem;
+            font-size: 0.8rem;
+            color: rgba(255,255,255,0.5);
+        }}
+        
         .visually-hidden {{
             position: absolute;
             width: 1px;
@@ -343,7 +584,37 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             margin: 0 auto;
         }}
         
+        .crate-section {{
+            max-width: 1200px;
+            margin: 0 auto 2.5rem;
+        }}
+        
+        .section-title {{
+            font-size: 1.3rem;
+            font-weight: 600;
+            margin-bottom: 1rem;
+            display: flex;
+            align-items: center;
+            gap: 0.5rem;
+        }}
+        
+        .section-count {{
+            font-size: 0.85rem;
+            font-weight: normal;
+            color: rgba(255,255,255,0.6);
+            background: rgba(255,255,255,0.1);
+            padding: 0.1rem 0.5rem;
+            border-radius: 999px;
+        }}
+        
+        .section-hint {{
+            margin: -0.5rem 0 1rem;
+            color: rgba(255,255,255,0.6);
+            font-size: 0.9rem;
+        }}
+        
         .crate-card {{
+            position: relative;
             background: var(--cdv-card-bg);
             border: 1px solid var(--cdv-border);
             border-radius: 12px;
@@ -355,6 +626,59 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             backdrop-filter: blur(10px);
         }}
         
+        .crate-card.stale {{
+            opacity: 0.65;
+            border-style: dashed;
+        }}
+        
+        .crate-link {{
+            color: inherit;
+            text-decoration: none;
+        }}
+        
+        .crate-link::after {{
+            content: "";
+            position: absolute;
+            inset: 0;
+        }}
+        
+        .crate-meta {{
+            display: flex;
+            flex-wrap: wrap;
+            align-items: center;
+            gap: 0.5rem;
+            margin-top: 0.9rem;
+            font-size: 0.8rem;
+        }}
+        
+        .crate-badge {{
+            color: rgba(255,255,255,0.75);
+            background: rgba(255,255,255,0.08);
+            border: 1px solid var(--cdv-border);
+            padding: 0.1rem 0.45rem;
+            border-radius: 4px;
+        }}
+        
+        .crate-badge.stale {{
+            color: #ffcf70;
+            border-color: rgba(255,207,112,0.4);
+        }}
+        
+        .crate-repo {{
+            position: relative;
+            z-index: 1;
+            color: var(--cdv-accent);
+            text-decoration: none;
+            overflow: hidden;
+            text-overflow: ellipsis;
+            white-space: nowrap;
+            max-width: 100%;
+        }}
+        
+        .crate-repo:hover {{
+            text-decoration: underline;
+        }}
+        
         .crate-card:hover {{
             background: var(--cdv-hover);
             transform: translateY(-2px);
@@ -420,7 +744,7 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
 <body>
     <div class="header">
         <h1>📦 所有包概览</h1>
-        <p>点击任意卡片查看对应包的文档</p>
+        <p>点击任意卡片查看对应包的文档 · <a class="header-link" href="cdv-deps.html">查看依赖图 →</a></p>
         {search}
     </div>
     
@@ -437,39 +761,561 @@ pub fn generate_overview_html(crates: &[CrateInfo]) -> String {
             </div>"#
                 .to_string()
         } else {
-            format!(r#"<div class="crates-grid">{}</div>"#, cards_html)
+            grid_html
         },
         no_results = no_results_html,
         script = filter_script
     )
 }
 
+fn render_cards(crates: &[&CrateInfo]) -> String {
+    crates
+        .iter()
+        .map(|krate| generate_crate_card_html(krate))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn generate_crate_card_html(crate_info: &CrateInfo) -> String {
+    let mut meta = Vec::new();
+    if let Some(license) = &crate_info.license {
+        meta.push(format!(
+            r#"<span class="crate-badge">{}</span>"#,
+            escape_attr(license)
+        ));
+    }
+    if crate_info.stale {
+        meta.push(
+            r#"<span class="crate-badge stale" title="不在当前依赖图中">过期 stale</span>"#
+                .to_string(),
+        );
+    }
+    if let Some(repo) = crate_info
+        .repository
+        .as_deref()
+        .filter(|url| url.starts_with("https://") || url.starts_with("http://"))
+    {
+        let label = repo
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        meta.push(format!(
+            r#"<a class="crate-repo" href="{href}" target="_blank" rel="noopener">{label}</a>"#,
+            href = escape_attr(repo),
+            label = escape_attr(label)
+        ));
+    }
+    let meta_html = if meta.is_empty() {
+        String::new()
+    } else {
+        format!(r#"<div class="crate-meta">{}</div>"#, meta.join(""))
+    };
+
     format!(
-        r#"<a href="{path}" class="crate-card" data-name="{name_attr}">
+        r#"<article class="crate-card{stale_class}" data-name="{name_attr}">
         <div class="crate-name">
-            {name}
+            <a href="{path}" class="crate-link">{name}</a>
             {version}
         </div>
         <div class="crate-description">{description}</div>
-    </a>"#,
-        path = crate_info.path,
-        name = crate_info.name,
+        {meta}
+    </article>"#,
+        stale_class = if crate_info.stale { " stale" } else { "" },
+        path = escape_attr(&crate_info.path),
+        name = escape_attr(&crate_info.name),
         version = crate_info
             .version
             .as_ref()
-            .map(|v| format!(r#"<span class="crate-version">v{}</span>"#, v))
+            .map(|v| format!(r#"<span class="crate-version">v{}</span>"#, escape_attr(v)))
             .unwrap_or_default(),
         name_attr = escape_attr(&crate_info.name),
         description = if crate_info.description.is_empty() {
             "Rust crate documentation".to_string()
         } else {
-            crate_info.description.clone()
+            escape_attr(&crate_info.description)
+        },
+        meta = meta_html
+    )
+}
+
+const NODE_WIDTH: usize = 190;
+const NODE_HEIGHT: usize = 30;
+const COLUMN_GAP: usize = 90;
+const ROW_GAP: usize = 12;
+const GRAPH_PADDING: usize = 20;
+/// Characters that fit in a node label at the SVG font size.
+const LABEL_CHARS: usize = 24;
+
+/// Column and row placement for every node of a [`DepGraph`].
+struct GraphLayout {
+    /// `(x, y)` of each node's top-left corner, indexed like `graph.nodes`.
+    positions: Vec<(usize, usize)>,
+    width: usize,
+    height: usize,
+}
+
+/// Renders `/cdv-deps.html`: the resolved dependency graph as an inline SVG.
+///
+/// Workspace members form the leftmost column and every dependency sits one
+/// column right of its deepest dependent, so edges always point rightwards.
+/// Nodes link to the crate docs when a directory for them exists in
+/// `crates`.
+pub fn generate_deps_html(graph: Option<&DepGraph>, crates: &[CrateInfo]) -> String {
+    let content = match graph.filter(|graph| !graph.nodes.is_empty()) {
+        Some(graph) => {
+            let documented: HashSet<&str> = crates.iter().map(|c| c.name.as_str()).collect();
+            render_deps_svg(graph, &documented)
+        }
+        None => r#"<div class="empty-state">
+                <h2>😮 没有可用的依赖数据</h2>
+                <p>依赖图来自 <code>cargo metadata</code>，请在 Cargo 项目目录中运行本工具</p>
+            </div>"#
+            .to_string(),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>依赖图 - Cargo Doc Viewer</title>
+    <style>
+        :root {{
+            --cdv-fg: #e6e6e6;
+            --cdv-accent: #6aa6ff;
+            --cdv-border: rgba(255,255,255,0.12);
+        }}
+        
+        * {{
+            margin: 0;
+            padding: 0;
+            box-sizing: border-box;
+        }}
+        
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", sans-serif;
+            background: linear-gradient(135deg, #1a1d29 0%, #2a2d3a 100%);
+            color: var(--cdv-fg);
+            min-height: 100vh;
+            padding: 2rem;
+        }}
+        
+        .header {{
+            text-align: center;
+            margin-bottom: 2rem;
+        }}
+        
+        .header h1 {{
+            font-size: 2.5rem;
+            font-weight: 600;
+            color: var(--cdv-accent);
+            margin-bottom: 0.5rem;
+        }}
+        
+        .header p {{
+            font-size: 1.1rem;
+            opacity: 0.8;
+        }}
+        
+        .header a {{
+            color: var(--cdv-accent);
+            text-decoration: none;
+        }}
+        
+        .legend {{
+            display: flex;
+            justify-content: center;
+            flex-wrap: wrap;
+            gap: 1.25rem;
+            margin-bottom: 1.5rem;
+            font-size: 0.85rem;
+            color: rgba(255,255,255,0.7);
+        }}
+        
+        .legend span::before {{
+            content: "";
+            display: inline-block;
+            width: 1.6rem;
+            margin-right: 0.4rem;
+            vertical-align: middle;
+            border-top: 2px solid rgba(255,255,255,0.5);
+        }}
+        
+        .legend .dev::before {{
+            border-top-style: dashed;
+        }}
+        
+        .legend .build::before {{
+            border-top-style: dotted;
+        }}
+        
+        .graph-container {{
+            overflow: auto;
+            border: 1px solid var(--cdv-border);
+            border-radius: 12px;
+            background: rgba(0,0,0,0.2);
+        }}
+        
+        .dep-graph {{
+            display: block;
+            font-size: 12px;
+        }}
+        
+        .dep-edge {{
+            fill: none;
+            stroke: rgba(255,255,255,0.25);
+            stroke-width: 1.2;
+        }}
+        
+        .dep-edge.dev {{
+            stroke-dasharray: 5 4;
+        }}
+        
+        .dep-edge.build {{
+            stroke-dasharray: 2 3;
+        }}
+        
+        .dep-node rect {{
+            fill: rgba(255,255,255,0.08);
+            stroke: var(--cdv-border);
+            rx: 6px;
+        }}
+        
+        .dep-node.member rect {{
+            fill: rgba(106,166,255,0.18);
+            stroke: var(--cdv-accent);
+        }}
+        
+        .dep-node text {{
+            fill: var(--cdv-fg);
+            dominant-baseline: central;
+        }}
+        
+        .dep-node.undocumented text {{
+            fill: rgba(255,255,255,0.45);
+        }}
+        
+        .dep-node a:hover rect {{
+            stroke: var(--cdv-accent);
+        }}
+        
+        .dep-graph.focus .dep-node,
+        .dep-graph.focus .dep-edge {{
+            opacity: 0.15;
+        }}
+        
+        .dep-graph.focus .hl {{
+            opacity: 1;
+        }}
+        
+        .dep-graph.focus .dep-edge.hl {{
+            stroke: var(--cdv-accent);
+        }}
+        
+        .empty-state {{
+            text-align: center;
+            padding: 3rem;
+            color: rgba(255,255,255,0.6);
+        }}
+        
+        .empty-state h2 {{
+            font-size: 1.5rem;
+            margin-bottom: 1rem;
+        }}
+    </style>
+</head>
+<body>
+    <div class="header">
+        <h1>🕸️ 依赖图</h1>
+        <p>悬停包名查看是谁引入了它 · <a href="cdv-crate-overview.html">← 所有包概览</a></p>
+    </div>
+    
+    {content}
+</body>
+</html>"#
+    )
+}
+
+fn render_deps_svg(graph: &DepGraph, documented: &HashSet<&str>) -> String {
+    let layout = layout_graph(graph);
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); graph.nodes.len()];
+    for edge in &graph.edges {
+        if !dependents[edge.to].contains(&edge.from) {
+            dependents[edge.to].push(edge.from);
+        }
+    }
+    let via = shortest_paths_from_members(graph);
+
+    let mut edges_svg = String::new();
+    for edge in &graph.edges {
+        let (x1, y1) = layout.positions[edge.from];
+        let (x2, y2) = layout.positions[edge.to];
+        let (x1, y1) = ((x1 + NODE_WIDTH) as f32, (y1 + NODE_HEIGHT / 2) as f32);
+        let (x2, y2) = (x2 as f32, (y2 + NODE_HEIGHT / 2) as f32);
+        let bend = ((x2 - x1).abs() / 2.0).max(40.0);
+        let kind = match edge.kind {
+            DepKind::Normal => "",
+            DepKind::Build => " build",
+            DepKind::Dev => " dev",
+        };
+        let _ = writeln!(
+            edges_svg,
+            r#"<path class="dep-edge{kind}" data-from="{from}" data-to="{to}" d="M{x1} {y1} C{c1} {y1}, {c2} {y2}, {x2} {y2}"/>"#,
+            from = edge.from,
+            to = edge.to,
+            c1 = x1 + bend,
+            c2 = x2 - bend,
+        );
+    }
+
+    let mut nodes_svg = String::new();
+    for (id, node) in graph.nodes.iter().enumerate() {
+        let (x, y) = layout.positions[id];
+        let has_docs = documented.contains(node.doc_name.as_str());
+
+        let mut title = format!("{} v{}", node.name, node.version);
+        if node.workspace_member {
+            title.push_str("\n工作区成员 workspace member");
+        }
+        if let Some(path) = &via[id]
+            && path.len() > 1
+        {
+            let chain: Vec<&str> = path.iter().map(|&n| graph.nodes[n].name.as_str()).collect();
+            let _ = write!(title, "\n引入路径 via: {}", chain.join(" → "));
+        }
+        if !dependents[id].is_empty() {
+            let mut names: Vec<&str> = dependents[id]
+                .iter()
+                .map(|&n| graph.nodes[n].name.as_str())
+                .collect();
+            names.sort_unstable();
+            let _ = write!(title, "\n被依赖 required by: {}", names.join(", "));
+        }
+        if !has_docs {
+            title.push_str("\n未生成文档 no docs in target/doc");
+        }
+
+        let label = format!("{} v{}", node.name, node.version);
+        let label = if label.chars().count() > LABEL_CHARS {
+            let mut short: String = label.chars().take(LABEL_CHARS - 1).collect();
+            short.push('…');
+            short
+        } else {
+            label
+        };
+        let shape = format!(
+            r#"<rect x="{x}" y="{y}" width="{NODE_WIDTH}" height="{NODE_HEIGHT}"/><text x="{tx}" y="{ty}">{label}</text>"#,
+            tx = x + 10,
+            ty = y + NODE_HEIGHT / 2,
+            label = escape_attr(&label),
+        );
+        let body = if has_docs {
+            format!(
+                r#"<a href="{}/index.html">{shape}</a>"#,
+                escape_attr(&node.doc_name)
+            )
+        } else {
+            shape
+        };
+
+        let mut class = String::from("dep-node");
+        if node.workspace_member {
+            class.push_str(" member");
+        }
+        if !has_docs {
+            class.push_str(" undocumented");
         }
+        let _ = writeln!(
+            nodes_svg,
+            r#"<g class="{class}" data-id="{id}" data-name="{name}"><title>{title}</title>{body}</g>"#,
+            name = escape_attr(&node.name),
+            title = escape_attr(&title),
+        );
+    }
+
+    format!(
+        r#"<div class="legend">
+        <span class="normal">普通依赖 normal</span>
+        <span class="build">构建依赖 build</span>
+        <span class="dev">开发依赖 dev</span>
+    </div>
+    <div class="graph-container">
+        <svg class="dep-graph" xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">
+{edges_svg}{nodes_svg}        </svg>
+    </div>
+    <script>
+(function () {{
+    const svg = document.querySelector(".dep-graph");
+    if (!svg) {{
+        return;
+    }}
+    const edges = Array.from(svg.querySelectorAll(".dep-edge"));
+    const nodes = new Map(
+        Array.from(svg.querySelectorAll(".dep-node")).map((node) => [node.dataset.id, node])
+    );
+
+    // Highlight every path from a workspace member down to the hovered crate.
+    const highlight = (id) => {{
+        const seen = new Set([id]);
+        const queue = [id];
+        while (queue.length > 0) {{
+            const current = queue.shift();
+            edges.forEach((edge) => {{
+                if (edge.dataset.to !== current) {{
+                    return;
+                }}
+                edge.classList.add("hl");
+                if (!seen.has(edge.dataset.from)) {{
+                    seen.add(edge.dataset.from);
+                    queue.push(edge.dataset.from);
+                }}
+            }});
+        }}
+        seen.forEach((nodeId) => {{
+            const node = nodes.get(nodeId);
+            if (node) {{
+                node.classList.add("hl");
+            }}
+        }});
+        svg.classList.add("focus");
+    }};
+
+    const clear = () => {{
+        svg.classList.remove("focus");
+        svg.querySelectorAll(".hl").forEach((el) => el.classList.remove("hl"));
+    }};
+
+    nodes.forEach((node, id) => {{
+        node.addEventListener("mouseenter", () => highlight(id));
+        node.addEventListener("mouseleave", clear);
+    }});
+}})();
+    </script>"#,
+        width = layout.width,
+        height = layout.height,
     )
 }
 
-fn escape_attr(value: &str) -> String {
+/// Layered layout: each node's column is the longest dependency chain from a
+/// root, and rows inside a column follow the average row of the dependents
+/// to keep edges short.
+fn layout_graph(graph: &DepGraph) -> GraphLayout {
+    let count = graph.nodes.len();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut parents: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut indegree = vec![0usize; count];
+    for edge in &graph.edges {
+        if edge.from != edge.to {
+            children[edge.from].push(edge.to);
+            parents[edge.to].push(edge.from);
+            indegree[edge.to] += 1;
+        }
+    }
+
+    // Kahn's algorithm; nodes stuck in a cycle keep the depth reached so far.
+    let mut depth = vec![0usize; count];
+    let mut queue: VecDeque<usize> = (0..count).filter(|&n| indegree[n] == 0).collect();
+    while let Some(node) = queue.pop_front() {
+        for &child in &children[node] {
+            depth[child] = depth[child].max(depth[node] + 1);
+            indegree[child] -= 1;
+            if indegree[child] == 0 {
+                queue.push_back(child);
+            }
+        }
+    }
+
+    let column_count = depth.iter().max().map_or(0, |d| d + 1);
+    let mut columns: Vec<Vec<usize>> = vec![Vec::new(); column_count];
+    for (node, &d) in depth.iter().enumerate() {
+        columns[d].push(node);
+    }
+
+    let mut row = vec![0f32; count];
+    for column in &mut columns {
+        let barycenter = |node: usize| {
+            let placed: Vec<f32> = parents[node]
+                .iter()
+                .filter(|&&p| depth[p] < depth[node])
+                .map(|&p| row[p])
+                .collect();
+            if placed.is_empty() {
+                0.0
+            } else {
+                placed.iter().sum::<f32>() / placed.len() as f32
+            }
+        };
+        let mut keyed: Vec<(f32, usize)> = column.iter().map(|&n| (barycenter(n), n)).collect();
+        keyed.sort_by(|a, b| {
+            a.0.total_cmp(&b.0)
+                .then_with(|| graph.nodes[a.1].name.cmp(&graph.nodes[b.1].name))
+        });
+        *column = keyed.into_iter().map(|(_, n)| n).collect();
+        for (index, &node) in column.iter().enumerate() {
+            row[node] = index as f32;
+        }
+    }
+
+    let max_rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+    let row_height = NODE_HEIGHT + ROW_GAP;
+    let mut positions = vec![(0, 0); count];
+    for (index, column) in columns.iter().enumerate() {
+        // Center shorter columns against the tallest one.
+        let offset = (max_rows - column.len()) * row_height / 2;
+        for (slot, &node) in column.iter().enumerate() {
+            positions[node] = (
+                GRAPH_PADDING + index * (NODE_WIDTH + COLUMN_GAP),
+                GRAPH_PADDING + offset + slot * row_height,
+            );
+        }
+    }
+
+    GraphLayout {
+        positions,
+        width: GRAPH_PADDING * 2 + column_count * (NODE_WIDTH + COLUMN_GAP) - COLUMN_GAP,
+        height: GRAPH_PADDING * 2 + max_rows * row_height - ROW_GAP,
+    }
+}
+
+/// For each node, one shortest chain of packages from a workspace member
+/// (or any root when there are none) down to it.
+fn shortest_paths_from_members(graph: &DepGraph) -> Vec<Option<Vec<usize>>> {
+    let count = graph.nodes.len();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut has_parent = vec![false; count];
+    for edge in &graph.edges {
+        children[edge.from].push(edge.to);
+        has_parent[edge.to] = true;
+    }
+
+    let mut roots: Vec<usize> = (0..count)
+        .filter(|&n| graph.nodes[n].workspace_member)
+        .collect();
+    if roots.is_empty() {
+        roots = (0..count).filter(|&n| !has_parent[n]).collect();
+    }
+
+    let mut via: Vec<Option<Vec<usize>>> = vec![None; count];
+    let mut queue = VecDeque::new();
+    for root in roots {
+        via[root] = Some(vec![root]);
+        queue.push_back(root);
+    }
+    while let Some(node) = queue.pop_front() {
+        for &child in &children[node] {
+            if via[child].is_none() {
+                let mut path = via[node].clone().unwrap_or_default();
+                path.push(child);
+                via[child] = Some(path);
+                queue.push_back(child);
+            }
+        }
+    }
+    via
+}
+
+pub fn escape_attr(value: &str) -> String {
     let mut escaped = String::with_capacity(value.len());
 
     for ch in value.chars() {
@@ -485,3 +1331,109 @@ fn escape_attr(value: &str) -> String {
 
     escaped
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{DepEdge, DepNode};
+
+    fn krate(name: &str) -> CrateInfo {
+        CrateInfo {
+            name: name.to_string(),
+            description: format!("{name} docs"),
+            version: Some("1.0.0".to_string()),
+            path: format!("{name}/index.html"),
+            ..CrateInfo::default()
+        }
+    }
+
+    #[test]
+    fn groups_members_dependencies_and_stale_crates() {
+        let mut member = krate("my_app");
+        member.workspace_member = true;
+        let dependency = krate("serde");
+        let mut old = krate("old_dep");
+        old.stale = true;
+
+        let html = generate_overview_html(&[dependency, member, old]);
+        let workspace = html
+            .find(r#"data-section="workspace""#)
+            .expect("workspace section");
+        let deps = html
+            .find(r#"data-section="dependencies""#)
+            .expect("dependency section");
+        let stale = html.find(r#"data-section="stale""#).expect("stale section");
+        assert!(workspace < deps && deps < stale);
+        assert!(html.contains(r#"class="crate-card stale" data-name="old_dep""#));
+    }
+
+    #[test]
+    fn keeps_single_grid_without_metadata() {
+        let html = generate_overview_html(&[krate("serde")]);
+        assert!(!html.contains("data-section="));
+        assert!(html.contains(r#"<div class="crates-grid">"#));
+    }
+
+    fn node(name: &str, workspace_member: bool) -> DepNode {
+        DepNode {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            doc_name: name.to_string(),
+            workspace_member,
+        }
+    }
+
+    fn edge(from: usize, to: usize, kind: DepKind) -> DepEdge {
+        DepEdge { from, to, kind }
+    }
+
+    #[test]
+    fn lays_out_dependencies_right_of_dependents() {
+        // app -> hyper -> tokio, app -> tokio
+        let graph = DepGraph {
+            nodes: vec![
+                node("app", true),
+                node("hyper", false),
+                node("tokio", false),
+            ],
+            edges: vec![
+                edge(0, 1, DepKind::Normal),
+                edge(0, 2, DepKind::Normal),
+                edge(1, 2, DepKind::Normal),
+            ],
+        };
+
+        let layout = layout_graph(&graph);
+        let (app, hyper, tokio) = (
+            layout.positions[0].0,
+            layout.positions[1].0,
+            layout.positions[2].0,
+        );
+        assert!(app < hyper && hyper < tokio);
+
+        let via = shortest_paths_from_members(&graph);
+        assert_eq!(via[2], Some(vec![0, 2]));
+    }
+
+    #[test]
+    fn links_only_documented_nodes() {
+        let graph = DepGraph {
+            nodes: vec![node("app", true), node("serde", false), node("cc", false)],
+            edges: vec![edge(0, 1, DepKind::Normal), edge(0, 2, DepKind::Build)],
+        };
+
+        let html = generate_deps_html(Some(&graph), &[krate("app"), krate("serde")]);
+        assert!(html.contains(r#"<a href="serde/index.html">"#));
+        assert!(!html.contains(r#"<a href="cc/index.html">"#));
+        assert!(html.contains(r#"class="dep-node undocumented" data-id="2""#));
+        assert!(html.contains(r#"class="dep-edge build" data-from="0" data-to="2""#));
+        assert!(html.contains("被依赖 required by: app"));
+    }
+
+    #[test]
+    fn deps_page_without_metadata_shows_empty_state() {
+        let html = generate_deps_html(None, &[krate("serde")]);
+        assert!(html.contains("empty-state"));
+        assert!(!html.contains("<svg"));
+    }
+}
@@ -0,0 +1,260 @@
+//! User plugins: CSS and JS added to pages after the built-in UI.
+//!
+//! Plugins live in `plugins/` next to the global config
+//! (`~/.cargo-doc-viewer/plugins/`) and, once `project_plugins: true` in the
+//! global config allows it, in the project's `.cargo-doc-viewer/plugins/`.
+//! Every `*.css` and `*.js` file is part of the plugin named after its file
+//! stem, so `tracker.css` and `tracker.js` are both `tracker`, and
+//! `plugins.tracker: false` in the config turns it off. A project file
+//! replaces the global file of the same name. Scripts talk to the UI through
+//! the `window.CDV` hooks defined in `cdv.js`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::assets;
+use crate::config;
+
+const PROJECT_PLUGINS_DIR: &str = ".cargo-doc-viewer/plugins";
+
+/// The enabled plugins, each kind concatenated in load order: global files
+/// first, then the project's, each sorted by name.
+#[derive(Debug, Default)]
+pub struct Plugins {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+    pub css: String,
+    pub js: String,
+}
+
+impl Plugins {
+    /// Plugins of the global and project directories, as the current config
+    /// enables them.
+    pub fn load() -> Self {
+        let config = config::app_config();
+        let cwd = env::current_dir().unwrap_or_default();
+        let dirs = plugin_dirs(&config::config_path(), &cwd, config.project_plugins);
+        Self::from_dirs(&dirs, &config.plugins)
+    }
+
+    fn from_dirs(dirs: &[PathBuf], toggles: &BTreeMap<String, bool>) -> Self {
+        // Later directories replace files of the same name.
+        let mut files: BTreeMap<String, (usize, PathBuf)> = BTreeMap::new();
+        for (index, dir) in dirs.iter().enumerate() {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let kind = path.extension().and_then(OsStr::to_str);
+                if !matches!(kind, Some("css" | "js")) || !path.is_file() {
+                    continue;
+                }
+                if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+                    files.insert(name.to_string(), (index, path));
+                }
+            }
+        }
+        let mut files: Vec<(usize, PathBuf)> = files.into_values().collect();
+        files.sort();
+
+        let mut plugins = Plugins::default();
+        let mut enabled = BTreeSet::new();
+        let mut disabled = BTreeSet::new();
+        for (_, path) in files {
+            let name = plugin_name(&path);
+            if toggles.get(&name) == Some(&false) {
+                disabled.insert(name);
+                continue;
+            }
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(err) => {
+                    eprintln!("Warning: Skipping plugin {}: {err}", path.display());
+                    continue;
+                }
+            };
+            if path.extension() == Some(OsStr::new("css")) {
+                plugins
+                    .css
+                    .push_str(&format!("/* plugin: {name} */\n{content}\n"));
+            } else {
+                plugins.js.push_str(&plugin_script(&name, &content));
+            }
+            enabled.insert(name);
+        }
+        plugins.enabled = enabled.into_iter().collect();
+        plugins.disabled = disabled.into_iter().collect();
+        plugins
+    }
+
+    /// `plugins.<hash>.css`, linked like the built-in assets; `None` without
+    /// plugin CSS.
+    pub fn css_file_name(&self) -> Option<String> {
+        (!self.css.is_empty()).then(|| format!("plugins.{}.css", assets::content_hash(&self.css)))
+    }
+
+    /// `plugins.<hash>.js`, see [`Plugins::css_file_name`].
+    pub fn js_file_name(&self) -> Option<String> {
+        (!self.js.is_empty()).then(|| format!("plugins.{}.js", assets::content_hash(&self.js)))
+    }
+
+    /// Content and MIME type of the linked bundle called `name`, as
+    /// [`assets::linked`] does for the built-in ones.
+    pub fn linked(self, name: &str) -> Option<(String, &'static str)> {
+        if self.css_file_name().as_deref() == Some(name) {
+            Some((self.css, "text/css; charset=utf-8"))
+        } else if self.js_file_name().as_deref() == Some(name) {
+            Some((self.js, "application/javascript; charset=utf-8"))
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs one plugin from the shared bundle. The source is compiled with
+/// `new Function`, so a syntax error is thrown where it can be caught instead
+/// of stopping the whole bundle from parsing; together with the `try` around
+/// the call, one broken plugin does not stop the others.
+fn plugin_script(name: &str, content: &str) -> String {
+    let source = format!("{content}\n//# sourceURL=cdv-plugins/{name}.js");
+    let source = serde_json::to_string(&source).unwrap_or_default();
+    let label = serde_json::to_string(name).unwrap_or_default();
+    format!(
+        "try {{ new Function({})(); }} catch (err) {{ console.warn('[CDV] Plugin ' + {} + ' failed:', err); }}\n",
+        config::escape_json_for_script(&source),
+        config::escape_json_for_script(&label)
+    )
+}
+
+/// Prints the plugins in use, if there are any, and a project plugin
+/// directory left out for want of the opt-in.
+pub fn report() {
+    let config_path = config::config_path();
+    if !config::app_config().project_plugins
+        && let Ok(cwd) = env::current_dir()
+        && let Some(dir) = project_dir(&config_path, &cwd)
+    {
+        println!(
+            "Ignoring project plugins in {}; set `project_plugins: true` in {} to load them.",
+            dir.display(),
+            config_path.display()
+        );
+    }
+    let plugins = Plugins::load();
+    if !plugins.enabled.is_empty() {
+        println!("Plugins: {}", plugins.enabled.join(", "));
+    }
+    if !plugins.disabled.is_empty() {
+        println!(
+            "Plugins disabled in config: {}",
+            plugins.disabled.join(", ")
+        );
+    }
+}
+
+/// The global directory next to `config_path`, then with `project_plugins`
+/// the nearest project one above `cwd`.
+fn plugin_dirs(config_path: &Path, cwd: &Path, project_plugins: bool) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = config_path
+        .parent()
+        .map(|parent| parent.join("plugins"))
+        .into_iter()
+        .collect();
+    if project_plugins {
+        dirs.extend(project_dir(config_path, cwd));
+    }
+    dirs
+}
+
+/// The nearest project plugin directory above `cwd`, unless that is the
+/// global one.
+fn project_dir(config_path: &Path, cwd: &Path) -> Option<PathBuf> {
+    let project = cwd
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_PLUGINS_DIR))
+        .find(|dir| dir.is_dir())?;
+    let global = config_path.parent().map(|parent| parent.join("plugins"));
+    let same = global
+        .is_some_and(|global| fs::canonicalize(global).ok() == fs::canonicalize(&project).ok());
+    (!same).then_some(project)
+}
+
+fn plugin_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_plugins_replace_global_ones_and_config_disables() {
+        let root = std::env::temp_dir().join(format!("cdv-plugins-{}", std::process::id()));
+        let global = root.join("global");
+        let project = root.join("project");
+        fs::create_dir_all(&global).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(global.join("tracker.js"), "window.fromGlobal = 1;").unwrap();
+        fs::write(global.join("tracker.css"), ".tracker {}").unwrap();
+        fs::write(global.join("keys.js"), "window.keys = 1;").unwrap();
+        fs::write(global.join("notes.txt"), "not a plugin").unwrap();
+        fs::write(project.join("tracker.js"), "window.fromProject = 1;").unwrap();
+        fs::write(project.join("theme.css"), ".theme {}").unwrap();
+        let dirs = [global, project];
+
+        let plugins = Plugins::from_dirs(&dirs, &BTreeMap::new());
+        assert_eq!(plugins.enabled, ["keys", "theme", "tracker"]);
+        assert!(plugins.js.contains("window.fromProject"));
+        assert!(!plugins.js.contains("window.fromGlobal"));
+        assert!(plugins.js.find("window.keys") < plugins.js.find("window.fromProject"));
+        assert!(plugins.css.find(".tracker") < plugins.css.find(".theme"));
+        let js = plugins.js_file_name().unwrap();
+        assert!(js.starts_with("plugins.") && js.ends_with(".js"));
+        assert!(
+            plugins
+                .linked(&js)
+                .is_some_and(|(content, _)| content.contains("window.keys"))
+        );
+
+        // A syntax error only reaches the bundle as a string to compile.
+        let broken = "window.broken = (;";
+        fs::write(dirs[1].join("broken.js"), broken).unwrap();
+        let plugins = Plugins::from_dirs(&dirs, &BTreeMap::new());
+        assert!(plugins.js.contains(r#"new Function("window.broken = (;\n"#));
+        assert!(plugins.js.contains(r#"new Function("window.keys = 1;\n"#));
+        fs::remove_file(dirs[1].join("broken.js")).unwrap();
+
+        let toggles = BTreeMap::from([("tracker".to_string(), false)]);
+        let plugins = Plugins::from_dirs(&dirs, &toggles);
+        assert_eq!(plugins.enabled, ["keys", "theme"]);
+        assert_eq!(plugins.disabled, ["tracker"]);
+        assert!(!plugins.css.contains(".tracker"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn project_plugins_need_the_global_opt_in() {
+        let root = std::env::temp_dir().join(format!("cdv-plugin-dirs-{}", std::process::id()));
+        let config_path = root.join("home/.cargo-doc-viewer/config.yaml");
+        let checkout = root.join("checkout");
+        let cwd = checkout.join("src");
+        let global = root.join("home/.cargo-doc-viewer/plugins");
+        let project = checkout.join(PROJECT_PLUGINS_DIR);
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&cwd).unwrap();
+
+        // A cloned repository's scripts are refused until the user opts in.
+        assert_eq!(plugin_dirs(&config_path, &cwd, false), [global.as_path()]);
+        assert_eq!(
+            plugin_dirs(&config_path, &cwd, true),
+            [global.as_path(), project.as_path()]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,480 @@
+//! Wire formats of the supported chat providers.
+//!
+//! The chat panel always speaks the OpenAI chat-completions format. For other
+//! providers the proxy translates each request into the provider's API and
+//! the reply, buffered or streamed, back into chat-completions JSON or
+//! Server-Sent Events, so the panel needs no provider-specific parsing.
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::config::Provider;
+use crate::llm::ChatMessage;
+
+/// Anthropic rejects requests without an output limit.
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Serialize)]
+struct OpenAiRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+/// The providers reached over HTTP; [`Provider::Mock`] has no wire format
+/// because the server answers it itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    OpenAi,
+    Anthropic,
+    Ollama,
+}
+
+impl WireFormat {
+    /// `None` for [`Provider::Mock`].
+    pub fn of(provider: Provider) -> Option<Self> {
+        match provider {
+            Provider::OpenAi => Some(WireFormat::OpenAi),
+            Provider::Anthropic => Some(WireFormat::Anthropic),
+            Provider::Ollama => Some(WireFormat::Ollama),
+            Provider::Mock => None,
+        }
+    }
+
+    fn provider(self) -> Provider {
+        match self {
+            WireFormat::OpenAi => Provider::OpenAi,
+            WireFormat::Anthropic => Provider::Anthropic,
+            WireFormat::Ollama => Provider::Ollama,
+        }
+    }
+}
+
+/// Endpoint for chat requests under `base`, which may already include it.
+pub fn chat_url(provider: WireFormat, base: &str) -> String {
+    let trimmed = base.trim().trim_end_matches('/');
+    let base = if trimmed.is_empty() {
+        provider.provider().default_base_url()
+    } else {
+        trimmed
+    };
+    let suffix = match provider {
+        WireFormat::OpenAi => "/chat/completions",
+        WireFormat::Anthropic => "/messages",
+        WireFormat::Ollama => "/api/chat",
+    };
+    if base.to_ascii_lowercase().ends_with(suffix) {
+        base.to_string()
+    } else {
+        format!("{base}{suffix}")
+    }
+}
+
+/// Header carrying the API key, and how a bare key is written into it.
+pub fn auth_header(provider: WireFormat, key: &str) -> (&'static str, String) {
+    match provider {
+        WireFormat::Anthropic => {
+            let key = key.trim();
+            let key = match key.get(..7) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("bearer ") => key[7..].trim_start(),
+                _ => key,
+            };
+            ("x-api-key", key.to_string())
+        }
+        WireFormat::OpenAi | WireFormat::Ollama => ("authorization", normalize_bearer(key)),
+    }
+}
+
+/// Headers the provider requires that users should not have to configure.
+pub fn default_headers(provider: WireFormat) -> &'static [(&'static str, &'static str)] {
+    match provider {
+        WireFormat::Anthropic => &[("anthropic-version", ANTHROPIC_VERSION)],
+        WireFormat::OpenAi | WireFormat::Ollama => &[],
+    }
+}
+
+pub fn normalize_bearer(value: &str) -> String {
+    let trimmed = value.trim();
+    let has_scheme = trimmed
+        .get(..7)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("bearer "));
+    if has_scheme || trimmed.contains(' ') {
+        trimmed.to_string()
+    } else {
+        format!("Bearer {trimmed}")
+    }
+}
+
+pub fn encode_request(
+    provider: WireFormat,
+    model: &str,
+    messages: &[ChatMessage],
+    stream: bool,
+) -> serde_json::Result<Vec<u8>> {
+    match provider {
+        WireFormat::OpenAi => serde_json::to_vec(&OpenAiRequest {
+            model,
+            messages,
+            stream,
+        }),
+        WireFormat::Anthropic => {
+            let (system, messages) = anthropic_messages(messages);
+            let mut body = json!({
+                "model": model,
+                "max_tokens": ANTHROPIC_MAX_TOKENS,
+                "messages": messages,
+            });
+            if !system.is_empty() {
+                body["system"] = Value::String(system);
+            }
+            if stream {
+                body["stream"] = Value::Bool(true);
+            }
+            serde_json::to_vec(&body)
+        }
+        // Ollama streams unless told otherwise, so the flag is always sent.
+        WireFormat::Ollama => serde_json::to_vec(&json!({
+            "model": model,
+            "messages": messages,
+            "stream": stream,
+        })),
+    }
+}
+
+/// Anthropic takes system prompts as a separate field and expects user and
+/// assistant turns to alternate, so consecutive turns of one role are joined.
+fn anthropic_messages(messages: &[ChatMessage]) -> (String, Vec<ChatMessage>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<ChatMessage> = Vec::new();
+    for message in messages {
+        if message.role == "system" {
+            system.push(message.content.as_str());
+            continue;
+        }
+        match turns.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => turns.push(message.clone()),
+        }
+    }
+    (system.join("\n\n"), turns)
+}
+
+/// Translates a successful buffered reply into a chat completion.
+pub fn translate_response(provider: WireFormat, body: &[u8]) -> Result<Vec<u8>, String> {
+    let parse = || {
+        serde_json::from_slice::<Value>(body)
+            .map_err(|err| format!("provider returned invalid JSON: {err}"))
+    };
+    let (content, finish_reason) = match provider {
+        WireFormat::OpenAi => return Ok(body.to_vec()),
+        WireFormat::Anthropic => {
+            let reply = parse()?;
+            let blocks = reply["content"]
+                .as_array()
+                .ok_or("provider reply has no content")?;
+            let text: String = blocks
+                .iter()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect();
+            let finish = match reply["stop_reason"].as_str() {
+                Some("max_tokens") => Some("length"),
+                Some(_) => Some("stop"),
+                None => None,
+            };
+            (text, finish.map(str::to_string))
+        }
+        WireFormat::Ollama => {
+            let reply = parse()?;
+            let text = reply["message"]["content"]
+                .as_str()
+                .ok_or("provider reply has no message")?
+                .to_string();
+            let finish = reply["done_reason"].as_str().map(str::to_string);
+            (text, finish)
+        }
+    };
+    Ok(completion(&content, finish_reason.as_deref()))
+}
+
+/// A chat-completions body holding `content`.
+pub fn completion(content: &str, finish_reason: Option<&str>) -> Vec<u8> {
+    json!({
+        "object": "chat.completion",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": finish_reason,
+        }],
+    })
+    .to_string()
+    .into_bytes()
+}
+
+/// One chat-completions SSE frame carrying `text`.
+pub fn delta_frame(text: &str) -> String {
+    let frame = json!({"choices": [{"index": 0, "delta": {"content": text}}]});
+    format!("data: {frame}\n\n")
+}
+
+/// Rewrites `{"error": "..."}` bodies into the `{"error": {"message": ...}}`
+/// shape the panel reports; anything else is returned unchanged.
+pub fn translate_error(body: &[u8]) -> Vec<u8> {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(map)) if map.get("error").is_some_and(Value::is_string) => {
+            json!({"error": {"message": map["error"]}})
+                .to_string()
+                .into_bytes()
+        }
+        _ => body.to_vec(),
+    }
+}
+
+enum StreamEvent {
+    Delta(String),
+    Error(String),
+    Done,
+}
+
+/// Turns a provider's streamed reply into chat-completions SSE frames.
+///
+/// Anthropic sends SSE with its own event types and Ollama sends one JSON
+/// object per line; both are fed in arbitrary chunks and re-framed by line.
+pub struct StreamTranslator {
+    provider: WireFormat,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl StreamTranslator {
+    pub fn new(provider: WireFormat) -> Self {
+        Self {
+            provider,
+            buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// SSE frames for the complete lines in `chunk` plus earlier leftovers.
+    pub fn push(&mut self, chunk: &[u8]) -> String {
+        if matches!(self.provider, WireFormat::OpenAi) {
+            return String::from_utf8_lossy(chunk).into_owned();
+        }
+        self.buffer.extend_from_slice(chunk);
+        let mut out = String::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            self.translate_line(&String::from_utf8_lossy(&line), &mut out);
+        }
+        out
+    }
+
+    /// Flushes a trailing line and closes the stream with `[DONE]` if the
+    /// provider did not end it.
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        if matches!(self.provider, WireFormat::OpenAi) {
+            return out;
+        }
+        let rest = std::mem::take(&mut self.buffer);
+        self.translate_line(&String::from_utf8_lossy(&rest), &mut out);
+        if !self.finished {
+            self.finished = true;
+            out.push_str("data: [DONE]\n\n");
+        }
+        out
+    }
+
+    fn translate_line(&mut self, line: &str, out: &mut String) {
+        let line = line.trim();
+        if line.is_empty() || self.finished {
+            return;
+        }
+        let data = match self.provider {
+            // `event:` lines repeat the `type` field of the data that follows.
+            WireFormat::Anthropic => match line.strip_prefix("data:") {
+                Some(data) => data.trim(),
+                None => return,
+            },
+            WireFormat::Ollama | WireFormat::OpenAi => line,
+        };
+        let Ok(event) = serde_json::from_str::<Value>(data) else {
+            return;
+        };
+        let events = match self.provider {
+            WireFormat::Anthropic => anthropic_event(&event),
+            WireFormat::Ollama => ollama_event(&event),
+            WireFormat::OpenAi => Vec::new(),
+        };
+        for event in events {
+            match event {
+                StreamEvent::Delta(text) => out.push_str(&delta_frame(&text)),
+                StreamEvent::Error(message) => {
+                    let frame = json!({"error": {"message": message}});
+                    out.push_str(&format!("data: {frame}\n\n"));
+                }
+                StreamEvent::Done => {
+                    self.finished = true;
+                    out.push_str("data: [DONE]\n\n");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn anthropic_event(event: &Value) -> Vec<StreamEvent> {
+    match event["type"].as_str() {
+        Some("content_block_delta") => match event["delta"]["text"].as_str() {
+            Some(text) if !text.is_empty() => vec![StreamEvent::Delta(text.to_string())],
+            _ => Vec::new(),
+        },
+        Some("message_stop") => vec![StreamEvent::Done],
+        Some("error") => {
+            let message = event["error"]["message"]
+                .as_str()
+                .unwrap_or("provider stream failed");
+            vec![StreamEvent::Error(message.to_string())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn ollama_event(event: &Value) -> Vec<StreamEvent> {
+    if let Some(message) = event["error"].as_str() {
+        return vec![StreamEvent::Error(message.to_string())];
+    }
+    let mut events = Vec::new();
+    if let Some(text) = event["message"]["content"].as_str()
+        && !text.is_empty()
+    {
+        events.push(StreamEvent::Delta(text.to_string()));
+    }
+    if event["done"].as_bool() == Some(true) {
+        events.push(StreamEvent::Done);
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn builds_provider_urls() {
+        assert_eq!(
+            chat_url(WireFormat::OpenAi, "https://api.openai.com/v1/"),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(
+            chat_url(
+                WireFormat::OpenAi,
+                "http://localhost:8080/v1/chat/completions"
+            ),
+            "http://localhost:8080/v1/chat/completions"
+        );
+        assert_eq!(
+            chat_url(WireFormat::OpenAi, "  "),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(
+            chat_url(WireFormat::Anthropic, ""),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(
+            chat_url(WireFormat::Ollama, "http://gpu-box:11434/"),
+            "http://gpu-box:11434/api/chat"
+        );
+    }
+
+    #[test]
+    fn stream_flag_is_only_sent_when_requested() {
+        let messages = vec![message("user", "hi")];
+        let plain: Value = serde_json::from_slice(
+            &encode_request(WireFormat::OpenAi, "m", &messages, false).unwrap(),
+        )
+        .unwrap();
+        assert!(plain.get("stream").is_none());
+
+        let streaming: Value = serde_json::from_slice(
+            &encode_request(WireFormat::OpenAi, "m", &messages, true).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(streaming["stream"], json!(true));
+
+        let ollama: Value = serde_json::from_slice(
+            &encode_request(WireFormat::Ollama, "m", &messages, false).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(ollama["stream"], json!(false));
+    }
+
+    #[test]
+    fn anthropic_requests_lift_system_prompts_and_merge_turns() {
+        let messages = vec![
+            message("system", "be brief"),
+            message("system", "Page summary: x"),
+            message("user", "earlier"),
+            message("assistant", "answer"),
+            message("user", "first"),
+            message("user", "second"),
+        ];
+        let body: Value = serde_json::from_slice(
+            &encode_request(WireFormat::Anthropic, "claude", &messages, false).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["system"], "be brief\n\nPage summary: x");
+        assert_eq!(body["max_tokens"], ANTHROPIC_MAX_TOKENS);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+        assert_eq!(body["messages"][2]["content"], "first\n\nsecond");
+    }
+
+    #[test]
+    fn normalizes_bearer_tokens() {
+        assert_eq!(normalize_bearer("sk-123"), "Bearer sk-123");
+        assert_eq!(normalize_bearer("bearer sk-123"), "bearer sk-123");
+        assert_eq!(normalize_bearer("Basic abc"), "Basic abc");
+        assert_eq!(
+            auth_header(WireFormat::Anthropic, "Bearer sk-ant"),
+            ("x-api-key", "sk-ant".to_string())
+        );
+    }
+
+    #[test]
+    fn reframes_streams_split_mid_line() {
+        let mut translator = StreamTranslator::new(WireFormat::Ollama);
+        let mut out = translator.push(b"{\"message\":{\"content\":\"Hel");
+        assert!(out.is_empty());
+        out += &translator
+            .push(b"lo\"},\"done\":false}\n{\"message\":{\"content\":\"!\"},\"done\":true}\n");
+        out += &translator.finish();
+        assert_eq!(
+            out,
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"},\"index\":0}]}\n\n\
+             data: {\"choices\":[{\"delta\":{\"content\":\"!\"},\"index\":0}]}\n\n\
+             data: [DONE]\n\n"
+        );
+    }
+
+    #[test]
+    fn wraps_string_errors() {
+        assert_eq!(
+            translate_error(br#"{"error":"model not found"}"#),
+            br#"{"error":{"message":"model not found"}}"#.to_vec()
+        );
+        let structured = br#"{"error":{"message":"bad key"}}"#;
+        assert_eq!(translate_error(structured), structured.to_vec());
+    }
+}
@@ -0,0 +1,277 @@
+//! Retrieval for the chat panel.
+//!
+//! Item docs collected by the search index are split into overlapping chunks
+//! and ranked with BM25 against the user's question, so answers can draw on
+//! pages other than the one being read. Served at
+//! `GET /cdv-api/retrieve?q=&k=&current=`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::search::{self, SearchItem};
+
+/// Route served by `server.rs` for retrieval queries.
+pub const RETRIEVE_PATH: &str = "/cdv-api/retrieve";
+
+pub const DEFAULT_TOP_K: usize = 5;
+pub const MAX_TOP_K: usize = 20;
+
+/// Words per chunk and how many of them are repeated in the next chunk so a
+/// sentence cut at the boundary still appears whole once.
+const CHUNK_WORDS: usize = 120;
+const CHUNK_OVERLAP: usize = 20;
+
+/// At most this many chunks from one item, so a single long page cannot fill
+/// the whole result list.
+const CHUNKS_PER_ITEM: usize = 2;
+
+/// Score multiplier for chunks from the crate the reader is on.
+const CURRENT_CRATE_BOOST: f32 = 1.5;
+
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Question words that carry no signal about which docs are relevant.
+pub const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "can", "do", "does", "for", "how", "i", "in", "is", "it", "my", "of",
+    "on", "or", "the", "to", "what", "when", "which", "why", "with",
+];
+
+#[derive(Debug)]
+struct Chunk {
+    item: usize,
+    text: String,
+    len: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RetrievedChunk<'a> {
+    pub path: &'a str,
+    pub url: &'a str,
+    pub kind: &'a str,
+    #[serde(rename = "crate")]
+    pub krate: &'a str,
+    pub text: &'a str,
+    pub score: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct ChunkIndex {
+    items: Vec<SearchItem>,
+    chunks: Vec<Chunk>,
+    /// Token to `(chunk, term frequency)` postings.
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    avg_len: f32,
+}
+
+impl ChunkIndex {
+    pub fn from_items(items: &[SearchItem]) -> Self {
+        let items: Vec<SearchItem> = items
+            .iter()
+            .filter(|item| !item.text.is_empty())
+            .cloned()
+            .collect();
+
+        let mut chunks = Vec::new();
+        let mut postings: HashMap<String, Vec<(usize, u32)>> = HashMap::new();
+        for (item_id, item) in items.iter().enumerate() {
+            for text in split_chunks(&item.text) {
+                // The item path is part of every chunk so "client builder"
+                // also finds `Client::builder` prose that never repeats it.
+                let mut counts: HashMap<String, u32> = HashMap::new();
+                let mut len = 0;
+                for token in terms(&item.path).chain(terms(&text)) {
+                    *counts.entry(token).or_default() += 1;
+                    len += 1;
+                }
+                let id = chunks.len();
+                for (token, count) in counts {
+                    postings.entry(token).or_default().push((id, count));
+                }
+                chunks.push(Chunk {
+                    item: item_id,
+                    text,
+                    len,
+                });
+            }
+        }
+
+        let total: u64 = chunks.iter().map(|chunk| u64::from(chunk.len)).sum();
+        let avg_len = if chunks.is_empty() {
+            0.0
+        } else {
+            total as f32 / chunks.len() as f32
+        };
+
+        Self {
+            items,
+            chunks,
+            postings,
+            avg_len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The `k` chunks most relevant to `question`, best first.
+    pub fn retrieve(
+        &self,
+        question: &str,
+        k: usize,
+        current: Option<&str>,
+    ) -> Vec<RetrievedChunk<'_>> {
+        let mut query: Vec<String> = terms(question)
+            .filter(|term| !STOPWORDS.contains(&term.as_str()))
+            .collect();
+        query.sort();
+        query.dedup();
+
+        let count = self.chunks.len() as f32;
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for term in &query {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f32;
+            let idf = (1.0 + (count - df + 0.5) / (df + 0.5)).ln();
+            for &(chunk, tf) in postings {
+                let tf = tf as f32;
+                let len = self.chunks[chunk].len as f32;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / self.avg_len.max(1.0));
+                *scores.entry(chunk).or_default() += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores
+            .into_iter()
+            .map(|(chunk, score)| {
+                let item = &self.items[self.chunks[chunk].item];
+                if current.is_some_and(|krate| item.krate == krate) {
+                    (chunk, score * CURRENT_CRATE_BOOST)
+                } else {
+                    (chunk, score)
+                }
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let mut per_item: HashMap<usize, usize> = HashMap::new();
+        ranked
+            .into_iter()
+            .filter(|(chunk, _)| {
+                let taken = per_item.entry(self.chunks[*chunk].item).or_default();
+                *taken += 1;
+                *taken <= CHUNKS_PER_ITEM
+            })
+            .take(k)
+            .map(|(chunk, score)| {
+                let chunk_ref = &self.chunks[chunk];
+                let item = &self.items[chunk_ref.item];
+                RetrievedChunk {
+                    path: &item.path,
+                    url: &item.url,
+                    kind: &item.kind,
+                    krate: &item.krate,
+                    text: &chunk_ref.text,
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Word tokens with repeats kept, for term frequencies.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace().flat_map(search::tokenize)
+}
+
+fn split_chunks(text: &str) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() <= CHUNK_WORDS {
+        return vec![words.join(" ")];
+    }
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + CHUNK_WORDS).min(words.len());
+        chunks.push(words[start..end].join(" "));
+        if end == words.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(krate: &str, path: &str, text: &str) -> SearchItem {
+        SearchItem {
+            name: path.rsplit("::").next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            kind: "struct".to_string(),
+            krate: krate.to_string(),
+            url: format!("/{}.html", path.replace("::", "/")),
+            summary: String::new(),
+            text: text.to_string(),
+        }
+    }
+
+    fn index() -> ChunkIndex {
+        ChunkIndex::from_items(&[
+            item(
+                "hyper_rustls",
+                "hyper_rustls::HttpsConnectorBuilder",
+                "A builder for an HttpsConnector. Enable TLS with native or webpki roots before building the client.",
+            ),
+            item(
+                "hyper",
+                "hyper::client::Builder",
+                "A builder to configure a new Client. Set pool options and timeouts.",
+            ),
+            item(
+                "serde",
+                "serde::Serialize",
+                "A data structure that can be serialized.",
+            ),
+            item("hyper", "hyper::Body", ""),
+        ])
+    }
+
+    #[test]
+    fn splits_long_text_with_overlap() {
+        let text: Vec<String> = (0..250).map(|n| format!("w{n}")).collect();
+        let chunks = split_chunks(&text.join(" "));
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].ends_with("w119"));
+        assert!(chunks[1].starts_with("w100 "));
+        assert!(chunks[2].ends_with("w249"));
+    }
+
+    #[test]
+    fn ranks_chunks_from_other_pages() {
+        let index = index();
+        assert_eq!(index.len(), 3);
+
+        let hits = index.retrieve("How do I build a client with TLS?", 2, None);
+        assert_eq!(hits[0].path, "hyper_rustls::HttpsConnectorBuilder");
+        assert_eq!(hits[0].url, "/hyper_rustls/HttpsConnectorBuilder.html");
+
+        assert!(index.retrieve("how do i", 5, None).is_empty());
+    }
+
+    #[test]
+    fn boosts_the_current_crate() {
+        let index = index();
+        let neutral = index.retrieve("builder client", 1, None);
+        let boosted = index.retrieve("builder client", 1, Some("hyper"));
+        assert_eq!(boosted[0].krate, "hyper");
+        assert!(neutral[0].score <= boosted[0].score);
+    }
+}
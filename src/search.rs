@@ -0,0 +1,563 @@
+//! Cross-crate full-text search over the generated docs.
+//!
+//! rustdoc's own search only covers one crate's `search-index.js` at a time.
+//! The server walks every item page under the doc root instead, records item
+//! names, paths and docblock text in an inverted index and answers
+//! `GET /cdv-api/search?q=&kind=&crate=` from it.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::overview::strip_html_tags;
+
+/// Route served by `server.rs` for search queries.
+pub const SEARCH_PATH: &str = "/cdv-api/search";
+
+/// Default and maximum number of results per query.
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 200;
+
+/// Length of the `summary` shown in result lists.
+const SUMMARY_CHARS: usize = 200;
+
+/// Top-level doc directories that are not crates.
+const SKIPPED_DIRS: &[&str] = &[
+    "static.files",
+    "src",
+    "implementors",
+    "trait.impl",
+    "type.impl",
+];
+
+/// rustdoc file prefixes (`struct.Foo.html`) that name an item page.
+const ITEM_KINDS: &[&str] = &[
+    "struct",
+    "enum",
+    "trait",
+    "fn",
+    "macro",
+    "constant",
+    "type",
+    "union",
+    "static",
+    "primitive",
+    "keyword",
+    "attr",
+    "derive",
+    "traitalias",
+];
+
+/// Headings after which a page only lists trait impls, whose methods
+/// (`fmt`, `clone`, …) would drown out the item's own API.
+const IMPL_SECTIONS: &[&str] = &[
+    r#"id="trait-implementations""#,
+    r#"id="synthetic-implementations""#,
+    r#"id="blanket-implementations""#,
+    r#"id="implementors""#,
+    r#"id="foreign-impls""#,
+];
+
+/// One searchable item: a rustdoc page or a method documented on one.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchItem {
+    pub name: String,
+    /// Full path such as `once_cell::sync::OnceCell::get`.
+    pub path: String,
+    pub kind: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// Absolute URL below the doc root, with a fragment for methods.
+    pub url: String,
+    pub summary: String,
+    /// Plain docblock text.
+    #[serde(skip)]
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    items: Vec<SearchItem>,
+    /// Token to `(item, weight)` postings, ordered for prefix scans.
+    postings: BTreeMap<String, Vec<(usize, u32)>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SearchQuery {
+    pub text: String,
+    pub kind: Option<String>,
+    pub krate: Option<String>,
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults<'a> {
+    pub query: &'a str,
+    pub total: usize,
+    pub results: Vec<&'a SearchItem>,
+}
+
+impl SearchIndex {
+    /// Scans every crate directory under `doc_root`.
+    pub fn build(doc_root: &Path) -> io::Result<Self> {
+        Ok(Self::from_items(scan_items(doc_root)?))
+    }
+
+    pub fn from_items(items: Vec<SearchItem>) -> Self {
+        let mut postings: BTreeMap<String, Vec<(usize, u32)>> = BTreeMap::new();
+        for (id, item) in items.iter().enumerate() {
+            let mut weights: HashMap<String, u32> = HashMap::new();
+            weights.insert(item.name.to_lowercase(), 20);
+            for token in tokenize(&item.name) {
+                *weights.entry(token).or_default() += 10;
+            }
+            for token in tokenize(&item.path) {
+                *weights.entry(token).or_default() += 3;
+            }
+            // Tokens are deduplicated, so long docs repeating a word still
+            // count it once and cannot outrank a name hit.
+            for token in tokenize(&item.text) {
+                *weights.entry(token).or_default() += 1;
+            }
+            for (token, weight) in weights {
+                postings.entry(token).or_default().push((id, weight));
+            }
+        }
+        Self { items, postings }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn items(&self) -> &[SearchItem] {
+        &self.items
+    }
+
+    /// Items matching every term of `query.text`, best first. The last term
+    /// also matches as a prefix so results follow the user's typing.
+    pub fn search<'a>(&'a self, query: &'a SearchQuery) -> SearchResults<'a> {
+        let terms = tokenize(&query.text);
+        let mut scores: Option<HashMap<usize, f32>> = None;
+
+        for (index, term) in terms.iter().enumerate() {
+            let prefix = index + 1 == terms.len();
+            let mut term_scores: HashMap<usize, f32> = HashMap::new();
+            for (token, postings) in self.postings.range(term.clone()..) {
+                let factor = if token == term {
+                    1.0
+                } else if prefix && token.starts_with(term.as_str()) {
+                    0.5
+                } else {
+                    break;
+                };
+                for &(id, weight) in postings {
+                    *term_scores.entry(id).or_default() += weight as f32 * factor;
+                }
+            }
+
+            scores = Some(match scores {
+                None => term_scores,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, score)| term_scores.get(&id).map(|s| (id, score + s)))
+                    .collect(),
+            });
+        }
+
+        let kind = query.kind.as_deref().map(normalize_kind);
+        let exact = query.text.trim().to_lowercase();
+        let mut hits: Vec<(usize, f32)> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&(id, _)| {
+                let item = &self.items[id];
+                kind.is_none_or(|kind| item.kind == kind)
+                    && query
+                        .krate
+                        .as_deref()
+                        .is_none_or(|krate| item.krate == krate)
+            })
+            .map(|(id, score)| {
+                let item = &self.items[id];
+                let bonus =
+                    if item.name.to_lowercase() == exact || item.path.to_lowercase() == exact {
+                        100.0
+                    } else {
+                        0.0
+                    };
+                (id, score + bonus)
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            let (left, right) = (&self.items[a.0], &self.items[b.0]);
+            b.1.total_cmp(&a.1)
+                .then_with(|| left.path.len().cmp(&right.path.len()))
+                .then_with(|| left.path.cmp(&right.path))
+        });
+
+        SearchResults {
+            query: &query.text,
+            total: hits.len(),
+            results: hits
+                .into_iter()
+                .take(query.limit)
+                .map(|(id, _)| &self.items[id])
+                .collect(),
+        }
+    }
+}
+
+/// Maps the filter names used by the injected search UI onto rustdoc kinds.
+fn normalize_kind(kind: &str) -> &str {
+    match kind {
+        "function" => "fn",
+        "module" => "mod",
+        "const" => "constant",
+        "tymethod" => "method",
+        other => other,
+    }
+}
+
+/// Lowercase word tokens; `snake_case` and `CamelCase` identifiers also yield
+/// their parts so `hashmap`, `hash` and `map` all find `HashMap`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut seen = HashSet::new();
+    let mut push = |token: String| {
+        if !token.is_empty() && seen.insert(token.clone()) {
+            tokens.push(token);
+        }
+    };
+
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        let word = word.trim_matches('_');
+        if word.is_empty() {
+            continue;
+        }
+        push(word.to_lowercase());
+
+        let mut part = String::new();
+        let mut prev_lower = false;
+        for ch in word.chars() {
+            if ch == '_' || (ch.is_uppercase() && prev_lower) {
+                push(std::mem::take(&mut part).to_lowercase());
+            }
+            if ch != '_' {
+                part.push(ch);
+            }
+            prev_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        }
+        push(part.to_lowercase());
+    }
+
+    tokens
+}
+
+/// Reads every item page under `doc_root` into [`SearchItem`]s.
+pub fn scan_items(doc_root: &Path) -> io::Result<Vec<SearchItem>> {
+    let mut items = Vec::new();
+    for entry in fs::read_dir(doc_root)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if !path.is_dir() || SKIPPED_DIRS.contains(&name) || !path.join("index.html").exists() {
+            continue;
+        }
+        scan_dir(&path, &[name.to_string()], &mut items)?;
+    }
+    Ok(items)
+}
+
+fn scan_dir(dir: &Path, modules: &[String], items: &mut Vec<SearchItem>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        if path.is_dir() {
+            let mut nested = modules.to_vec();
+            nested.push(file_name.to_string());
+            scan_dir(&path, &nested, items)?;
+            continue;
+        }
+
+        let Some((kind, name)) = page_kind(file_name, modules) else {
+            continue;
+        };
+        // One unreadable page should not cost the whole index.
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!(
+                    "Warning: Skipping {} in the search index: {err}",
+                    path.display()
+                );
+                continue;
+            }
+        };
+        // Re-exports leave small redirect stubs behind.
+        if content.contains(r#"http-equiv="refresh""#) {
+            continue;
+        }
+
+        let mut url = String::new();
+        for module in modules {
+            url.push('/');
+            url.push_str(module);
+        }
+        url.push('/');
+        url.push_str(file_name);
+
+        let item_path = if kind == "mod" {
+            modules.join("::")
+        } else {
+            format!("{}::{name}", modules.join("::"))
+        };
+        items.extend(parse_page(
+            &content,
+            modules[0].as_str(),
+            kind,
+            &name,
+            &item_path,
+            &url,
+        ));
+    }
+    Ok(())
+}
+
+/// Kind and name of the item documented by `file_name`, if it is an item page.
+fn page_kind(file_name: &str, modules: &[String]) -> Option<(&'static str, String)> {
+    if file_name == "index.html" {
+        return Some(("mod", modules.last()?.clone()));
+    }
+    let stem = file_name.strip_suffix(".html")?;
+    let (kind, name) = stem.split_once('.')?;
+    let kind = ITEM_KINDS.iter().find(|k| **k == kind)?;
+    Some((kind, name.to_string()))
+}
+
+fn parse_page(
+    html: &str,
+    krate: &str,
+    kind: &str,
+    name: &str,
+    path: &str,
+    url: &str,
+) -> Vec<SearchItem> {
+    // Long item docs sit in a `top-doc` toggle; short ones are a bare
+    // docblock ahead of the first member section.
+    let top_doc = match html.find(r#"class="toggle top-doc""#) {
+        Some(start) => &html[start..],
+        None => {
+            let members_start = [r#"<h2 id=""#, r#"id="method."#, r#"id="tymethod."#]
+                .iter()
+                .filter_map(|marker| html.find(marker))
+                .min()
+                .unwrap_or(html.len());
+            &html[..members_start]
+        }
+    };
+    let text = find_docblock(top_doc).map(html_to_text).unwrap_or_default();
+
+    let mut items = vec![SearchItem {
+        name: name.to_string(),
+        path: path.to_string(),
+        kind: kind.to_string(),
+        krate: krate.to_string(),
+        url: url.to_string(),
+        summary: summarize(&text),
+        text,
+    }];
+
+    let own_api_end = IMPL_SECTIONS
+        .iter()
+        .filter_map(|marker| html.find(marker))
+        .min()
+        .unwrap_or(html.len());
+    let own_api = &html[..own_api_end];
+
+    let mut anchors: Vec<(usize, String)> = Vec::new();
+    for prefix in ["method.", "tymethod."] {
+        let marker = format!(r#"id="{prefix}"#);
+        for (pos, _) in own_api.match_indices(&marker) {
+            let rest = &own_api[pos + r#"id=""#.len()..];
+            if let Some(end) = rest.find('"') {
+                anchors.push((pos, rest[..end].to_string()));
+            }
+        }
+    }
+    anchors.sort();
+
+    let mut seen = HashSet::new();
+
+    for (index, (pos, anchor)) in anchors.iter().enumerate() {
+        let Some((_, method)) = anchor.split_once('.') else {
+            continue;
+        };
+        // rustdoc suffixes repeated ids (`method.fmt-1`).
+        if method.contains('-') || !seen.insert(method.to_string()) {
+            continue;
+        }
+        let end = anchors
+            .get(index + 1)
+            .map_or(own_api.len(), |(next, _)| *next);
+        let text = find_docblock(&own_api[*pos..end])
+            .map(html_to_text)
+            .unwrap_or_default();
+        items.push(SearchItem {
+            name: method.to_string(),
+            path: format!("{path}::{method}"),
+            kind: "method".to_string(),
+            krate: krate.to_string(),
+            url: format!("{url}#{anchor}"),
+            summary: summarize(&text),
+            text,
+        });
+    }
+
+    items
+}
+
+/// Inner HTML of the first `<div class="docblock">` in `html`.
+fn find_docblock(html: &str) -> Option<&str> {
+    const OPEN: &str = r#"<div class="docblock">"#;
+    let start = html.find(OPEN)? + OPEN.len();
+    let mut depth = 1;
+    let mut cursor = start;
+    while depth > 0 {
+        let rest = &html[cursor..];
+        let open = rest.find("<div");
+        let close = rest.find("</div>")?;
+        match open {
+            Some(open) if open < close => {
+                depth += 1;
+                cursor += open + 4;
+            }
+            _ => {
+                depth -= 1;
+                cursor += close + 6;
+            }
+        }
+    }
+    Some(&html[start..cursor - 6])
+}
+
+fn html_to_text(html: &str) -> String {
+    // Keep block boundaries as spaces so words from adjacent tags don't merge.
+    let spaced = html.replace("</p>", " </p>").replace("<br>", " ");
+    strip_html_tags(&spaced)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn summarize(text: &str) -> String {
+    if text.chars().count() <= SUMMARY_CHARS {
+        return text.to_string();
+    }
+    let mut summary: String = text.chars().take(SUMMARY_CHARS - 1).collect();
+    summary.push('…');
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRUCT_PAGE: &str = r#"<html><body><main>
+        <details class="toggle top-doc" open><summary></summary><div class="docblock"><p>A thread-safe cell which can be written to only <code>once</code>.</p><div class="example-wrap"><pre>let cell = OnceCell::new();</pre></div></div></details>
+        <h2 id="implementations">Implementations</h2>
+        <section id="method.new" class="method"><h4>pub const fn new()</h4></section><div class="docblock"><p>Creates a new empty cell.</p></div>
+        <section id="method.get_or_init" class="method"><h4>pub fn get_or_init()</h4></section><div class="docblock"><p>Gets the contents, initializing it with <code>f</code>.</p></div>
+        <h2 id="trait-implementations">Trait Implementations</h2>
+        <section id="method.fmt" class="method"><h4>fn fmt()</h4></section><div class="docblock"><p>Formats the value.</p></div>
+    </main></body></html>"#;
+
+    fn index() -> SearchIndex {
+        let mut items = parse_page(
+            STRUCT_PAGE,
+            "once_cell",
+            "struct",
+            "OnceCell",
+            "once_cell::sync::OnceCell",
+            "/once_cell/sync/struct.OnceCell.html",
+        );
+        items.push(SearchItem {
+            name: "HashMap".to_string(),
+            path: "hashbrown::HashMap".to_string(),
+            kind: "struct".to_string(),
+            krate: "hashbrown".to_string(),
+            url: "/hashbrown/struct.HashMap.html".to_string(),
+            summary: String::new(),
+            text: "A hash map implemented with quadratic probing.".to_string(),
+        });
+        SearchIndex::from_items(items)
+    }
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            text: text.to_string(),
+            limit: DEFAULT_LIMIT,
+            ..SearchQuery::default()
+        }
+    }
+
+    #[test]
+    fn parses_item_docs_and_own_methods() {
+        let index = index();
+        let paths: Vec<&str> = index.items.iter().map(|i| i.path.as_str()).collect();
+        assert!(paths.contains(&"once_cell::sync::OnceCell::get_or_init"));
+        assert!(!paths.contains(&"once_cell::sync::OnceCell::fmt"));
+
+        let cell = &index.items[0];
+        assert!(cell.text.starts_with("A thread-safe cell"));
+        assert!(cell.text.contains("OnceCell::new()"));
+        let method = &index.items[2];
+        assert_eq!(
+            method.url,
+            "/once_cell/sync/struct.OnceCell.html#method.get_or_init"
+        );
+        assert_eq!(method.summary, "Gets the contents, initializing it with f.");
+    }
+
+    #[test]
+    fn splits_identifiers_into_parts() {
+        assert_eq!(
+            tokenize("HashMap get_or_init"),
+            vec!["hashmap", "hash", "map", "get_or_init", "get", "or", "init"]
+        );
+    }
+
+    #[test]
+    fn ranks_name_matches_and_filters() {
+        let index = index();
+        let top = |text: &str| {
+            let query = query(text);
+            index.search(&query).results[0].path.clone()
+        };
+
+        assert_eq!(top("hashmap"), "hashbrown::HashMap");
+        assert_eq!(top("init"), "once_cell::sync::OnceCell::get_or_init");
+        // Prefix match on the last term while typing.
+        assert_eq!(top("once ce"), "once_cell::sync::OnceCell");
+
+        let mut methods = query("cell");
+        methods.kind = Some("method".to_string());
+        let results = index.search(&methods);
+        assert!(results.total > 0);
+        assert!(results.results.iter().all(|i| i.kind == "method"));
+
+        let mut other_crate = query("map");
+        other_crate.krate = Some("once_cell".to_string());
+        assert_eq!(index.search(&other_crate).total, 0);
+    }
+}
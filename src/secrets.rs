@@ -0,0 +1,102 @@
+//! Secret sources for config values.
+//!
+//! `!cmd "pass show openai"` runs a shell command, usually a password manager,
+//! and uses its output; `!file ~/.secrets/key` reads a file. Command output is
+//! cached for the life of the process so config reloads do not prompt again.
+//! Neither the output nor the file contents are ever printed: errors name the
+//! command or path only.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+use once_cell::sync::Lazy;
+
+static COMMAND_CACHE: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(Default::default);
+
+/// The output of `command` run by the platform shell, without the trailing
+/// newline. Stdin and stderr stay attached to the terminal so password
+/// managers can prompt.
+pub fn command_output(command: &str) -> Result<String, String> {
+    let cached = COMMAND_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(command)
+        .cloned();
+    if let Some(output) = cached {
+        return Ok(output);
+    }
+
+    let output = shell(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(|err| format!("unable to run command `{command}`: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("command `{command}` failed ({})", output.status));
+    }
+    let text = String::from_utf8(output.stdout)
+        .map_err(|_| format!("command `{command}` printed invalid UTF-8"))?;
+    let text = trim_line_end(&text).to_string();
+    COMMAND_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(command.to_string(), text.clone());
+    Ok(text)
+}
+
+/// The contents of the file at `path` (`~/` is the home directory), without
+/// the trailing newline.
+pub fn file_contents(path: &str) -> Result<String, String> {
+    let resolved = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    };
+    fs::read_to_string(&resolved)
+        .map(|text| trim_line_end(&text).to_string())
+        .map_err(|err| format!("unable to read {path}: {err}"))
+}
+
+fn trim_line_end(text: &str) -> &str {
+    text.trim_end_matches(['\r', '\n'])
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_commands_and_files_without_trailing_newlines() {
+        assert_eq!(command_output("echo sk-from-cmd").unwrap(), "sk-from-cmd");
+        let err = command_output("exit 3").unwrap_err();
+        assert!(err.starts_with("command `exit 3` failed"));
+
+        let path = std::env::temp_dir().join(format!("cdv-secret-{}", std::process::id()));
+        fs::write(&path, "sk-from-file\n").unwrap();
+        assert_eq!(
+            file_contents(path.to_str().unwrap()).unwrap(),
+            "sk-from-file"
+        );
+        fs::remove_file(&path).unwrap();
+        assert!(file_contents(path.to_str().unwrap()).is_err());
+    }
+}
@@ -1,115 +1,60 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::convert::Infallible;
-use std::io;
-use std::net::SocketAddr;
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+use hyper::body::{self, Bytes, HttpBody};
 use hyper::header;
 use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::{Body, Method, Request, Response, Server, StatusCode, Uri};
 use mime_guess::MimeGuess;
 use percent_encoding::percent_decode_str;
 use tokio::fs;
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::task;
 use tokio_util::io::ReaderStream;
 
-use crate::injector;
+use crate::assets;
+use crate::config::{self, Provider};
+use crate::enhance;
+use crate::events::{self, EventBus, ServerEvent};
+use crate::injector::{self, Assets};
+use crate::llm;
+use crate::metadata::{self, Metadata};
+use crate::mock;
+use crate::notes::{self, NoteStore, NotesError};
 use crate::overview;
+use crate::plugins::Plugins;
+use crate::providers;
+use crate::retrieval::{self, ChunkIndex};
+use crate::search::{self, SearchIndex, SearchQuery};
+use crate::settings;
+use crate::state::{self, StateError, StateStore};
+use crate::transcripts::{self, TranscriptError, TranscriptStore};
 
-const SERVICE_WORKER_JS: &str = r#"const CACHE_VERSION = 'v1';
-const HTML_CACHE = 'cdv-html-' + CACHE_VERSION;
-const STATIC_CACHE = 'cdv-static-' + CACHE_VERSION;
-const OFFLINE_HTML = '<!DOCTYPE html><html lang="zh-CN"><head><meta charset="utf-8"><title>离线模式</title><meta name="viewport" content="width=device-width, initial-scale=1"></head><body style="font-family: sans-serif; padding: 24px; background: #111; color: #f0f0f0;"><h1>📡 无法连接到 Cargo Doc Viewer</h1><p>当前处于离线状态，且没有缓存的页面可以展示。</p><p>重新连接后刷新页面即可恢复。</p></body></html>';
-
-self.addEventListener('install', function(event) {
-  self.skipWaiting();
-});
-
-self.addEventListener('activate', function(event) {
-  event.waitUntil(
-    caches.keys().then(function(keys) {
-      return Promise.all(keys.filter(function(key) {
-        return !key.startsWith('cdv-html-') && !key.startsWith('cdv-static-');
-      }).map(function(key) { return caches.delete(key); }));
-    }).then(function() {
-      return self.clients.claim();
-    })
-  );
-});
-
-function isHtmlRequest(request) {
-  var accept = request.headers.get('accept') || '';
-  return accept.indexOf('text/html') !== -1;
-}
-
-function shouldCacheStatic(pathname) {
-  return /\.(?:css|js|wasm|json|png|jpe?g|svg|gif|ico|woff2?|ttf)$/.test(pathname);
-}
+/// Upper bound for JSON request bodies posted to `/cdv-api/*`.
+const MAX_API_BODY_BYTES: usize = 2 * 1024 * 1024;
 
-self.addEventListener('fetch', function(event) {
-  var request = event.request;
-  if (request.method !== 'GET') return;
+/// Read size when streaming pages through the injector.
+const HTML_CHUNK_BYTES: usize = 64 * 1024;
 
-  var url = new URL(request.url);
-  if (url.origin !== location.origin) return;
+/// Interval between SSE comments that keep idle event streams open and let
+/// the server notice pages that have gone away.
+const EVENTS_KEEPALIVE: Duration = Duration::from_secs(20);
 
-  if (url.pathname === '/cdv-sw.js') {
-    return;
-  }
-
-  if (isHtmlRequest(request)) {
-    event.respondWith(
-      fetch(request).then(function(response) {
-        var copy = response.clone();
-        caches.open(HTML_CACHE).then(function(cache) {
-          cache.put(request, copy);
-        });
-        return response;
-      }).catch(function() {
-        return caches.open(HTML_CACHE).then(function(cache) {
-          return cache.match(request).then(function(cached) {
-            if (cached) return cached;
-            return new Response(OFFLINE_HTML, {
-              headers: {'Content-Type': 'text/html; charset=utf-8'}
-            });
-          });
-        });
-      })
-    );
-    return;
-  }
-
-  if (!shouldCacheStatic(url.pathname)) {
-    return;
-  }
-
-  event.respondWith(
-    caches.open(STATIC_CACHE).then(function(cache) {
-      return cache.match(request).then(function(cached) {
-        if (cached) {
-          fetch(request).then(function(response) {
-            if (response && response.ok) {
-              cache.put(request, response.clone());
-            }
-          }).catch(function(){});
-          return cached;
-        }
-        return fetch(request).then(function(response) {
-          if (response && response.ok) {
-            cache.put(request, response.clone());
-          }
-          return response;
-        }).catch(function() {
-          return new Response('', { status: 503, statusText: 'Offline' });
-        });
-      });
-    })
-  );
-});
-"#;
+/// Pause between lines of a streamed `mock` answer, so the panel renders it
+/// the way it would a real provider's.
+const MOCK_STREAM_DELAY: Duration = Duration::from_millis(40);
 
-pub async fn run(doc_dir: &Path, addr: SocketAddr) -> io::Result<()> {
-    let state = Arc::new(ServerState::new(doc_dir).await?);
+pub async fn run(doc_dir: &Path, addr: SocketAddr, events: EventBus) -> io::Result<()> {
+    let state = Arc::new(ServerState::new(doc_dir, addr, events).await?);
+    spawn_search_indexer(state.clone());
 
     println!(
         "Serving docs from {} at http://{}",
@@ -131,23 +76,75 @@ pub async fn run(doc_dir: &Path, addr: SocketAddr) -> io::Result<()> {
     Server::bind(&addr)
         .serve(make_service)
         .await
-        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+        .map_err(io::Error::other)
 }
 
 struct ServerState {
+    /// Where the server listens; requests must name it in `Host`.
+    addr: SocketAddr,
     doc_root: PathBuf,
     canonical_root: PathBuf,
+    chat: llm::ChatClient,
+    events: EventBus,
+    store: StateStore,
+    transcripts: TranscriptStore,
+    notes: NoteStore,
+    /// Built on the first search and dropped whenever the docs are rebuilt.
+    search: Mutex<Option<Arc<SearchIndex>>>,
+    /// Chat retrieval chunks, derived from the search index.
+    retrieval: Mutex<Option<Arc<ChunkIndex>>>,
 }
 
 impl ServerState {
-    async fn new(doc_dir: &Path) -> io::Result<Self> {
+    async fn new(doc_dir: &Path, addr: SocketAddr, events: EventBus) -> io::Result<Self> {
         let canonical_root = fs::canonicalize(doc_dir).await?;
         Ok(Self {
+            addr,
             doc_root: doc_dir.to_path_buf(),
             canonical_root,
+            chat: llm::ChatClient::new(),
+            events,
+            store: StateStore::new(StateStore::default_dir()),
+            transcripts: TranscriptStore::new(TranscriptStore::default_dir()),
+            notes: NoteStore::discover(),
+            search: Mutex::new(None),
+            retrieval: Mutex::new(None),
         })
     }
 
+    async fn search_index(&self) -> Result<Arc<SearchIndex>, ServerError> {
+        let mut slot = self.search.lock().await;
+        if let Some(index) = slot.as_ref() {
+            return Ok(index.clone());
+        }
+
+        let root = self.doc_root.clone();
+        let index = task::spawn_blocking(move || SearchIndex::build(&root))
+            .await
+            .map_err(ServerError::internal)?
+            .map_err(ServerError::from)?;
+        println!("Indexed {} items for search.", index.len());
+        let index = Arc::new(index);
+        *slot = Some(index.clone());
+        Ok(index)
+    }
+
+    async fn chunk_index(&self) -> Result<Arc<ChunkIndex>, ServerError> {
+        let mut slot = self.retrieval.lock().await;
+        if let Some(index) = slot.as_ref() {
+            return Ok(index.clone());
+        }
+
+        let search = self.search_index().await?;
+        let index = task::spawn_blocking(move || ChunkIndex::from_items(search.items()))
+            .await
+            .map_err(ServerError::internal)?;
+        println!("Indexed {} chunks for chat retrieval.", index.len());
+        let index = Arc::new(index);
+        *slot = Some(index.clone());
+        Ok(index)
+    }
+
     fn join(&self, uri_path: &str) -> Option<PathBuf> {
         let mut buf = self.doc_root.clone();
 
@@ -208,8 +205,34 @@ async fn handle_request(
 ) -> Result<Response<Body>, Infallible> {
     let method = req.method().clone();
 
+    // Every route serves or changes private data (state, transcripts, notes,
+    // the config inlined in pages), so a DNS-rebinding page must not reach
+    // any of them.
+    if !names_server(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "the Host header does not name this server",
+        ));
+    }
+
     let mut response = match method {
-        Method::GET | Method::HEAD => match dispatch(state, req.uri().path()).await {
+        Method::GET | Method::HEAD => match dispatch(state, req.uri()).await {
+            Ok(resp) => resp,
+            Err(err) => err.into_response(),
+        },
+        Method::POST => match dispatch_post(state, req).await {
+            Ok(resp) => resp,
+            Err(err) => err.into_response(),
+        },
+        Method::PUT => match dispatch_put(state, req).await {
+            Ok(resp) => resp,
+            Err(err) => err.into_response(),
+        },
+        Method::PATCH => match dispatch_patch(state, req).await {
+            Ok(resp) => resp,
+            Err(err) => err.into_response(),
+        },
+        Method::DELETE => match dispatch_delete(state, req).await {
             Ok(resp) => resp,
             Err(err) => err.into_response(),
         },
@@ -223,15 +246,643 @@ async fn handle_request(
     Ok(response)
 }
 
-async fn dispatch(state: Arc<ServerState>, path: &str) -> Result<Response<Body>, ServerError> {
+async fn dispatch(state: Arc<ServerState>, uri: &Uri) -> Result<Response<Body>, ServerError> {
+    let path = uri.path();
     match path {
         "/" | "/index.html" => serve_overview(state).await,
         "/cdv-crate-overview.html" => serve_overview(state).await,
+        "/cdv-deps.html" => serve_deps(state).await,
         "/cdv-sw.js" => serve_service_worker().await,
+        _ if path.starts_with(assets::ASSETS_PATH) => {
+            serve_asset(&path[assets::ASSETS_PATH.len()..]).await
+        }
+        settings::SETTINGS_PAGE_PATH => Ok(text_response(
+            StatusCode::OK,
+            settings::SETTINGS_HTML.to_string(),
+        )),
+        settings::CONFIG_API_PATH => serve_config().await,
+        events::EVENTS_PATH => serve_events(state).await,
+        search::SEARCH_PATH => serve_search(state, uri.query()).await,
+        retrieval::RETRIEVE_PATH => serve_retrieve(state, uri.query()).await,
+        _ if path.starts_with(state::STATE_PATH) => serve_state(state, path).await,
+        transcripts::TRANSCRIPTS_PATH => serve_transcripts(state, uri.query()).await,
+        _ if path.starts_with(transcripts::TRANSCRIPTS_PATH) => serve_transcript(state, path).await,
+        notes::NOTES_PATH => serve_notes(state, uri.query()).await,
         _ => serve_path(state, path).await,
     }
 }
 
+async fn dispatch_post(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    match req.uri().path() {
+        config::CHAT_PROXY_PATH => proxy_chat(state, req).await,
+        _ => Ok(method_not_allowed()),
+    }
+}
+
+async fn dispatch_put(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    match req.uri().path() {
+        settings::CONFIG_API_PATH => update_config(state, req).await,
+        path if path.starts_with(state::STATE_PATH) => update_state(state, req, true).await,
+        path if path.starts_with(transcripts::TRANSCRIPTS_PATH) => {
+            save_transcript(state, req).await
+        }
+        path if path.starts_with(notes::NOTES_PATH) => update_note(state, req, false).await,
+        _ => Ok(method_not_allowed()),
+    }
+}
+
+async fn dispatch_patch(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    match req.uri().path() {
+        path if path.starts_with(state::STATE_PATH) => update_state(state, req, false).await,
+        _ => Ok(method_not_allowed()),
+    }
+}
+
+async fn dispatch_delete(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    match req.uri().path() {
+        path if path.starts_with(notes::NOTES_PATH) => update_note(state, req, true).await,
+        _ => Ok(method_not_allowed()),
+    }
+}
+
+async fn serve_state(state: Arc<ServerState>, path: &str) -> Result<Response<Body>, ServerError> {
+    let namespace = path[state::STATE_PATH.len()..].to_string();
+    let values = task::spawn_blocking(move || state.store.get(&namespace))
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::from)?;
+    Ok(json_response(StatusCode::OK, &values))
+}
+
+/// `PUT` replaces a state namespace, `PATCH` merges keys into it.
+async fn update_state(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+    replace: bool,
+) -> Result<Response<Body>, ServerError> {
+    if !is_same_origin(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "state can only be changed by pages from this server",
+        ));
+    }
+    let namespace = req.uri().path()[state::STATE_PATH.len()..].to_string();
+    let body = read_body(req.into_body()).await?;
+    let values: state::Namespace =
+        serde_json::from_slice(&body).map_err(|err| ServerError::BadRequest(err.to_string()))?;
+    let stored = task::spawn_blocking(move || {
+        if replace {
+            state.store.put(&namespace, values)
+        } else {
+            state.store.patch(&namespace, values)
+        }
+    })
+    .await
+    .map_err(ServerError::internal)?
+    .map_err(ServerError::from)?;
+    Ok(json_response(StatusCode::OK, &stored))
+}
+
+async fn serve_transcripts(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+) -> Result<Response<Body>, ServerError> {
+    let params = query_params(query);
+    let listed = task::spawn_blocking(move || {
+        let krate = params.get("crate").map(String::as_str);
+        let page = params.get("page").map(String::as_str);
+        state.transcripts.list(
+            krate.filter(|v| !v.is_empty()),
+            page.filter(|v| !v.is_empty()),
+        )
+    })
+    .await
+    .map_err(ServerError::internal)?
+    .map_err(ServerError::from)?;
+    Ok(json_response(
+        StatusCode::OK,
+        &serde_json::json!({ "transcripts": listed }),
+    ))
+}
+
+/// `<id>` as JSON, `<id>.md` as a Markdown download.
+async fn serve_transcript(
+    state: Arc<ServerState>,
+    path: &str,
+) -> Result<Response<Body>, ServerError> {
+    let Some(name) = transcript_id(path) else {
+        return Err(ServerError::NotFound);
+    };
+    let (id, markdown) = match name.strip_suffix(".md") {
+        Some(id) => (id.to_string(), true),
+        None => (name.to_string(), false),
+    };
+    let transcript = task::spawn_blocking(move || state.transcripts.get(&id))
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::from)?;
+    if !markdown {
+        return Ok(json_response(StatusCode::OK, &transcript));
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/markdown; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.md\"", transcript.id),
+        )
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(transcript.to_markdown()))
+        .map_err(|err| ServerError::Internal(err.to_string()))
+}
+
+async fn save_transcript(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    if !is_same_origin(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "transcripts can only be saved by pages from this server",
+        ));
+    }
+    let Some(id) = transcript_id(req.uri().path()).map(str::to_string) else {
+        return Ok(method_not_allowed());
+    };
+    let body = read_body(req.into_body()).await?;
+    let transcript: transcripts::Transcript =
+        serde_json::from_slice(&body).map_err(|err| ServerError::BadRequest(err.to_string()))?;
+    let saved = task::spawn_blocking(move || state.transcripts.save(&id, transcript))
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::from)?;
+    Ok(json_response(StatusCode::OK, &saved))
+}
+
+fn transcript_id(path: &str) -> Option<&str> {
+    path.strip_prefix(transcripts::TRANSCRIPTS_PATH)?
+        .strip_prefix('/')
+        .filter(|id| !id.is_empty())
+}
+
+/// All notes, or those of `?page=`, with the ids of the orphaned ones.
+async fn serve_notes(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+) -> Result<Response<Body>, ServerError> {
+    let params = query_params(query);
+    let body = task::spawn_blocking(move || {
+        let page = params.get("page").map(|page| page.trim_start_matches('/'));
+        let keep = |note: &notes::Note| page.is_none_or(|page| note.page == page);
+        let all: Vec<notes::Note> = state.notes.all().into_iter().filter(keep).collect();
+        let orphaned: Vec<String> = state
+            .notes
+            .orphaned(&state.doc_root)
+            .into_iter()
+            .filter(keep)
+            .map(|note| note.id)
+            .collect();
+        serde_json::json!({ "notes": all, "orphaned": orphaned })
+    })
+    .await
+    .map_err(ServerError::internal)?;
+    Ok(json_response(StatusCode::OK, &body))
+}
+
+/// `PUT` adds or replaces a note, `DELETE` removes it.
+async fn update_note(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+    delete: bool,
+) -> Result<Response<Body>, ServerError> {
+    if !is_same_origin(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "notes can only be changed by pages from this server",
+        ));
+    }
+    let Some(id) = req
+        .uri()
+        .path()
+        .strip_prefix(notes::NOTES_PATH)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(str::to_string)
+    else {
+        return Ok(method_not_allowed());
+    };
+    if delete {
+        let removed = task::spawn_blocking(move || state.notes.delete(&id))
+            .await
+            .map_err(ServerError::internal)?
+            .map_err(ServerError::from)?;
+        return if removed {
+            Ok(json_response(
+                StatusCode::OK,
+                &serde_json::json!({ "deleted": true }),
+            ))
+        } else {
+            Err(ServerError::NotFound)
+        };
+    }
+    let body = read_body(req.into_body()).await?;
+    let note: notes::Note =
+        serde_json::from_slice(&body).map_err(|err| ServerError::BadRequest(err.to_string()))?;
+    let saved = task::spawn_blocking(move || state.notes.save(&id, note))
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::from)?;
+    Ok(json_response(StatusCode::OK, &saved))
+}
+
+async fn serve_config() -> Result<Response<Body>, ServerError> {
+    let snapshot = task::spawn_blocking(settings::snapshot)
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::Internal)?;
+    Ok(json_response(StatusCode::OK, &snapshot))
+}
+
+/// Writes settings from `/cdv-settings.html`. Only same-origin requests may
+/// change files; browsers preflight cross-origin `PUT`s, and this also stops
+/// pages that reach the server under another host name.
+async fn update_config(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    if !is_same_origin(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "config can only be changed from the settings page",
+        ));
+    }
+    let body = read_body(req.into_body()).await?;
+    let submitted: settings::Settings =
+        serde_json::from_slice(&body).map_err(|err| ServerError::BadRequest(err.to_string()))?;
+
+    let applied = task::spawn_blocking(move || {
+        let written = settings::apply(submitted)?;
+        let snapshot = settings::snapshot().map_err(settings::ApplyError::Write)?;
+        Ok((written, snapshot))
+    })
+    .await
+    .map_err(ServerError::internal)?;
+    match applied {
+        Ok((written, snapshot)) => {
+            if !written.is_empty() {
+                println!("Settings saved to {}.", display_paths(&written));
+                state.events.publish(ServerEvent::ConfigUpdated);
+            }
+            let mut body = serde_json::to_value(&snapshot)
+                .map_err(|err| ServerError::Internal(err.to_string()))?;
+            body["written"] = serde_json::json!(written);
+            Ok(json_response(StatusCode::OK, &body))
+        }
+        Err(settings::ApplyError::Invalid(problems)) => Ok(json_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            &serde_json::json!({
+                "error": { "message": "Some settings are invalid", "problems": problems }
+            }),
+        )),
+        Err(settings::ApplyError::Write(message)) => Err(ServerError::Internal(message)),
+    }
+}
+
+/// Whether `req` comes from a page of this server, or from no page at all:
+/// it must pass [`names_server`], and an `Origin` must match its host.
+fn is_same_origin(req: &Request<Body>, addr: SocketAddr) -> bool {
+    if !names_server(req, addr) {
+        return false;
+    }
+    let Some(host) = host_header(req) else {
+        return false;
+    };
+    let Some(origin) = req.headers().get(header::ORIGIN) else {
+        return true;
+    };
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, host)| host);
+    origin_host == Some(host)
+}
+
+/// Whether the `Host` of `req` names the server, so a DNS-rebinding page
+/// whose own name now resolves to it is turned away.
+fn names_server(req: &Request<Body>, addr: SocketAddr) -> bool {
+    host_header(req).is_some_and(|host| is_server_host(host, addr))
+}
+
+fn host_header(req: &Request<Body>) -> Option<&str> {
+    req.headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+}
+
+/// `host` is `localhost` or an IP address of the server, on its port. Names
+/// other than `localhost` are refused: whoever controls one can point it here.
+fn is_server_host(host: &str, addr: SocketAddr) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => (name, port.parse().ok()),
+        _ => (host, Some(80)),
+    };
+    if port != Some(addr.port()) {
+        return false;
+    }
+    if name.eq_ignore_ascii_case("localhost") {
+        return addr.ip().is_loopback() || addr.ip().is_unspecified();
+    }
+    let ip = name.trim_start_matches('[').trim_end_matches(']');
+    match ip.parse::<IpAddr>() {
+        Ok(ip) => {
+            ip == addr.ip()
+                || addr.ip().is_unspecified()
+                || (ip.is_loopback() && addr.ip().is_loopback())
+        }
+        Err(_) => false,
+    }
+}
+
+fn is_json(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn proxy_chat(
+    state: Arc<ServerState>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ServerError> {
+    // Chat spends the key held by the server; a plain-text form post from
+    // another site would reach here without a CORS preflight.
+    if !is_same_origin(&req, state.addr) {
+        return Ok(json_error(
+            StatusCode::FORBIDDEN,
+            "chat is only available to pages from this server",
+        ));
+    }
+    if !is_json(&req) {
+        return Ok(json_error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "chat requests must be application/json",
+        ));
+    }
+    let fallback_auth = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = read_body(req.into_body()).await?;
+    let request: llm::ChatRequest =
+        serde_json::from_slice(&body).map_err(|err| ServerError::BadRequest(err.to_string()))?;
+
+    let config = config::app_config();
+    let api = config.profile(request.profile.as_deref()).ok_or_else(|| {
+        ServerError::BadRequest(format!(
+            "unknown chat profile: {}",
+            request.profile.as_deref().unwrap_or_default()
+        ))
+    })?;
+    if api.provider == Provider::Mock {
+        return mock_chat(&request);
+    }
+    let upstream = state
+        .chat
+        .forward(api, &request, fallback_auth.as_deref())
+        .await
+        .map_err(ServerError::Chat)?;
+
+    let content_type = upstream
+        .content_type
+        .unwrap_or_else(|| header::HeaderValue::from_static("application/json"));
+    Response::builder()
+        .status(upstream.status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-store")
+        .header("X-Accel-Buffering", "no")
+        .body(upstream.body)
+        .map_err(|err| ServerError::Internal(err.to_string()))
+}
+
+/// Answers locally for the `mock` provider, in the same formats the proxy
+/// relays from real providers.
+fn mock_chat(request: &llm::ChatRequest) -> Result<Response<Body>, ServerError> {
+    if request.messages.is_empty() {
+        return Err(ServerError::BadRequest("no messages supplied".to_string()));
+    }
+    let answer = mock::answer(request);
+    let (content_type, body) = if request.stream {
+        ("text/event-stream", mock_stream(answer))
+    } else {
+        (
+            "application/json",
+            Body::from(providers::completion(&answer, Some("stop"))),
+        )
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(body)
+        .map_err(|err| ServerError::Internal(err.to_string()))
+}
+
+fn mock_stream(answer: String) -> Body {
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        for line in answer.split_inclusive('\n') {
+            let frame = providers::delta_frame(line);
+            if sender.send_data(Bytes::from(frame)).await.is_err() {
+                return;
+            }
+            tokio::time::sleep(MOCK_STREAM_DELAY).await;
+        }
+        let _ = sender
+            .send_data(Bytes::from_static(b"data: [DONE]\n\n"))
+            .await;
+    });
+    body
+}
+
+async fn read_body(mut body: Body) -> Result<Vec<u8>, ServerError> {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| ServerError::BadRequest(err.to_string()))?;
+        if buffer.len() + chunk.len() > MAX_API_BODY_BYTES {
+            return Err(ServerError::PayloadTooLarge);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(buffer)
+}
+
+async fn serve_events(state: Arc<ServerState>) -> Result<Response<Body>, ServerError> {
+    let mut receiver = state.events.subscribe();
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        if sender
+            .send_data(Bytes::from_static(b"retry: 2000\n\n"))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let mut keepalive = tokio::time::interval(EVENTS_KEEPALIVE);
+        keepalive.tick().await;
+        loop {
+            let frame = tokio::select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => event.to_sse(),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+            };
+            if sender.send_data(Bytes::from(frame)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-store")
+        .header("X-Accel-Buffering", "no")
+        .body(body)
+        .map_err(|err| ServerError::Internal(err.to_string()))
+}
+
+async fn serve_search(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+) -> Result<Response<Body>, ServerError> {
+    let mut params = query_params(query);
+    let text = params
+        .remove("q")
+        .ok_or_else(|| ServerError::BadRequest("missing `q` parameter".to_string()))?;
+    let limit = match params.get("limit") {
+        Some(raw) => raw
+            .parse::<usize>()
+            .map_err(|_| ServerError::BadRequest(format!("invalid `limit`: {raw}")))?
+            .min(search::MAX_LIMIT),
+        None => search::DEFAULT_LIMIT,
+    };
+    let query = SearchQuery {
+        text,
+        kind: params.remove("kind").filter(|v| !v.is_empty()),
+        krate: params.remove("crate").filter(|v| !v.is_empty()),
+        limit,
+    };
+
+    let index = state.search_index().await?;
+    let results = index.search(&query);
+    Ok(json_response(StatusCode::OK, &results))
+}
+
+async fn serve_retrieve(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+) -> Result<Response<Body>, ServerError> {
+    let params = query_params(query);
+    let question = params
+        .get("q")
+        .ok_or_else(|| ServerError::BadRequest("missing `q` parameter".to_string()))?;
+    let k = match params.get("k") {
+        Some(raw) => raw
+            .parse::<usize>()
+            .map_err(|_| ServerError::BadRequest(format!("invalid `k`: {raw}")))?
+            .min(retrieval::MAX_TOP_K),
+        None => retrieval::DEFAULT_TOP_K,
+    };
+    let current = params
+        .get("current")
+        .map(String::as_str)
+        .filter(|v| !v.is_empty());
+
+    let index = state.chunk_index().await?;
+    let chunks = index.retrieve(question, k, current);
+    Ok(json_response(
+        StatusCode::OK,
+        &serde_json::json!({ "query": question, "chunks": chunks }),
+    ))
+}
+
+/// Builds the search and retrieval indexes in the background so the first
+/// query is quick, and drops them after each rebuild so the next query sees
+/// the new docs.
+fn spawn_search_indexer(state: Arc<ServerState>) {
+    let mut receiver = state.events.subscribe();
+    tokio::spawn(async move {
+        report_orphaned_notes(&state).await;
+        if let Err(err) = state.chunk_index().await {
+            eprintln!("Failed to build search index: {err:?}");
+        }
+        loop {
+            match receiver.recv().await {
+                Ok(events::ServerEvent::Reload) | Err(RecvError::Lagged(_)) => {
+                    *state.search.lock().await = None;
+                    *state.retrieval.lock().await = None;
+                    report_orphaned_notes(&state).await;
+                }
+                Ok(_) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Lists team notes whose anchors the current docs no longer have.
+async fn report_orphaned_notes(state: &Arc<ServerState>) {
+    let state = state.clone();
+    if let Err(err) =
+        task::spawn_blocking(move || state.notes.report_orphaned(&state.doc_root)).await
+    {
+        eprintln!("Failed to check team notes: {err}");
+    }
+}
+
+/// Decodes `a=1&b=two+words` into a map; later duplicates win.
+fn query_params(query: Option<&str>) -> HashMap<String, String> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |raw: &str| {
+                percent_decode_str(&raw.replace('+', " "))
+                    .decode_utf8_lossy()
+                    .into_owned()
+            };
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
 async fn serve_overview(state: Arc<ServerState>) -> Result<Response<Body>, ServerError> {
     let root = state.doc_root.clone();
     let crates = task::spawn_blocking(move || overview::scan_crates(&root))
@@ -243,6 +894,17 @@ async fn serve_overview(state: Arc<ServerState>) -> Result<Response<Body>, Serve
     Ok(text_response(StatusCode::OK, html))
 }
 
+async fn serve_deps(state: Arc<ServerState>) -> Result<Response<Body>, ServerError> {
+    let root = state.doc_root.clone();
+    let crates = task::spawn_blocking(move || overview::scan_crates(&root))
+        .await
+        .map_err(ServerError::internal)?
+        .map_err(ServerError::from)?;
+    let html = overview::generate_deps_html(metadata::get().map(Metadata::graph), &crates);
+
+    Ok(text_response(StatusCode::OK, html))
+}
+
 async fn serve_path(state: Arc<ServerState>, path: &str) -> Result<Response<Body>, ServerError> {
     let resolved = state
         .locate(path)
@@ -256,23 +918,97 @@ async fn serve_path(state: Arc<ServerState>, path: &str) -> Result<Response<Body
         .unwrap_or_default();
 
     if extension.eq_ignore_ascii_case("html") {
-        serve_html(&resolved).await
+        serve_html(state, &resolved).await
     } else {
         serve_file(&resolved).await
     }
 }
 
-async fn serve_html(path: &Path) -> Result<Response<Body>, ServerError> {
-    let content = fs::read_to_string(path).await.map_err(ServerError::from)?;
-    let modified = injector::inject(&content).unwrap_or(content);
+async fn serve_html(state: Arc<ServerState>, path: &Path) -> Result<Response<Body>, ServerError> {
+    let file = fs::File::open(path)
+        .await
+        .map_err(ServerError::from)?
+        .into_std()
+        .await;
+    let page = enhance::page_path(&state.doc_root, path);
+    let (sender, body) = Body::channel();
+    let runtime = Handle::current();
+    task::spawn_blocking(move || {
+        let bootstrap = format!(
+            "{}\n{}\n{}",
+            config::server_bootstrap_assignment(),
+            state.store.script_assignment(),
+            state.notes.index().script_assignment(&page)
+        );
+        // Only the bootstrap differs between pages; the UI itself is linked
+        // so the browser fetches it once. Plugins are read for every page so
+        // edits show up on reload.
+        let plugins = Plugins::load();
+        let assets = Assets::Linked {
+            root: "/",
+            plugins: &plugins,
+        };
+        if let Err(err) = stream_html(file, &bootstrap, &assets, sender, &runtime) {
+            eprintln!("Warning: Failed to serve {page}: {err}");
+        }
+    });
 
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
-        .body(Body::from(modified))
+        .body(body)
         .expect("valid HTML response"))
 }
 
+/// Reads `file` in chunks on a blocking thread and hands each rewritten piece
+/// to the response body as soon as it is ready. Stops quietly when the client
+/// goes away; on errors the body is aborted so the page is not taken as
+/// complete.
+fn stream_html(
+    mut file: std::fs::File,
+    bootstrap: &str,
+    assets: &Assets,
+    sender: body::Sender,
+    runtime: &Handle,
+) -> io::Result<()> {
+    let sender = RefCell::new(sender);
+    let closed = Cell::new(false);
+    let result = {
+        let mut rewriter = injector::rewriter(bootstrap, assets, |chunk: &[u8]| {
+            if closed.get() || chunk.is_empty() {
+                return;
+            }
+            let data = Bytes::copy_from_slice(chunk);
+            if runtime
+                .block_on(sender.borrow_mut().send_data(data))
+                .is_err()
+            {
+                closed.set(true);
+            }
+        });
+        let mut buffer = vec![0; HTML_CHUNK_BYTES];
+        loop {
+            if closed.get() {
+                break Ok(());
+            }
+            match file.read(&mut buffer) {
+                Ok(0) => break rewriter.end().map_err(io::Error::other),
+                Ok(read) => {
+                    if let Err(err) = rewriter.write(&buffer[..read]) {
+                        break Err(io::Error::other(err));
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        }
+    };
+    if result.is_err() {
+        sender.into_inner().abort();
+    }
+    result
+}
+
 async fn serve_file(path: &Path) -> Result<Response<Body>, ServerError> {
     let file = fs::File::open(path).await.map_err(ServerError::from)?;
     let stream = ReaderStream::new(file);
@@ -288,6 +1024,27 @@ async fn serve_file(path: &Path) -> Result<Response<Body>, ServerError> {
     Ok(response)
 }
 
+async fn serve_asset(name: &str) -> Result<Response<Body>, ServerError> {
+    let (content, mime) = match assets::linked(name) {
+        Some((content, mime)) => (Body::from(content), mime),
+        None if name.starts_with("plugins.") => {
+            let name = name.to_string();
+            let (content, mime) = task::spawn_blocking(move || Plugins::load().linked(&name))
+                .await
+                .map_err(|err| ServerError::Internal(err.to_string()))?
+                .ok_or(ServerError::NotFound)?;
+            (Body::from(content), mime)
+        }
+        None => return Err(ServerError::NotFound),
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .body(content)
+        .map_err(|err| ServerError::Internal(err.to_string()))
+}
+
 async fn serve_service_worker() -> Result<Response<Body>, ServerError> {
     Response::builder()
         .status(StatusCode::OK)
@@ -297,13 +1054,16 @@ async fn serve_service_worker() -> Result<Response<Body>, ServerError> {
         )
         .header(header::CACHE_CONTROL, "no-store")
         .header("Service-Worker-Allowed", "/")
-        .body(Body::from(SERVICE_WORKER_JS))
+        .body(Body::from(assets::CDV_SW_JS))
         .map_err(|err| ServerError::Internal(err.to_string()))
 }
 
 #[derive(Debug)]
 enum ServerError {
     NotFound,
+    BadRequest(String),
+    PayloadTooLarge,
+    Chat(llm::ChatError),
     Internal(String),
     Io(io::Error),
 }
@@ -316,6 +1076,18 @@ impl ServerError {
     fn into_response(self) -> Response<Body> {
         match self {
             ServerError::NotFound => simple_text(StatusCode::NOT_FOUND, "Not Found"),
+            ServerError::BadRequest(message) => json_error(StatusCode::BAD_REQUEST, &message),
+            ServerError::PayloadTooLarge => {
+                json_error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+            }
+            ServerError::Chat(err) => {
+                let status = match err {
+                    llm::ChatError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+                    llm::ChatError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+                    llm::ChatError::Upstream(_) => StatusCode::BAD_GATEWAY,
+                };
+                json_error(status, &err.to_string())
+            }
             ServerError::Io(err) => match err.kind() {
                 io::ErrorKind::NotFound => simple_text(StatusCode::NOT_FOUND, "Not Found"),
                 io::ErrorKind::PermissionDenied => {
@@ -330,6 +1102,35 @@ impl ServerError {
     }
 }
 
+impl From<StateError> for ServerError {
+    fn from(value: StateError) -> Self {
+        match value {
+            StateError::InvalidNamespace => Self::BadRequest(value.to_string()),
+            StateError::TooLarge => Self::PayloadTooLarge,
+            StateError::Io(err) => Self::Io(err),
+        }
+    }
+}
+
+impl From<NotesError> for ServerError {
+    fn from(value: NotesError) -> Self {
+        match value {
+            NotesError::InvalidId | NotesError::Invalid(_) => Self::BadRequest(value.to_string()),
+            NotesError::Io(err) => Self::Io(err),
+        }
+    }
+}
+
+impl From<TranscriptError> for ServerError {
+    fn from(value: TranscriptError) -> Self {
+        match value {
+            TranscriptError::InvalidId => Self::BadRequest(value.to_string()),
+            TranscriptError::TooLarge => Self::PayloadTooLarge,
+            TranscriptError::Io(err) => Self::Io(err),
+        }
+    }
+}
+
 impl From<io::Error> for ServerError {
     fn from(value: io::Error) -> Self {
         Self::Io(value)
@@ -353,6 +1154,129 @@ fn simple_text(status: StatusCode, body: impl Into<String>) -> Response<Body> {
         .expect("valid simple response")
 }
 
+/// Error body shaped like the OpenAI error envelope so the chat panel can
+/// surface `error.message` the same way for proxy and upstream failures.
+fn json_error(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "error": { "message": message } });
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("valid JSON error response")
+}
+
+fn json_response(status: StatusCode, value: &impl serde::Serialize) -> Response<Body> {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(body))
+            .expect("valid JSON response"),
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, &err.to_string()),
+    }
+}
+
 fn method_not_allowed() -> Response<Body> {
     simple_text(StatusCode::METHOD_NOT_ALLOWED, "Method Not Allowed")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutating_requests_must_name_this_server() {
+        let addr: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+        let request = |host: &str, origin: Option<&str>| {
+            let mut builder = Request::post(config::CHAT_PROXY_PATH).header(header::HOST, host);
+            if let Some(origin) = origin {
+                builder = builder.header(header::ORIGIN, origin);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        assert!(is_same_origin(&request("127.0.0.1:7878", None), addr));
+        assert!(is_same_origin(
+            &request("localhost:7878", Some("http://localhost:7878")),
+            addr
+        ));
+        assert!(!is_same_origin(
+            &request("127.0.0.1:7878", Some("https://evil.example")),
+            addr
+        ));
+        // DNS rebinding: the attacker's name resolves to the server and the
+        // page's origin matches its own `Host`.
+        assert!(!is_same_origin(
+            &request("evil.example:7878", Some("http://evil.example:7878")),
+            addr
+        ));
+        assert!(!is_same_origin(&request("127.0.0.1:8080", None), addr));
+
+        let any: SocketAddr = "0.0.0.0:7878".parse().unwrap();
+        assert!(is_server_host("192.168.1.20:7878", any));
+        assert!(is_server_host("[::1]:7878", "[::1]:7878".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn reads_must_name_this_server_too() {
+        let addr: SocketAddr = "127.0.0.1:7878".parse().unwrap();
+        let state = ServerState::new(&std::env::temp_dir(), addr, EventBus::new())
+            .await
+            .unwrap();
+        let state = Arc::new(state);
+        let get = |path: &str, host: &str| {
+            Request::get(path)
+                .header(header::HOST, host)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for path in [transcripts::TRANSCRIPTS_PATH, "/"] {
+            let response = handle_request(state.clone(), get(path, "evil.example:7878"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{path}");
+        }
+        let response = handle_request(state, get("/cdv-missing.html", "localhost:7878"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn mock_provider_streams_its_answer_offline() {
+        let request: llm::ChatRequest = serde_json::from_str(
+            r#"{
+                "stream": true,
+                "messages": [
+                    {"role": "system", "content": "Page summary:\nA Client holds a connection pool. It is cheap to clone."},
+                    {"role": "user", "content": "What does the pool do?"}
+                ],
+                "metadata": {"doc_path": "/hyper/struct.Client.html", "crate": "hyper", "module": "hyper"}
+            }"#,
+        )
+        .unwrap();
+        let response = mock_chat(&request).unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.ends_with("data: [DONE]\n\n"));
+        let streamed: String = body
+            .split("\n\n")
+            .filter_map(|frame| frame.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .filter_map(|event| {
+                event["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect();
+        assert_eq!(streamed, mock::answer(&request));
+        assert!(streamed.contains("A Client holds a connection pool. [1]"));
+        assert!(streamed.contains("[1] `hyper` (/hyper/struct.Client.html)"));
+    }
+}
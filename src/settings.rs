@@ -0,0 +1,631 @@
+//! The settings page served at `/cdv-settings.html`.
+//!
+//! The page edits the prompt, context and UI sections through
+//! `GET`/`PUT /cdv-api/config`. Submitted values are checked with the rules
+//! `AppConfig::normalize` applies, and each changed value is written to the
+//! YAML layer that already sets it, so project overrides stay in the project
+//! file and personal settings in the global one.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ConfigLayer, ContextConfig, FieldProblem, PromptConfig, UiConfig};
+
+pub const SETTINGS_PAGE_PATH: &str = "/cdv-settings.html";
+pub const CONFIG_API_PATH: &str = "/cdv-api/config";
+
+/// The sections the settings page edits. API endpoints and credentials stay
+/// in the files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub prompts: PromptConfig,
+    pub context: ContextConfig,
+    pub ui: UiConfig,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub settings: Settings,
+    /// Layer label setting each value, by dotted path; missing paths come
+    /// from the defaults.
+    pub sources: BTreeMap<String, &'static str>,
+    pub layers: Vec<ConfigLayer>,
+}
+
+#[derive(Debug)]
+pub enum ApplyError {
+    Invalid(Vec<FieldProblem>),
+    Write(String),
+}
+
+/// The editable sections as the layers set them, placeholders unresolved.
+pub fn snapshot() -> Result<Snapshot, String> {
+    let merged = config::merged_config();
+    let sources = config::value_sources()?
+        .into_iter()
+        .filter(|(path, _)| {
+            ["prompts.", "context.", "ui."]
+                .iter()
+                .any(|section| path.starts_with(section))
+        })
+        .collect();
+    Ok(Snapshot {
+        settings: Settings {
+            prompts: merged.prompts,
+            context: merged.context,
+            ui: merged.ui,
+        },
+        sources,
+        layers: config::config_layers(),
+    })
+}
+
+/// Validates `settings` and writes the values that differ from the current
+/// config, returning the files written.
+pub fn apply(settings: Settings) -> Result<Vec<PathBuf>, ApplyError> {
+    let current = config::merged_config();
+    let mut updated = current.clone();
+    updated.prompts = settings.prompts;
+    updated.context = settings.context;
+    updated.ui = settings.ui;
+
+    let problems = config::check_values(&updated);
+    if !problems.is_empty() {
+        return Err(ApplyError::Invalid(problems));
+    }
+
+    let mut changes = Vec::new();
+    for section in ["prompts", "context", "ui"] {
+        let before = section_value(&current, section)?;
+        let after = section_value(&updated, section)?;
+        diff(&before, after, &mut vec![section.to_string()], &mut changes);
+    }
+    if changes.is_empty() {
+        return Ok(Vec::new());
+    }
+    config::write_values(changes).map_err(ApplyError::Write)
+}
+
+fn section_value(
+    config: &config::AppConfig,
+    section: &str,
+) -> Result<serde_yaml::Value, ApplyError> {
+    let value = match section {
+        "prompts" => serde_yaml::to_value(&config.prompts),
+        "context" => serde_yaml::to_value(&config.context),
+        _ => serde_yaml::to_value(&config.ui),
+    };
+    value.map_err(|err| ApplyError::Write(err.to_string()))
+}
+
+/// Collects the values in `after` that differ from `before`. Mappings are
+/// compared key by key; anything else, including lists, is one value.
+fn diff(
+    before: &serde_yaml::Value,
+    after: serde_yaml::Value,
+    path: &mut Vec<String>,
+    changes: &mut Vec<(Vec<String>, serde_yaml::Value)>,
+) {
+    match (before, after) {
+        (serde_yaml::Value::Mapping(before), serde_yaml::Value::Mapping(after)) => {
+            for (key, value) in after {
+                let Some(name) = key.as_str() else { continue };
+                path.push(name.to_string());
+                match before.get(&key) {
+                    Some(old) => diff(old, value, path, changes),
+                    None => changes.push((path.clone(), value)),
+                }
+                path.pop();
+            }
+        }
+        (before, after) if *before != after => changes.push((path.clone(), after)),
+        _ => {}
+    }
+}
+
+pub const SETTINGS_HTML: &str = r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="utf-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>设置 - Cargo Doc Viewer</title>
+    <style>
+        :root {
+            --cdv-fg: #e6e6e6;
+            --cdv-accent: #6aa6ff;
+            --cdv-border: rgba(255,255,255,0.12);
+            --cdv-error: #ff8a80;
+        }
+
+        * {
+            margin: 0;
+            padding: 0;
+            box-sizing: border-box;
+        }
+
+        body {
+            font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, "Helvetica Neue", sans-serif;
+            background: linear-gradient(135deg, #1a1d29 0%, #2a2d3a 100%);
+            color: var(--cdv-fg);
+            min-height: 100vh;
+            padding: 2rem;
+        }
+
+        .header {
+            text-align: center;
+            margin-bottom: 2rem;
+        }
+
+        .header h1 {
+            font-size: 2.5rem;
+            font-weight: 600;
+            color: var(--cdv-accent);
+            margin-bottom: 0.5rem;
+        }
+
+        .header p {
+            font-size: 1rem;
+            opacity: 0.8;
+        }
+
+        .header a {
+            color: var(--cdv-accent);
+            text-decoration: none;
+        }
+
+        form {
+            max-width: 860px;
+            margin: 0 auto;
+        }
+
+        fieldset {
+            border: 1px solid var(--cdv-border);
+            border-radius: 12px;
+            background: rgba(0,0,0,0.2);
+            padding: 1.25rem 1.5rem;
+            margin-bottom: 1.5rem;
+        }
+
+        legend {
+            padding: 0 0.5rem;
+            font-size: 1.1rem;
+            color: var(--cdv-accent);
+        }
+
+        .field {
+            display: grid;
+            grid-template-columns: 14rem 1fr;
+            gap: 0.25rem 1rem;
+            align-items: start;
+            margin: 0.9rem 0;
+        }
+
+        .field > span {
+            padding-top: 0.35rem;
+            font-size: 0.9rem;
+        }
+
+        .field code,
+        .source {
+            display: block;
+            font-size: 0.75rem;
+            opacity: 0.55;
+        }
+
+        .source.layer {
+            color: var(--cdv-accent);
+            opacity: 0.85;
+        }
+
+        input[type="text"],
+        input[type="number"],
+        textarea {
+            width: 100%;
+            padding: 0.45rem 0.6rem;
+            border-radius: 6px;
+            border: 1px solid var(--cdv-border);
+            background: rgba(255,255,255,0.06);
+            color: var(--cdv-fg);
+            font: inherit;
+            font-size: 0.9rem;
+        }
+
+        textarea {
+            min-height: 7rem;
+            resize: vertical;
+            font-family: ui-monospace, SFMono-Regular, Menlo, monospace;
+        }
+
+        input[type="checkbox"] {
+            margin-top: 0.5rem;
+        }
+
+        .invalid input,
+        .invalid textarea {
+            border-color: var(--cdv-error);
+        }
+
+        .error {
+            grid-column: 2;
+            color: var(--cdv-error);
+            font-size: 0.8rem;
+        }
+
+        .patterns .row {
+            display: grid;
+            grid-template-columns: 1fr 12rem auto;
+            gap: 0.5rem;
+            margin-bottom: 0.5rem;
+        }
+
+        button {
+            height: 2.1rem;
+            padding: 0 1rem;
+            border-radius: 6px;
+            border: 1px solid rgba(106,166,255,0.4);
+            background: rgba(106,166,255,0.16);
+            color: #e6efff;
+            cursor: pointer;
+            font-size: 0.9rem;
+        }
+
+        button.primary {
+            background: var(--cdv-accent);
+            color: #0d1a2f;
+            font-weight: 600;
+        }
+
+        .actions {
+            display: flex;
+            align-items: center;
+            gap: 1rem;
+            max-width: 860px;
+            margin: 0 auto 2rem;
+        }
+
+        #status {
+            font-size: 0.9rem;
+            opacity: 0.85;
+        }
+
+        #status.failed {
+            color: var(--cdv-error);
+        }
+
+        #layers,
+        #overrides {
+            font-size: 0.85rem;
+            line-height: 1.8;
+        }
+
+        #overrides li {
+            list-style: none;
+            display: flex;
+            align-items: center;
+            gap: 0.75rem;
+        }
+
+        #overrides button {
+            height: 1.6rem;
+            font-size: 0.8rem;
+        }
+    </style>
+</head>
+<body>
+    <div class="header">
+        <h1>⚙️ 设置</h1>
+        <p>编辑 AI 聊天的提示词、上下文与界面选项，保存到对应的配置文件 · <a href="cdv-crate-overview.html">← 所有包概览</a></p>
+    </div>
+
+    <form id="settings" novalidate>
+        <fieldset>
+            <legend>配置文件 Config files</legend>
+            <ul id="layers"></ul>
+        </fieldset>
+
+        <fieldset>
+            <legend>提示词 Prompts</legend>
+            <label class="field" data-field="prompts.system"><span>系统提示词<code>prompts.system</code></span><textarea name="prompts.system"></textarea></label>
+            <label class="field" data-field="prompts.environment_template"><span>环境模板<code>prompts.environment_template</code></span><textarea name="prompts.environment_template"></textarea></label>
+            <label class="field" data-field="prompts.fallback_language"><span>备选回答语言<code>prompts.fallback_language</code></span><input type="text" name="prompts.fallback_language"></label>
+        </fieldset>
+
+        <fieldset>
+            <legend>上下文 Context</legend>
+            <label class="field" data-field="context.history_window"><span>历史轮次<code>context.history_window</code></span><input type="number" min="1" name="context.history_window"></label>
+            <label class="field" data-field="context.page_tokens_budget"><span>页面摘要 Token 预算<code>context.page_tokens_budget</code></span><input type="number" min="1" name="context.page_tokens_budget"></label>
+            <label class="field" data-field="context.debounce_ms"><span>选区去抖 (ms)<code>context.debounce_ms</code></span><input type="number" min="1" name="context.debounce_ms"></label>
+            <label class="field" data-field="context.retrieval_top_k"><span>检索片段数（0 关闭）<code>context.retrieval_top_k</code></span><input type="number" min="0" name="context.retrieval_top_k"></label>
+            <label class="field" data-field="context.retrieval_tokens_budget"><span>检索 Token 预算<code>context.retrieval_tokens_budget</code></span><input type="number" min="1" name="context.retrieval_tokens_budget"></label>
+            <div class="field patterns" data-field="context.sanitize_patterns">
+                <span>清洗规则<code>context.sanitize_patterns</code></span>
+                <div>
+                    <div id="patterns"></div>
+                    <button type="button" id="add-pattern">添加规则</button>
+                </div>
+            </div>
+        </fieldset>
+
+        <fieldset>
+            <legend>界面 UI</legend>
+            <label class="field" data-field="ui.language"><span>界面语言<code>ui.language</code></span><input type="text" name="ui.language"></label>
+            <label class="field" data-field="ui.show_context_preview"><span>展开上下文预览<code>ui.show_context_preview</code></span><input type="checkbox" name="ui.show_context_preview"></label>
+            <label class="field" data-field="ui.allow_prompt_edit"><span>允许编辑系统提示词<code>ui.allow_prompt_edit</code></span><input type="checkbox" name="ui.allow_prompt_edit"></label>
+        </fieldset>
+
+        <fieldset>
+            <legend>浏览器覆盖 Browser overrides</legend>
+            <ul id="overrides"></ul>
+        </fieldset>
+    </form>
+
+    <div class="actions">
+        <button type="submit" form="settings" class="primary">保存 Save</button>
+        <button type="button" id="reload">还原 Revert</button>
+        <span id="status"></span>
+    </div>
+
+    <script>
+    (function() {
+        var API = '/cdv-api/config';
+        var CHAT_STATE = '/cdv-api/state/chat';
+        var OVERRIDES = {
+            'cdv.ai.api_key': 'API Key',
+            'cdv.ai.model': 'Model',
+            'cdv.ai.profile': 'Profile',
+            'cdv.ai.system_prompt': 'System prompt'
+        };
+        var NUMBERS = ['history_window', 'page_tokens_budget', 'debounce_ms', 'retrieval_top_k', 'retrieval_tokens_budget'];
+        var form = document.getElementById('settings');
+        var status = document.getElementById('status');
+        var patterns = document.getElementById('patterns');
+
+        function field(name) {
+            return form.querySelector('[name="' + name + '"]');
+        }
+
+        function setStatus(text, failed) {
+            status.textContent = text;
+            status.className = failed ? 'failed' : '';
+        }
+
+        function addPattern(pattern) {
+            var row = document.createElement('div');
+            row.className = 'row';
+            var regex = document.createElement('input');
+            regex.type = 'text';
+            regex.placeholder = 'regex';
+            regex.value = pattern.regex || '';
+            var replacement = document.createElement('input');
+            replacement.type = 'text';
+            replacement.placeholder = 'replacement';
+            replacement.value = pattern.replacement || '';
+            var remove = document.createElement('button');
+            remove.type = 'button';
+            remove.textContent = '删除';
+            remove.addEventListener('click', function() { row.remove(); });
+            row.appendChild(regex);
+            row.appendChild(replacement);
+            row.appendChild(remove);
+            patterns.appendChild(row);
+        }
+
+        function render(snapshot) {
+            var settings = snapshot.settings;
+            ['system', 'environment_template', 'fallback_language'].forEach(function(key) {
+                field('prompts.' + key).value = settings.prompts[key];
+            });
+            NUMBERS.forEach(function(key) {
+                field('context.' + key).value = settings.context[key];
+            });
+            field('ui.language').value = settings.ui.language;
+            field('ui.show_context_preview').checked = !!settings.ui.show_context_preview;
+            field('ui.allow_prompt_edit').checked = !!settings.ui.allow_prompt_edit;
+            patterns.innerHTML = '';
+            settings.context.sanitize_patterns.forEach(addPattern);
+
+            Array.prototype.forEach.call(form.querySelectorAll('[data-field]'), function(el) {
+                var label = el.querySelector('span');
+                var badge = label.querySelector('.source');
+                if (!badge) {
+                    badge = document.createElement('span');
+                    label.appendChild(badge);
+                }
+                var source = snapshot.sources[el.getAttribute('data-field')];
+                badge.className = 'source' + (source ? ' layer' : '');
+                badge.textContent = source ? ('来自 ' + source) : '默认值';
+            });
+
+            var layers = document.getElementById('layers');
+            layers.innerHTML = '';
+            if (!snapshot.layers.length) {
+                layers.innerHTML = '<li>使用内置默认值，保存时会创建全局配置文件</li>';
+            }
+            snapshot.layers.forEach(function(layer) {
+                var item = document.createElement('li');
+                item.textContent = layer.label + ': ' + layer.path;
+                layers.appendChild(item);
+            });
+            clearErrors();
+        }
+
+        function collect() {
+            var context = { sanitize_patterns: [] };
+            NUMBERS.forEach(function(key) {
+                var value = field('context.' + key).value.trim();
+                context[key] = value === '' ? 0 : Number(value);
+            });
+            Array.prototype.forEach.call(patterns.querySelectorAll('.row'), function(row) {
+                var inputs = row.querySelectorAll('input');
+                context.sanitize_patterns.push({ regex: inputs[0].value, replacement: inputs[1].value });
+            });
+            return {
+                prompts: {
+                    system: field('prompts.system').value,
+                    environment_template: field('prompts.environment_template').value,
+                    fallback_language: field('prompts.fallback_language').value
+                },
+                context: context,
+                ui: {
+                    language: field('ui.language').value,
+                    show_context_preview: field('ui.show_context_preview').checked,
+                    allow_prompt_edit: field('ui.allow_prompt_edit').checked
+                }
+            };
+        }
+
+        function clearErrors() {
+            Array.prototype.forEach.call(form.querySelectorAll('.invalid'), function(el) {
+                el.classList.remove('invalid');
+            });
+            Array.prototype.forEach.call(form.querySelectorAll('.error'), function(el) {
+                el.remove();
+            });
+        }
+
+        function showProblems(problems) {
+            problems.forEach(function(problem) {
+                // `context.sanitize_patterns[2].regex` belongs to the list.
+                var name = problem.field.replace(/\[\d+\].*$/, '');
+                var el = form.querySelector('[data-field="' + name + '"]');
+                if (!el) return;
+                el.classList.add('invalid');
+                var message = document.createElement('div');
+                message.className = 'error';
+                message.textContent = problem.field + ': ' + problem.message;
+                el.appendChild(message);
+            });
+        }
+
+        function load() {
+            setStatus('加载中…');
+            return fetch(API, { cache: 'no-store' })
+                .then(function(res) { return res.json(); })
+                .then(function(body) {
+                    if (body.error) throw new Error(body.error.message);
+                    render(body);
+                    setStatus('');
+                })
+                .catch(function(err) { setStatus('无法读取配置：' + err.message, true); });
+        }
+
+        form.addEventListener('submit', function(event) {
+            event.preventDefault();
+            clearErrors();
+            setStatus('保存中…');
+            fetch(API, {
+                method: 'PUT',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(collect())
+            })
+                .then(function(res) { return res.json(); })
+                .then(function(body) {
+                    if (body.error) {
+                        showProblems(body.error.problems || []);
+                        throw new Error(body.error.message);
+                    }
+                    render(body);
+                    setStatus(body.written.length ? ('已保存到 ' + body.written.join(', ')) : '没有需要保存的修改');
+                })
+                .catch(function(err) { setStatus('保存失败：' + err.message, true); });
+        });
+
+        document.getElementById('add-pattern').addEventListener('click', function() {
+            addPattern({ regex: '', replacement: '[redacted]' });
+        });
+        document.getElementById('reload').addEventListener('click', load);
+
+        // Served pages keep panel overrides in the server's state store; the
+        // API key and anything saved from a file:// page stay in localStorage.
+        var stored = {};
+
+        function loadOverrides() {
+            fetch(CHAT_STATE, { cache: 'no-store' })
+                .then(function(res) { return res.ok ? res.json() : {}; })
+                .catch(function() { return {}; })
+                .then(function(values) { stored = values || {}; renderOverrides(); });
+        }
+
+        function clearOverride(key) {
+            try { localStorage.removeItem(key); } catch (_) {}
+            if (!Object.prototype.hasOwnProperty.call(stored, key)) {
+                renderOverrides();
+                return;
+            }
+            var change = {};
+            change[key] = null;
+            fetch(CHAT_STATE, {
+                method: 'PATCH',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(change)
+            })
+                .then(function(res) { return res.ok ? res.json() : stored; })
+                .catch(function() { return stored; })
+                .then(function(values) { stored = values || {}; renderOverrides(); });
+        }
+
+        function renderOverrides() {
+            var list = document.getElementById('overrides');
+            list.innerHTML = '';
+            Object.keys(OVERRIDES).forEach(function(key) {
+                var value = typeof stored[key] === 'string' ? stored[key] : null;
+                if (!value) {
+                    try { value = localStorage.getItem(key); } catch (_) {}
+                }
+                if (!value) return;
+                var item = document.createElement('li');
+                var shown = key === 'cdv.ai.api_key' ? '已保存' : (value.length > 60 ? value.slice(0, 60) + '…' : value);
+                item.textContent = OVERRIDES[key] + ': ' + shown;
+                var clear = document.createElement('button');
+                clear.type = 'button';
+                clear.textContent = '清除';
+                clear.addEventListener('click', function() { clearOverride(key); });
+                item.appendChild(clear);
+                list.appendChild(item);
+            });
+            if (!list.children.length) {
+                list.innerHTML = '<li>聊天面板没有保存覆盖值，使用配置文件中的设置</li>';
+            }
+        }
+
+        renderOverrides();
+        loadOverrides();
+        load();
+    })();
+    </script>
+</body>
+</html>"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_values_only() {
+        let before: serde_yaml::Value = serde_yaml::from_str(
+            "history_window: 6\nsanitize_patterns:\n  - regex: a\n    replacement: b\nretrieval_top_k: 5\n",
+        )
+        .unwrap();
+        let after: serde_yaml::Value = serde_yaml::from_str(
+            "history_window: 8\nsanitize_patterns:\n  - regex: a\n    replacement: c\nretrieval_top_k: 5\n",
+        )
+        .unwrap();
+        let mut changes = Vec::new();
+        diff(
+            &before,
+            after,
+            &mut vec!["context".to_string()],
+            &mut changes,
+        );
+
+        let paths: Vec<String> = changes.iter().map(|(path, _)| path.join(".")).collect();
+        assert_eq!(
+            paths,
+            ["context.history_window", "context.sanitize_patterns"]
+        );
+        assert_eq!(changes[0].1, serde_yaml::Value::from(8));
+    }
+}
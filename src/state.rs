@@ -0,0 +1,227 @@
+//! Browser state kept by the server.
+//!
+//! Pages served over HTTP store search history, outline expansion, focus mode
+//! and chat panel preferences here instead of `localStorage`, whose contents
+//! are lost whenever the port (and so the origin) changes. Each namespace is a
+//! flat JSON object in `~/.cargo-doc-viewer/state/<namespace>.json`, served at
+//! `/cdv-api/state/<namespace>` and inlined into pages so scripts can read it
+//! synchronously.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use serde_json::{Map, Value};
+
+use crate::config;
+use crate::files;
+
+/// Prefix of the per-namespace routes served by `server.rs`.
+pub const STATE_PATH: &str = "/cdv-api/state/";
+
+/// Upper bound for one namespace file, so a runaway script cannot fill the
+/// disk through the API.
+const MAX_NAMESPACE_BYTES: usize = 512 * 1024;
+
+pub type Namespace = Map<String, Value>;
+
+pub struct StateStore {
+    dir: PathBuf,
+    /// Serialises changes made through this store. Files are read afresh
+    /// every time, since servers on other ports may share the directory.
+    lock: Mutex<()>,
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    InvalidNamespace,
+    TooLarge,
+    Io(io::Error),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidNamespace => f.write_str("invalid state namespace"),
+            StateError::TooLarge => f.write_str("state namespace too large"),
+            StateError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for StateError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl StateStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// `state/` next to the global config file.
+    pub fn default_dir() -> PathBuf {
+        let config = config::config_path();
+        config
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("state")
+    }
+
+    pub fn get(&self, namespace: &str) -> Result<Namespace, StateError> {
+        check_namespace(namespace)?;
+        self.load(namespace)
+    }
+
+    /// Replaces a namespace.
+    pub fn put(&self, namespace: &str, values: Namespace) -> Result<Namespace, StateError> {
+        check_namespace(namespace)?;
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.store(namespace, values)
+    }
+
+    /// Merges `changes` into a namespace as it is on disk; `null` removes a
+    /// key.
+    pub fn patch(&self, namespace: &str, changes: Namespace) -> Result<Namespace, StateError> {
+        check_namespace(namespace)?;
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut values = self.load(namespace)?;
+        for (key, value) in changes {
+            if value.is_null() {
+                values.remove(&key);
+            } else {
+                values.insert(key, value);
+            }
+        }
+        self.store(namespace, values)
+    }
+
+    /// Every stored namespace, for inlining into served pages.
+    pub fn all(&self) -> Result<BTreeMap<String, Namespace>, StateError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut all = BTreeMap::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(namespace) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            if check_namespace(namespace).is_ok() {
+                all.insert(namespace.to_string(), self.load(namespace)?);
+            }
+        }
+        Ok(all)
+    }
+
+    /// `window.__CDV_STATE__ = {...};` for pages served over HTTP.
+    pub fn script_assignment(&self) -> String {
+        let all = self.all().unwrap_or_else(|err| {
+            eprintln!("Warning: Failed to read browser state: {err}");
+            BTreeMap::new()
+        });
+        let json = serde_json::to_string(&all).unwrap_or_else(|_| "{}".to_string());
+        format!(
+            "window.__CDV_STATE__ = {};",
+            config::escape_json_for_script(&json)
+        )
+    }
+
+    fn load(&self, namespace: &str) -> Result<Namespace, StateError> {
+        match fs::read(self.file(namespace)) {
+            // A damaged file starts over rather than failing every page.
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes).unwrap_or_default()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Namespace::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn store(&self, namespace: &str, values: Namespace) -> Result<Namespace, StateError> {
+        let bytes = serde_json::to_vec_pretty(&values).map_err(io::Error::other)?;
+        if bytes.len() > MAX_NAMESPACE_BYTES {
+            return Err(StateError::TooLarge);
+        }
+        files::write_atomic(&self.file(namespace), &bytes)?;
+        Ok(values)
+    }
+
+    fn file(&self, namespace: &str) -> PathBuf {
+        self.dir.join(format!("{namespace}.json"))
+    }
+}
+
+/// Namespaces become file names.
+fn check_namespace(namespace: &str) -> Result<(), StateError> {
+    if files::is_plain_name(namespace) {
+        Ok(())
+    } else {
+        Err(StateError::InvalidNamespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_persist_and_null_removes_keys() {
+        let dir = std::env::temp_dir().join(format!("cdv-state-{}", std::process::id()));
+        let store = StateStore::new(dir.clone());
+        assert!(store.get("ui").unwrap().is_empty());
+
+        let changes = serde_json::json!({"cdv.focus": "1", "cdv.focusWidth": "wide"});
+        store
+            .patch("ui", changes.as_object().unwrap().clone())
+            .unwrap();
+        let changes = serde_json::json!({"cdv.focusWidth": null});
+        store
+            .patch("ui", changes.as_object().unwrap().clone())
+            .unwrap();
+
+        let reopened = StateStore::new(dir.clone());
+        let all = reopened.all().unwrap();
+        assert_eq!(
+            Value::Object(all["ui"].clone()),
+            serde_json::json!({"cdv.focus": "1"})
+        );
+        assert!(matches!(
+            reopened.get("../config"),
+            Err(StateError::InvalidNamespace)
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stores_sharing_a_directory_keep_each_others_keys() {
+        let dir = std::env::temp_dir().join(format!("cdv-state-shared-{}", std::process::id()));
+        let first = StateStore::new(dir.clone());
+        let second = StateStore::new(dir.clone());
+        let patch = |store: &StateStore, changes: Value| {
+            store
+                .patch("ui", changes.as_object().unwrap().clone())
+                .unwrap()
+        };
+
+        patch(&first, serde_json::json!({"from.first": 1}));
+        patch(&second, serde_json::json!({"from.second": 2}));
+        patch(&first, serde_json::json!({"from.first": 3}));
+        assert_eq!(
+            Value::Object(second.get("ui").unwrap()),
+            serde_json::json!({"from.first": 3, "from.second": 2})
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
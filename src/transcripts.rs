@@ -0,0 +1,333 @@
+//! Saved chat conversations.
+//!
+//! The panel on served pages saves its conversation after every answer, under
+//! an id it picks, together with the crate and item it was started on and the
+//! context layers sent with each question. Each conversation is one JSON file
+//! in `~/.cargo-doc-viewer/transcripts/`; the panel lists and reopens them, and
+//! `/cdv-api/transcripts/<id>.md` exports one as Markdown.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::files;
+
+/// `GET` lists conversations; `GET`/`PUT <path>/<id>` reads and saves one and
+/// `GET <path>/<id>.md` exports it.
+pub const TRANSCRIPTS_PATH: &str = "/cdv-api/transcripts";
+
+/// Conversations returned by one listing, newest first.
+const MAX_LISTED: usize = 200;
+
+const MAX_TRANSCRIPT_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transcript {
+    pub id: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    /// Path of the documented item, e.g. `hyper::client::Client`.
+    pub item: String,
+    /// Page the conversation was started on.
+    pub page: String,
+    pub title: String,
+    pub model: String,
+    /// RFC 3339 timestamps written by the panel.
+    pub created: String,
+    pub updated: String,
+    pub turns: Vec<Turn>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Turn {
+    pub question: String,
+    pub answer: String,
+    pub context: TurnContext,
+}
+
+/// The layers the panel sent along with a question.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TurnContext {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub system: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub environment: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub selection: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub retrieved: String,
+}
+
+/// One entry of a listing.
+#[derive(Debug, Serialize)]
+pub struct TranscriptSummary {
+    pub id: String,
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub item: String,
+    pub page: String,
+    pub title: String,
+    pub updated: String,
+    pub turns: usize,
+}
+
+#[derive(Debug)]
+pub enum TranscriptError {
+    InvalidId,
+    TooLarge,
+    Io(io::Error),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscriptError::InvalidId => f.write_str("invalid transcript id"),
+            TranscriptError::TooLarge => f.write_str("transcript too large"),
+            TranscriptError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for TranscriptError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+pub struct TranscriptStore {
+    dir: PathBuf,
+}
+
+impl TranscriptStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `transcripts/` next to the global config file.
+    pub fn default_dir() -> PathBuf {
+        let config = config::config_path();
+        config
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("transcripts")
+    }
+
+    /// Saved conversations, newest first, optionally limited to a crate and
+    /// a page. Unreadable files are skipped.
+    pub fn list(
+        &self,
+        krate: Option<&str>,
+        page: Option<&str>,
+    ) -> Result<Vec<TranscriptSummary>, TranscriptError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut listed = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(transcript) = fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Transcript>(&bytes).ok())
+            else {
+                continue;
+            };
+            if krate.is_some_and(|krate| transcript.krate != krate)
+                || page.is_some_and(|page| transcript.page != page)
+            {
+                continue;
+            }
+            listed.push(TranscriptSummary {
+                turns: transcript.turns.len(),
+                id: transcript.id,
+                krate: transcript.krate,
+                item: transcript.item,
+                page: transcript.page,
+                title: transcript.title,
+                updated: transcript.updated,
+            });
+        }
+        listed.sort_by(|a, b| b.updated.cmp(&a.updated).then_with(|| a.id.cmp(&b.id)));
+        listed.truncate(MAX_LISTED);
+        Ok(listed)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Transcript, TranscriptError> {
+        check_id(id)?;
+        let bytes = fs::read(self.file(id))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| TranscriptError::Io(io::Error::new(io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Writes `transcript` under `id`, replacing any earlier version.
+    pub fn save(
+        &self,
+        id: &str,
+        mut transcript: Transcript,
+    ) -> Result<Transcript, TranscriptError> {
+        check_id(id)?;
+        transcript.id = id.to_string();
+        let bytes = serde_json::to_vec_pretty(&transcript).map_err(io::Error::other)?;
+        if bytes.len() > MAX_TRANSCRIPT_BYTES {
+            return Err(TranscriptError::TooLarge);
+        }
+        files::write_atomic(&self.file(id), &bytes)?;
+        Ok(transcript)
+    }
+
+    fn file(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+impl Transcript {
+    /// The conversation as a Markdown document, with the context sent for
+    /// each question folded under it.
+    pub fn to_markdown(&self) -> String {
+        let title = if self.title.is_empty() {
+            "Chat transcript"
+        } else {
+            self.title.as_str()
+        };
+        let mut out = format!("# {title}\n\n");
+        for (label, value) in [
+            ("Crate", &self.krate),
+            ("Item", &self.item),
+            ("Page", &self.page),
+            ("Model", &self.model),
+            ("Started", &self.created),
+            ("Updated", &self.updated),
+        ] {
+            if value.is_empty() {
+                continue;
+            }
+            if matches!(label, "Crate" | "Item" | "Model") {
+                out.push_str(&format!("- {label}: `{value}`\n"));
+            } else {
+                out.push_str(&format!("- {label}: {value}\n"));
+            }
+        }
+
+        for (index, turn) in self.turns.iter().enumerate() {
+            let number = index + 1;
+            out.push_str(&format!(
+                "\n## Question {number}\n\n{}\n",
+                turn.question.trim()
+            ));
+            let context = &turn.context;
+            let layers = [
+                ("System prompt", &context.system),
+                ("Environment", &context.environment),
+                ("Page summary", &context.summary),
+                ("Selection", &context.selection),
+                ("Related documentation", &context.retrieved),
+            ];
+            if layers.iter().any(|(_, text)| !text.is_empty()) {
+                out.push_str("\n<details>\n<summary>Context sent with this question</summary>\n");
+                for (label, text) in layers {
+                    if !text.is_empty() {
+                        out.push_str(&format!("\n#### {label}\n\n{}", fenced(text)));
+                    }
+                }
+                out.push_str("\n</details>\n");
+            }
+            out.push_str(&format!("\n## Answer {number}\n\n{}\n", turn.answer.trim()));
+        }
+        out
+    }
+}
+
+/// `text` in a code fence longer than any backtick run inside it.
+fn fenced(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}text\n{}\n{fence}\n", text.trim_end())
+}
+
+fn check_id(id: &str) -> Result<(), TranscriptError> {
+    if files::is_plain_name(id) {
+        Ok(())
+    } else {
+        Err(TranscriptError::InvalidId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(krate: &str, page: &str, updated: &str) -> Transcript {
+        Transcript {
+            krate: krate.to_string(),
+            item: format!("{krate}::client::Client"),
+            page: page.to_string(),
+            title: "How is the pool shared?".to_string(),
+            model: "gpt-4.1-mini".to_string(),
+            created: "2026-10-18T09:00:00.000Z".to_string(),
+            updated: updated.to_string(),
+            turns: vec![Turn {
+                question: "How is the pool shared?".to_string(),
+                answer: "Clones share one pool.".to_string(),
+                context: TurnContext {
+                    summary: "A Client holds a pool. ```rust\nlet c = Client::new();\n```"
+                        .to_string(),
+                    ..TurnContext::default()
+                },
+            }],
+            ..Transcript::default()
+        }
+    }
+
+    #[test]
+    fn saves_lists_and_exports_transcripts() {
+        let dir = std::env::temp_dir().join(format!("cdv-transcripts-{}", std::process::id()));
+        let store = TranscriptStore::new(dir.clone());
+        let page = "/hyper/client/struct.Client.html";
+        store
+            .save("a1", transcript("hyper", page, "2026-10-18T09:05:00.000Z"))
+            .unwrap();
+        store
+            .save("b2", transcript("hyper", page, "2026-10-18T10:00:00.000Z"))
+            .unwrap();
+        store
+            .save(
+                "c3",
+                transcript("serde", "/serde/index.html", "2026-10-18T11:00:00.000Z"),
+            )
+            .unwrap();
+
+        let listed = store.list(Some("hyper"), None).unwrap();
+        let ids: Vec<&str> = listed.iter().map(|entry| entry.id.as_str()).collect();
+        assert_eq!(ids, ["b2", "a1"]);
+        assert_eq!(listed[0].turns, 1);
+        assert_eq!(
+            store.list(None, Some("/serde/index.html")).unwrap().len(),
+            1
+        );
+        assert!(matches!(
+            store.save("../x", Transcript::default()),
+            Err(TranscriptError::InvalidId)
+        ));
+
+        let markdown = store.get("a1").unwrap().to_markdown();
+        assert!(markdown.starts_with("# How is the pool shared?\n\n- Crate: `hyper`\n"));
+        assert!(markdown.contains("## Question 1\n\nHow is the pool shared?\n"));
+        assert!(markdown.contains("#### Page summary\n\n````text\nA Client holds a pool."));
+        assert!(markdown.contains("## Answer 1\n\nClones share one pool.\n"));
+        assert!(!markdown.contains("#### System prompt"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! `serve --watch`: rebuild docs when sources change and tell open pages to
+//! reload through the [`EventBus`]. `serve` also always watches the config
+//! files and reloads them in place.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task;
+
+use crate::config;
+use crate::docgen;
+use crate::events::{EventBus, ServerEvent};
+
+/// Quiet period after the last change before `cargo doc` is rerun.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub fn spawn(
+    project_dir: PathBuf,
+    cargo_args: Vec<String>,
+    events: EventBus,
+) -> notify::Result<()> {
+    let targets = docgen::watch_targets(&project_dir);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res
+            && is_relevant(&event)
+        {
+            let _ = tx.send(());
+        }
+    })?;
+    for target in &targets {
+        let mode = if target.is_dir() {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(target, mode)?;
+    }
+
+    println!("Watching for changes:");
+    for target in &targets {
+        println!("  {}", target.display());
+    }
+
+    tokio::spawn(async move {
+        // The watcher stops delivering events once dropped.
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            loop {
+                match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+            rebuild(&project_dir, &cargo_args, &events).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// Reloads the config whenever one of [`config::watched_files`] changes.
+///
+/// The parent directories are watched rather than the files, so files that do
+/// not exist yet are picked up and editors that save by renaming still count.
+pub fn spawn_config(events: EventBus) -> notify::Result<()> {
+    let files = config::watched_files();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let watched = files.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res
+            && is_relevant(&event)
+            && event.paths.iter().any(|path| watched.contains(path))
+        {
+            let _ = tx.send(());
+        }
+    })?;
+    let mut dirs: Vec<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+    dirs.sort();
+    dirs.dedup();
+    for dir in dirs {
+        if dir.is_dir() {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+    }
+
+    tokio::spawn(async move {
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            loop {
+                match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+            match task::spawn_blocking(config::reload).await {
+                Ok(Ok(())) => {
+                    println!("Config changed; reloaded.");
+                    config::report_layers();
+                    events.publish(ServerEvent::ConfigUpdated);
+                }
+                Ok(Err(err)) => eprintln!("Config change ignored: {err}"),
+                Err(err) => eprintln!("Config reload task failed: {err}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn rebuild(project_dir: &Path, cargo_args: &[String], events: &EventBus) {
+    println!("Change detected; running `cargo doc`...");
+    events.publish(ServerEvent::Rebuilding);
+
+    let dir = project_dir.to_path_buf();
+    let args = cargo_args.to_vec();
+    match task::spawn_blocking(move || docgen::cargo_doc(&dir, &args)).await {
+        Ok(Ok(status)) if status.success() => {
+            println!("`cargo doc` completed; reloading open pages.");
+            events.publish(ServerEvent::Reload);
+        }
+        Ok(Ok(status)) => {
+            eprintln!("`cargo doc` failed with status: {status}");
+            events.publish(ServerEvent::RebuildFailed);
+        }
+        Ok(Err(err)) => {
+            eprintln!("Failed to run `cargo doc`: {err}");
+            events.publish(ServerEvent::RebuildFailed);
+        }
+        Err(err) => {
+            eprintln!("Rebuild task failed: {err}");
+            events.publish(ServerEvent::RebuildFailed);
+        }
+    }
+}
+
+fn is_relevant(event: &notify::Event) -> bool {
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return false;
+    }
+    event.paths.iter().any(|path| !is_scratch_file(path))
+}
+
+/// Editor swap and backup files that should not trigger a rebuild.
+fn is_scratch_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    name.ends_with('~') || name.ends_with(".swp") || name.ends_with(".swx") || name == "4913"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_editor_scratch_files() {
+        assert!(is_scratch_file(Path::new("src/.lib.rs.swp")));
+        assert!(is_scratch_file(Path::new("src/lib.rs~")));
+        assert!(!is_scratch_file(Path::new("src/lib.rs")));
+    }
+}